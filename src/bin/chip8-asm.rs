@@ -0,0 +1,65 @@
+//! This is an assembler for chip 8 programs.
+//! It reads source code written in the classic mnemonics and writes a .ch8 program that the emulator can run,
+//!  see the assembler module for the syntax it accepts.
+//!
+//! Run it with "cargo run --bin chip8-asm -- /path/to/source.asm". Options can be given before or after the source path:
+//!  -o <file>    where to write the program, by default it is the source path with a .ch8 extension.
+
+#![allow(clippy::needless_return)]
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process;
+use chip_8_emulator::chip_8::assembler;
+
+///The options that were given to the assembler on the command line.
+struct Options {
+    source_path: PathBuf,
+    output_path: PathBuf
+}
+
+///reads the command line arguments into an Options struct.
+///any problem with the arguments is returned as an Err() with a message for the user.
+fn parse_arguments(args: &[String]) -> Result<Options, String> {
+    let mut source_path = None;
+    let mut output_path = None;
+    let mut arguments = args.iter().skip(1);
+    while let Some(argument) = arguments.next() {
+	match argument.as_str() {
+	    "-o" => output_path = Some(PathBuf::from(arguments.next().ok_or("-o needs the path of the file to write")?)),
+	    _ if argument.starts_with('-') => return Err(format!("unknown option {}", argument)),
+	    _ => source_path = Some(PathBuf::from(argument))
+	}
+    }
+    let source_path: PathBuf = source_path.ok_or("source file location not provided, provide path to the source as an argument to assemble it")?;
+    return Ok(Options {
+	output_path: output_path.unwrap_or(source_path.with_extension("ch8")),
+	source_path
+    });
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    let options = match parse_arguments(&args) {
+	Ok(options) => options,
+	Err(message) => {
+	    eprintln!("{}", message);
+	    eprintln!("usage: chip8-asm [-o <program.ch8>] <source.asm>");
+	    process::exit(1);
+	}
+    };
+
+    let program = match assembler::assemble_file(&options.source_path) {
+	Ok(program) => program,
+	Err(error) => {
+	    eprintln!("error: {}", error);
+	    process::exit(1);
+	}
+    };
+    if let Err(error) = fs::write(&options.output_path, &program) {
+	eprintln!("unable to write {}: {}", options.output_path.display(), error);
+	process::exit(1);
+    }
+    println!("wrote {} bytes to {}", program.len(), options.output_path.display());
+}
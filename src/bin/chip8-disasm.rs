@@ -0,0 +1,63 @@
+//! This is a disassembler for chip 8 programs.
+//! It reads a .ch8 program and prints it as assembly, with the code and the sprite data separated and labels for the jump and call targets.
+//!
+//! Run it with "cargo run --bin chip8-disasm -- /path/to/.ch8/program". Options can be given before or after the program path:
+//!  --octo      prints the program in Octo's syntax instead of the classic mnemonics.
+//!  --xochip    reads F000 NNNN as the four byte XO-CHIP instruction.
+
+#![allow(clippy::needless_return)]
+
+use std::env;
+use std::fs;
+use std::process;
+use chip_8_emulator::chip_8::disassembler::{self, Syntax};
+
+///The options that were given to the disassembler on the command line.
+struct Options {
+    program_path: String,
+    syntax: Syntax,
+    xo_chip: bool
+}
+
+///reads the command line arguments into an Options struct.
+///any problem with the arguments is returned as an Err() with a message for the user.
+fn parse_arguments(args: &[String]) -> Result<Options, String> {
+    let mut program_path = None;
+    let mut syntax = Syntax::Classic;
+    let mut xo_chip = false;
+    for argument in args.iter().skip(1) {
+	match argument.as_str() {
+	    "--octo" => syntax = Syntax::Octo,
+	    "--xochip" => xo_chip = true,
+	    _ if argument.starts_with("--") => return Err(format!("unknown option {}", argument)),
+	    _ => program_path = Some(argument.clone())
+	}
+    }
+    return Ok(Options {
+	program_path: program_path.ok_or("program file location not provided, provide path to .ch8 program as an argument to disassemble it")?,
+	syntax,
+	xo_chip
+    });
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    let options = match parse_arguments(&args) {
+	Ok(options) => options,
+	Err(message) => {
+	    eprintln!("{}", message);
+	    eprintln!("usage: chip8-disasm [--octo] [--xochip] <program.ch8>");
+	    process::exit(1);
+	}
+    };
+    let program = match fs::read(&options.program_path) {
+	Ok(program) => program,
+	Err(error) => {
+	    eprintln!("unable to read {}: {}", options.program_path, error);
+	    process::exit(1);
+	}
+    };
+
+    let disassembly = disassembler::disassemble(&program, options.xo_chip);
+    print!("{}", disassembly.to_text(options.syntax));
+}
@@ -0,0 +1,689 @@
+//! This module is an assembler for chip 8 programs, it turns source code written in the classic mnemonics into a .ch8 program.
+//! It reads the same syntax the disassembler prints, so a disassembled program can be assembled back into the same bytes.
+//!
+//! Each line can have a label, then an instruction or a directive, then a comment starting with ';':
+//!  loop:   DRW V0, V1, 5     ; draw the sprite
+//! Numbers can be decimal (42), hexadecimal (0x2a) or binary (0b101010), and anywhere a number goes you can also use a label or a constant,
+//!  added or subtracted together (sprites + 5).
+//! The directives are:
+//!  name EQU value        defines a constant. The value can only use labels and constants defined above it.
+//!  DB 0x3c, 0x42, "hi"   puts bytes (and the characters of strings) into the program.
+//!  DW 0x1234             puts 16 bit words into the program, high byte first.
+//!  INCLUDE "file.asm"    assembles another file at this point, the path is relative to the file that includes it.
+//! Mnemonics, registers and directives can be upper or lower case, labels and constants are case sensitive.
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use crate::chip_8::disassembler::{Instruction, PROGRAM_START};
+
+///An error in the source code, with the file, line and column it was found at. Lines and columns start at 1.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AssemblyError {
+    pub file: String,
+    pub line: usize,
+    pub column: usize,
+    pub message: String
+}
+
+impl fmt::Display for AssemblyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+	if self.line == 0 {
+	    return write!(f, "{}: {}", self.file, self.message); //errors about the whole file, like not being able to read it.
+	}
+	return write!(f, "{}:{}:{}: {}", self.file, self.line, self.column, self.message);
+    }
+}
+
+impl Error for AssemblyError {}
+
+///an error found somewhere on the current line, the column it is at and the message.
+type LineError = (usize, String);
+
+#[derive(Clone, Debug, PartialEq)]
+enum TokenKind {
+    Identifier(String),
+    Number(i64),
+    Text(String),
+    Comma,
+    Colon,
+    Plus,
+    Minus,
+    OpenBracket,
+    CloseBracket
+}
+
+#[derive(Clone, Debug)]
+struct Token {
+    kind: TokenKind,
+    column: usize
+}
+
+///splits a line into tokens, stopping at a comment.
+fn tokenize(line: &str) -> Result<Vec<Token>, LineError> {
+    let characters = line.chars().collect::<Vec<char>>();
+    let mut tokens = Vec::new();
+    let mut position = 0;
+    while position < characters.len() {
+	let character = characters[position];
+	let column = position + 1;
+	let single = match character {
+	    ',' => Some(TokenKind::Comma),
+	    ':' => Some(TokenKind::Colon),
+	    '+' => Some(TokenKind::Plus),
+	    '-' => Some(TokenKind::Minus),
+	    '[' => Some(TokenKind::OpenBracket),
+	    ']' => Some(TokenKind::CloseBracket),
+	    _ => None
+	};
+	if let Some(kind) = single {
+	    tokens.push(Token { kind, column });
+	    position += 1;
+	} else if character == ';' {
+	    break;
+	} else if character.is_whitespace() {
+	    position += 1;
+	} else if character == '"' {
+	    let end = characters[(position + 1)..].iter().position(|c| *c == '"').ok_or((column, "this string is never closed".to_string()))?;
+	    let text = characters[(position + 1)..(position + 1 + end)].iter().collect::<String>();
+	    tokens.push(Token { kind: TokenKind::Text(text), column });
+	    position += end + 2;
+	} else if character.is_alphanumeric() || character == '_' || character == '.' {
+	    let length = characters[position..].iter().take_while(|c| c.is_alphanumeric() || **c == '_' || **c == '.').count();
+	    let word = characters[position..(position + length)].iter().collect::<String>();
+	    let kind = if character.is_ascii_digit() {
+		TokenKind::Number(parse_number(&word).ok_or((column, format!("\"{}\" is not a number", word)))?)
+	    } else {
+		TokenKind::Identifier(word)
+	    };
+	    tokens.push(Token { kind, column });
+	    position += length;
+	} else {
+	    return Err((column, format!("unexpected character '{}'", character)));
+	}
+    }
+    return Ok(tokens);
+}
+
+///reads a decimal, 0x hexadecimal or 0b binary number.
+fn parse_number(word: &str) -> Option<i64> {
+    let lowercase = word.to_ascii_lowercase();
+    if let Some(hex) = lowercase.strip_prefix("0x") {
+	return i64::from_str_radix(hex, 16).ok();
+    }
+    if let Some(binary) = lowercase.strip_prefix("0b") {
+	return i64::from_str_radix(binary, 2).ok();
+    }
+    return lowercase.parse::<i64>().ok();
+}
+
+#[derive(Clone, Debug)]
+enum Term {
+    Number(i64),
+    Symbol(String, usize)
+}
+
+///numbers, labels and constants added and subtracted together. The bool of each term is true if it is subtracted.
+#[derive(Clone, Debug)]
+struct Expression {
+    terms: Vec<(bool, Term)>,
+    column: usize
+}
+
+///An operand of an instruction. Everything other than a register or a value is one of the special names like I, DT or [I].
+#[derive(Clone, Debug)]
+enum Operand {
+    Register(usize),
+    Index,
+    IndexAddress,
+    DelayTimer,
+    SoundTimer,
+    Key,
+    Font,
+    BigFont,
+    Bcd,
+    Flags,
+    Long(Expression),
+    Value(Expression)
+}
+
+///An item of a DB directive.
+#[derive(Clone, Debug)]
+enum DataItem {
+    Byte(Expression),
+    Text(String)
+}
+
+#[derive(Clone, Debug)]
+enum StatementKind {
+    Instruction { mnemonic: String, column: usize, operands: Vec<Operand> },
+    Bytes(Vec<DataItem>),
+    Words(Vec<Expression>)
+}
+
+///A line of the program that puts bytes into it. The first pass collects these and the labels, and the second pass turns them into bytes.
+#[derive(Clone, Debug)]
+struct Statement {
+    file: String,
+    line: usize,
+    kind: StatementKind
+}
+
+///A defined label or constant, and where it was defined.
+struct Symbol {
+    value: i64,
+    file: String,
+    line: usize
+}
+
+///Goes through the tokens of a single line.
+struct Cursor <'t> {
+    tokens: &'t [Token],
+    position: usize,
+    end_column: usize
+}
+
+impl <'t> Cursor <'t> {
+    fn peek(&self) -> Option<&'t TokenKind> {
+	return self.tokens.get(self.position).map(|token| &token.kind);
+    }
+
+    fn next(&mut self) -> Option<&'t Token> {
+	let token = self.tokens.get(self.position);
+	self.position += 1;
+	return token;
+    }
+
+    ///the column of the next token, or just past the end of the line if there are no tokens left.
+    fn column(&self) -> usize {
+	return self.tokens.get(self.position).map_or(self.end_column, |token| token.column);
+    }
+
+    fn at_end(&self) -> bool {
+	return self.position >= self.tokens.len();
+    }
+
+    ///moves past a comma, returning false if there is no comma next.
+    fn comma(&mut self) -> bool {
+	if self.peek() == Some(&TokenKind::Comma) {
+	    self.position += 1;
+	    return true;
+	}
+	return false;
+    }
+
+    fn expect_end(&self) -> Result<(), LineError> {
+	if !self.at_end() {
+	    return Err((self.column(), "unexpected text at the end of the line".to_string()));
+	}
+	return Ok(());
+    }
+
+    fn expression(&mut self) -> Result<Expression, LineError> {
+	let column = self.column();
+	let mut terms = Vec::new();
+	let mut negative = match self.peek() {
+	    Some(TokenKind::Minus) => {self.position += 1; true},
+	    Some(TokenKind::Plus) => {self.position += 1; false},
+	    _ => false
+	};
+	loop {
+	    let term_column = self.column();
+	    let term = match self.next().map(|token| &token.kind) {
+		Some(TokenKind::Number(number)) => Term::Number(*number),
+		Some(TokenKind::Identifier(name)) => Term::Symbol(name.clone(), term_column),
+		_ => return Err((term_column, "expected a number, label or constant".to_string()))
+	    };
+	    terms.push((negative, term));
+	    negative = match self.peek() {
+		Some(TokenKind::Plus) => false,
+		Some(TokenKind::Minus) => true,
+		_ => break
+	    };
+	    self.position += 1;
+	}
+	return Ok(Expression { terms, column });
+    }
+
+    fn operand(&mut self) -> Result<Operand, LineError> {
+	if self.peek() == Some(&TokenKind::OpenBracket) {
+	    let column = self.column();
+	    self.position += 1;
+	    let is_index = matches!(self.next().map(|token| &token.kind), Some(TokenKind::Identifier(name)) if name.eq_ignore_ascii_case("i"));
+	    if !is_index || self.next().map(|token| &token.kind) != Some(&TokenKind::CloseBracket) {
+		return Err((column, "only [I] can be written in brackets".to_string()));
+	    }
+	    return Ok(Operand::IndexAddress);
+	}
+	if let Some(TokenKind::Identifier(name)) = self.peek() {
+	    let name = name.to_ascii_uppercase();
+	    if name == "LONG" {
+		self.position += 1;
+		return Ok(Operand::Long(self.expression()?));
+	    }
+	    //the special names are only read as such when they are the whole operand, so that labels can still start with them.
+	    let whole_operand = matches!(self.tokens.get(self.position + 1).map(|token| &token.kind), None | Some(TokenKind::Comma));
+	    let special = match name.as_str() {
+		"I" => Some(Operand::Index),
+		"DT" => Some(Operand::DelayTimer),
+		"ST" => Some(Operand::SoundTimer),
+		"K" => Some(Operand::Key),
+		"F" => Some(Operand::Font),
+		"HF" => Some(Operand::BigFont),
+		"B" => Some(Operand::Bcd),
+		"R" => Some(Operand::Flags),
+		_ if name.len() == 2 && name.starts_with('V') => usize::from_str_radix(&name[1..], 16).ok().map(Operand::Register),
+		_ => None
+	    };
+	    if let (true, Some(operand)) = (whole_operand, special) {
+		self.position += 1;
+		return Ok(operand);
+	    }
+	}
+	return Ok(Operand::Value(self.expression()?));
+    }
+}
+
+///Holds everything collected by the first pass over the source.
+struct Assembler {
+    statements: Vec<Statement>,
+    symbols: HashMap<String, Symbol>,
+    address: i64,
+    include_stack: Vec<PathBuf>
+}
+
+impl Assembler {
+    fn new() -> Self {
+	return Assembler {
+	    statements: Vec::new(),
+	    symbols: HashMap::new(),
+	    address: PROGRAM_START as i64,
+	    include_stack: Vec::new()
+	};
+    }
+
+    ///the first pass: reads every line of the source, collecting the labels and constants and the statements that make up the program.
+    fn read_source(&mut self, source: &str, file: &str, directory: &Path) -> Result<(), AssemblyError> {
+	for (index, line) in source.lines().enumerate() {
+	    let error = |(column, message): LineError| AssemblyError { file: file.to_string(), line: index + 1, column, message };
+	    let tokens = tokenize(line).map_err(error)?;
+	    let mut cursor = Cursor { tokens: &tokens, position: 0, end_column: line.chars().count() + 1 };
+	    self.read_line(&mut cursor, file, index + 1, directory).map_err(error)?;
+	}
+	return Ok(());
+    }
+
+    fn read_line(&mut self, cursor: &mut Cursor, file: &str, line: usize, directory: &Path) -> Result<(), LineError> {
+	let Some(Token { kind: TokenKind::Identifier(first), column }) = cursor.next() else {
+	    return match cursor.at_end() {
+		true => Ok(()),
+		false => Err((cursor.tokens[0].column, "expected a label, instruction or directive".to_string()))
+	    };
+	};
+	let mut word = first.clone();
+	let mut column = *column;
+
+	if cursor.peek() == Some(&TokenKind::Colon) {
+	    cursor.position += 1;
+	    self.define(&word, self.address, column, file, line)?;
+	    match cursor.next() {
+		Some(Token { kind: TokenKind::Identifier(next), column: next_column }) => {
+		    word = next.clone();
+		    column = *next_column;
+		},
+		Some(token) => return Err((token.column, "expected an instruction or directive after the label".to_string())),
+		None => return Ok(())
+	    }
+	}
+
+	if let Some(TokenKind::Identifier(directive)) = cursor.peek() {
+	    if directive.eq_ignore_ascii_case("equ") {
+		cursor.position += 1;
+		let expression = cursor.expression()?;
+		cursor.expect_end()?;
+		let value = self.evaluate(&expression)?;
+		return self.define(&word, value, column, file, line);
+	    }
+	}
+
+	let kind = match word.to_ascii_uppercase().as_str() {
+	    "DB" => {
+		let mut items = Vec::new();
+		loop {
+		    match cursor.peek() {
+			Some(TokenKind::Text(text)) => {
+			    cursor.position += 1;
+			    self.address += text.len() as i64;
+			    items.push(DataItem::Text(text.clone()));
+			},
+			_ => {
+			    self.address += 1;
+			    items.push(DataItem::Byte(cursor.expression()?));
+			}
+		    }
+		    if !cursor.comma() {
+			break;
+		    }
+		}
+		StatementKind::Bytes(items)
+	    },
+	    "DW" => {
+		let mut words = vec![cursor.expression()?];
+		while cursor.comma() {
+		    words.push(cursor.expression()?);
+		}
+		self.address += 2 * words.len() as i64;
+		StatementKind::Words(words)
+	    },
+	    "INCLUDE" => {
+		let included = match cursor.next() {
+		    Some(Token { kind: TokenKind::Text(path), .. }) => directory.join(path),
+		    _ => return Err((cursor.column(), "INCLUDE needs a file name in quotes".to_string()))
+		};
+		cursor.expect_end()?;
+		return self.read_file(&included).map_err(|error| match error.line {
+		    0 => (column, error.message), //the included file could not be read, so point at the INCLUDE.
+		    _ => (column, format!("in included file: {}", error))
+		});
+	    },
+	    mnemonic => {
+		let mut operands = Vec::new();
+		if !cursor.at_end() {
+		    operands.push(cursor.operand()?);
+		    while cursor.comma() {
+			operands.push(cursor.operand()?);
+		    }
+		}
+		self.address += match operands.as_slice() {
+		    [_, Operand::Long(_)] => 4,
+		    _ => 2
+		};
+		StatementKind::Instruction { mnemonic: mnemonic.to_string(), column, operands }
+	    }
+	};
+	cursor.expect_end()?;
+	self.statements.push(Statement { file: file.to_string(), line, kind });
+	return Ok(());
+    }
+
+    ///reads an included file, or the main file when the assembler is started with a path.
+    fn read_file(&mut self, path: &Path) -> Result<(), AssemblyError> {
+	let file = path.display().to_string();
+	let file_error = |message: String| AssemblyError { file: file.clone(), line: 0, column: 0, message };
+	let canonical = path.canonicalize().map_err(|error| file_error(format!("unable to read {}: {}", file, error)))?;
+	if self.include_stack.contains(&canonical) {
+	    return Err(file_error(format!("{} includes itself", file)));
+	}
+	let source = fs::read_to_string(path).map_err(|error| file_error(format!("unable to read {}: {}", file, error)))?;
+	self.include_stack.push(canonical);
+	let directory = path.parent().unwrap_or(Path::new(".")).to_path_buf();
+	let result = self.read_source(&source, &file, &directory);
+	self.include_stack.pop();
+	return result;
+    }
+
+    fn define(&mut self, name: &str, value: i64, column: usize, file: &str, line: usize) -> Result<(), LineError> {
+	if let Some(existing) = self.symbols.get(name) {
+	    return Err((column, format!("\"{}\" is already defined at {}:{}", name, existing.file, existing.line)));
+	}
+	self.symbols.insert(name.to_string(), Symbol { value, file: file.to_string(), line });
+	return Ok(());
+    }
+
+    fn evaluate(&self, expression: &Expression) -> Result<i64, LineError> {
+	let mut total = 0i64;
+	for (negative, term) in expression.terms.iter() {
+	    let value = match term {
+		Term::Number(number) => *number,
+		Term::Symbol(name, column) => self.symbols.get(name).ok_or((*column, format!("unknown label or constant \"{}\"", name)))?.value
+	    };
+	    total = if *negative {total - value} else {total + value};
+	}
+	return Ok(total);
+    }
+
+    ///evaluates an expression and checks that it fits between min and max, what is the name of the kind of value for the error message.
+    fn value_in_range(&self, expression: &Expression, min: i64, max: i64, what: &str) -> Result<i64, LineError> {
+	let value = self.evaluate(expression)?;
+	if value < min || value > max {
+	    return Err((expression.column, format!("{} {} is outside of {}..={:#x}", what, value, min, max)));
+	}
+	return Ok(value);
+    }
+
+    fn address(&self, expression: &Expression) -> Result<u16, LineError> {
+	return Ok(self.value_in_range(expression, 0, 0xFFF, "the address")? as u16);
+    }
+
+    fn byte(&self, expression: &Expression) -> Result<u8, LineError> {
+	return Ok(self.value_in_range(expression, -128, 0xFF, "the byte")? as u8);
+    }
+
+    fn nibble(&self, expression: &Expression) -> Result<u8, LineError> {
+	return Ok(self.value_in_range(expression, 0, 0xF, "the value")? as u8);
+    }
+
+    ///turns an instruction statement into an Instruction.
+    fn instruction(&self, mnemonic: &str, column: usize, operands: &[Operand]) -> Result<Instruction, LineError> {
+	use Operand::*;
+	let instruction = match (mnemonic.to_ascii_uppercase().as_str(), operands) {
+	    ("CLS", []) => Instruction::ClearScreen,
+	    ("RET", []) => Instruction::Return,
+	    ("SCD", [Value(n)]) => Instruction::ScrollDown(self.nibble(n)?),
+	    ("SCU", [Value(n)]) => Instruction::ScrollUp(self.nibble(n)?),
+	    ("SCR", []) => Instruction::ScrollRight,
+	    ("SCL", []) => Instruction::ScrollLeft,
+	    ("EXIT", []) => Instruction::Exit,
+	    ("LOW", []) => Instruction::LowResolution,
+	    ("HIGH", []) => Instruction::HighResolution,
+	    ("SYS", [Value(a)]) => Instruction::MachineCall(self.address(a)?),
+	    ("JP", [Value(a)]) => Instruction::Jump(self.address(a)?),
+	    ("JP", [Register(0), Value(a)]) => Instruction::JumpOffset(self.address(a)?),
+	    ("CALL", [Value(a)]) => Instruction::Call(self.address(a)?),
+	    ("SE", [Register(x), Value(b)]) => Instruction::SkipIfEqual(*x, self.byte(b)?),
+	    ("SE", [Register(x), Register(y)]) => Instruction::SkipIfRegistersEqual(*x, *y),
+	    ("SNE", [Register(x), Value(b)]) => Instruction::SkipIfNotEqual(*x, self.byte(b)?),
+	    ("SNE", [Register(x), Register(y)]) => Instruction::SkipIfRegistersNotEqual(*x, *y),
+	    ("SAVE", [Register(x), Register(y)]) => Instruction::SaveRange(*x, *y),
+	    ("LOAD", [Register(x), Register(y)]) => Instruction::LoadRange(*x, *y),
+	    ("LD", [Register(x), Value(b)]) => Instruction::LoadImmediate(*x, self.byte(b)?),
+	    ("LD", [Register(x), Register(y)]) => Instruction::Move(*x, *y),
+	    ("LD", [Index, Value(a)]) => Instruction::LoadIndex(self.address(a)?),
+	    ("LD", [Index, Long(a)]) => Instruction::LoadLongIndex(self.value_in_range(a, 0, 0xFFFF, "the address")? as u16),
+	    ("LD", [Register(x), DelayTimer]) => Instruction::ReadDelay(*x),
+	    ("LD", [Register(x), Key]) => Instruction::WaitKey(*x),
+	    ("LD", [DelayTimer, Register(x)]) => Instruction::SetDelay(*x),
+	    ("LD", [SoundTimer, Register(x)]) => Instruction::SetSound(*x),
+	    ("LD", [Font, Register(x)]) => Instruction::LoadFont(*x),
+	    ("LD", [BigFont, Register(x)]) => Instruction::LoadBigFont(*x),
+	    ("LD", [Bcd, Register(x)]) => Instruction::StoreBcd(*x),
+	    ("LD", [IndexAddress, Register(x)]) => Instruction::Store(*x),
+	    ("LD", [Register(x), IndexAddress]) => Instruction::Load(*x),
+	    ("LD", [Flags, Register(x)]) => Instruction::StoreFlags(*x),
+	    ("LD", [Register(x), Flags]) => Instruction::LoadFlags(*x),
+	    ("ADD", [Register(x), Value(b)]) => Instruction::AddImmediate(*x, self.byte(b)?),
+	    ("ADD", [Register(x), Register(y)]) => Instruction::Add(*x, *y),
+	    ("ADD", [Index, Register(x)]) => Instruction::AddIndex(*x),
+	    ("OR", [Register(x), Register(y)]) => Instruction::Or(*x, *y),
+	    ("AND", [Register(x), Register(y)]) => Instruction::And(*x, *y),
+	    ("XOR", [Register(x), Register(y)]) => Instruction::Xor(*x, *y),
+	    ("SUB", [Register(x), Register(y)]) => Instruction::Subtract(*x, *y),
+	    ("SUBN", [Register(x), Register(y)]) => Instruction::SubtractReverse(*x, *y),
+	    ("SHR", [Register(x)]) => Instruction::ShiftRight(*x, *x),
+	    ("SHR", [Register(x), Register(y)]) => Instruction::ShiftRight(*x, *y),
+	    ("SHL", [Register(x)]) => Instruction::ShiftLeft(*x, *x),
+	    ("SHL", [Register(x), Register(y)]) => Instruction::ShiftLeft(*x, *y),
+	    ("RND", [Register(x), Value(b)]) => Instruction::Random(*x, self.byte(b)?),
+	    ("DRW", [Register(x), Register(y), Value(n)]) => Instruction::Draw(*x, *y, self.nibble(n)?),
+	    ("SKP", [Register(x)]) => Instruction::SkipIfKey(*x),
+	    ("SKNP", [Register(x)]) => Instruction::SkipIfNotKey(*x),
+	    ("PLANE", [Value(n)]) => Instruction::SelectPlanes(self.nibble(n)?),
+	    ("AUDIO", []) => Instruction::LoadAudio,
+	    ("PITCH", [Register(x)]) => Instruction::SetPitch(*x),
+	    (known, _) if MNEMONICS.contains(&known) => return Err((column, format!("{} does not take these operands", mnemonic))),
+	    _ => return Err((column, format!("unknown instruction \"{}\"", mnemonic)))
+	};
+	return Ok(instruction);
+    }
+
+    ///the second pass: turns every statement into bytes, now that all the labels are known.
+    fn write_program(&self) -> Result<Vec<u8>, AssemblyError> {
+	let mut program = Vec::new();
+	for statement in self.statements.iter() {
+	    let error = |(column, message): LineError| AssemblyError { file: statement.file.clone(), line: statement.line, column, message };
+	    match &statement.kind {
+		StatementKind::Instruction { mnemonic, column, operands } => {
+		    program.extend(self.instruction(mnemonic, *column, operands).map_err(error)?.encode());
+		},
+		StatementKind::Bytes(items) => {
+		    for item in items.iter() {
+			match item {
+			    DataItem::Byte(expression) => program.push(self.byte(expression).map_err(error)?),
+			    DataItem::Text(text) => program.extend(text.bytes())
+			}
+		    }
+		},
+		StatementKind::Words(words) => {
+		    for word in words.iter() {
+			let value = self.value_in_range(word, -32768, 0xFFFF, "the word").map_err(error)? as u16;
+			program.extend(value.to_be_bytes());
+		    }
+		}
+	    }
+	}
+	return Ok(program);
+    }
+}
+
+///every mnemonic the assembler knows, to tell a wrong operand apart from a misspelled instruction.
+const MNEMONICS: [&str; 32] = [
+    "CLS", "RET", "SCD", "SCU", "SCR", "SCL", "EXIT", "LOW", "HIGH", "SYS", "JP", "CALL", "SE", "SNE", "SAVE", "LOAD",
+    "LD", "ADD", "OR", "AND", "XOR", "SUB", "SUBN", "SHR", "SHL", "RND", "DRW", "SKP", "SKNP", "PLANE", "AUDIO", "PITCH"
+];
+
+///assembles source code into a program that starts at 0x200. Files in INCLUDE directives are looked for relative to the current directory.
+pub fn assemble(source: &str) -> Result<Vec<u8>, AssemblyError> {
+    let mut assembler = Assembler::new();
+    assembler.read_source(source, "<source>", Path::new("."))?;
+    return assembler.write_program();
+}
+
+///assembles the source code in a file into a program that starts at 0x200.
+pub fn assemble_file(path: &Path) -> Result<Vec<u8>, AssemblyError> {
+    let mut assembler = Assembler::new();
+    assembler.read_file(path)?;
+    return assembler.write_program();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use crate::chip_8::disassembler::{disassemble, Syntax};
+
+    ///every form of every instruction, and the bytes it should assemble into.
+    const INSTRUCTIONS: [(&str, &[u8]); 54] = [
+	("CLS", &[0x00, 0xE0]),
+	("RET", &[0x00, 0xEE]),
+	("SCD 5", &[0x00, 0xC5]),
+	("SCU 3", &[0x00, 0xD3]),
+	("SCR", &[0x00, 0xFB]),
+	("SCL", &[0x00, 0xFC]),
+	("EXIT", &[0x00, 0xFD]),
+	("LOW", &[0x00, 0xFE]),
+	("HIGH", &[0x00, 0xFF]),
+	("SYS 0x123", &[0x01, 0x23]),
+	("JP 0x456", &[0x14, 0x56]),
+	("CALL 0x789", &[0x27, 0x89]),
+	("SE V1, 0x22", &[0x31, 0x22]),
+	("SNE V2, 0x33", &[0x42, 0x33]),
+	("SE V3, V4", &[0x53, 0x40]),
+	("SAVE V5, V6", &[0x55, 0x62]),
+	("LOAD V7, V8", &[0x57, 0x83]),
+	("LD V9, 0x44", &[0x69, 0x44]),
+	("ADD VA, 0x55", &[0x7A, 0x55]),
+	("LD VB, VC", &[0x8B, 0xC0]),
+	("OR VD, VE", &[0x8D, 0xE1]),
+	("AND VF, V0", &[0x8F, 0x02]),
+	("XOR V1, V2", &[0x81, 0x23]),
+	("ADD V3, V4", &[0x83, 0x44]),
+	("SUB V5, V6", &[0x85, 0x65]),
+	("SHR V7, V8", &[0x87, 0x86]),
+	("SUBN V9, VA", &[0x89, 0xA7]),
+	("SHL VB, VC", &[0x8B, 0xCE]),
+	("SNE VD, VE", &[0x9D, 0xE0]),
+	("LD I, 0xABC", &[0xAA, 0xBC]),
+	("JP V0, 0xDEF", &[0xBD, 0xEF]),
+	("RND V1, 0x0f", &[0xC1, 0x0F]),
+	("DRW V2, V3, 4", &[0xD2, 0x34]),
+	("DRW V4, V5, 0", &[0xD4, 0x50]),
+	("SKP V6", &[0xE6, 0x9E]),
+	("SKNP V7", &[0xE7, 0xA1]),
+	("LD I, LONG 0x1234", &[0xF0, 0x00, 0x12, 0x34]),
+	("PLANE 3", &[0xF3, 0x01]),
+	("AUDIO", &[0xF0, 0x02]),
+	("LD V8, DT", &[0xF8, 0x07]),
+	("LD V9, K", &[0xF9, 0x0A]),
+	("LD DT, VA", &[0xFA, 0x15]),
+	("LD ST, VB", &[0xFB, 0x18]),
+	("ADD I, VC", &[0xFC, 0x1E]),
+	("LD F, VD", &[0xFD, 0x29]),
+	("LD HF, VE", &[0xFE, 0x30]),
+	("LD B, VF", &[0xFF, 0x33]),
+	("PITCH V0", &[0xF0, 0x3A]),
+	("LD [I], V1", &[0xF1, 0x55]),
+	("LD V2, [I]", &[0xF2, 0x65]),
+	("LD R, V3", &[0xF3, 0x75]),
+	("LD V4, R", &[0xF4, 0x85]),
+	("DW 0xFFFF", &[0xFF, 0xFF]),
+	("DB 0x12, 0x34", &[0x12, 0x34])
+    ];
+
+    #[test]
+    fn every_instruction_round_trips_through_the_decoder() {
+	for (source, expected) in INSTRUCTIONS.iter() {
+	    let program = assemble(source).unwrap_or_else(|error| panic!("{}: {}", source, error));
+	    assert_eq!(&program, expected, "{} assembled into the wrong bytes", source);
+	    let opcode = u16::from_be_bytes([program[0], program[1]]);
+	    let instruction = match program.len() {
+		4 => Instruction::decode_long(opcode, u16::from_be_bytes([program[2], program[3]])),
+		_ => Instruction::decode(opcode)
+	    };
+	    assert_eq!(instruction.encode(), program, "{} decoded into {:?}, which encodes differently", source, instruction);
+	}
+    }
+
+    #[test]
+    fn disassembled_programs_reassemble_into_the_same_bytes() {
+	let programs = Path::new(env!("CARGO_MANIFEST_DIR")).join("programs");
+	let mut checked = 0;
+	for entry in fs::read_dir(&programs).expect("the programs directory is missing") {
+	    let path = entry.expect("unable to read the programs directory").path();
+	    if path.extension().and_then(|extension| extension.to_str()) != Some("ch8") {
+		continue;
+	    }
+	    let program = fs::read(&path).expect("unable to read a program");
+	    let source = disassemble(&program, false).to_text(Syntax::Classic);
+	    let reassembled = assemble(&source).unwrap_or_else(|error| panic!("{}: {}", path.display(), error));
+	    assert_eq!(reassembled, program, "{} did not reassemble into the same bytes", path.display());
+	    checked += 1;
+	}
+	assert!(checked > 0, "there were no programs to check");
+    }
+
+    #[test]
+    fn errors_point_at_their_line_and_column() {
+	let error_at = |source: &str| {
+	    let error = assemble(source).expect_err("the source should not assemble");
+	    return (error.line, error.column);
+	};
+	assert_eq!(error_at("CLS\n    LD V0, 0x100"), (2, 12));
+	assert_eq!(error_at("start:\n  JP nowhere"), (2, 6));
+	assert_eq!(error_at("  FOO V1"), (1, 3));
+	assert_eq!(error_at("CLS\nCLS\n  DRW V1, V2, 3 extra"), (3, 17));
+	assert_eq!(error_at("DB \"never closed"), (1, 4));
+	assert_eq!(error_at("loop:\nloop:"), (2, 1));
+    }
+}
@@ -0,0 +1,102 @@
+//! This module is for the chip 8 audio.
+//! The original chip 8 could only beep while the sound timer was above 0, XO-CHIP programs can also load a 16 byte pattern of
+//!  1 bit samples (F002) and pick the pitch it is played back at (FX3A).
+//! The SampleStream struct turns all of this into 16 bit PCM samples, which a SoundDriver pulls out of it at whatever rate it wants.
+
+///the sample rate that is used if the SoundDriver does not ask for a different one.
+pub const DEFAULT_SAMPLE_RATE: u32 = 44100;
+///the frequency of the square wave beep that is played when no XO-CHIP pattern has been loaded.
+pub const BEEP_FREQUENCY: f64 = 440.0;
+///how loud the samples are, the samples go between -AMPLITUDE and AMPLITUDE.
+const AMPLITUDE: i16 = 8000;
+///the pitch register value which plays the pattern back at 4000 bits per second, this is what the pitch register starts at.
+const DEFAULT_PITCH: u8 = 64;
+
+///The audio hardware of the chip 8, it generates 16 bit mono PCM samples at a given sample rate.
+///When no pattern has been loaded, the classic square wave beep is played, otherwise the 128 bits of the pattern are played in a loop,
+/// a 1 bit being a high sample and a 0 bit being a low one. Nothing is played while the sound timer is at 0.
+pub struct SampleStream {
+    sample_rate: u32,
+    pattern: Option<[u8; 16]>,
+    pitch: u8,
+    sounding: bool,
+    phase: f64 //how far into the square wave period (0 to 1) or the pattern (0 to 128 bits) playback is.
+}
+
+impl SampleStream {
+    ///returns a new silent SampleStream which generates samples at the given sample rate.
+    pub fn new(sample_rate: u32) -> Self {
+	return SampleStream {
+	    sample_rate,
+	    pattern: None,
+	    pitch: DEFAULT_PITCH,
+	    sounding: false,
+	    phase: 0.0
+	};
+    }
+
+    ///returns the number of samples per second this stream generates.
+    pub fn sample_rate(&self) -> u32 {
+	return self.sample_rate;
+    }
+
+    ///tells you if the chip 8 is currently making a sound (the sound timer is above 0).
+    pub fn is_sounding(&self) -> bool {
+	return self.sounding;
+    }
+
+    ///returns the XO-CHIP audio pattern, or None if the program never loaded one and the classic beep is played.
+    pub fn pattern(&self) -> Option<[u8; 16]> {
+	return self.pattern;
+    }
+
+    ///returns the XO-CHIP pitch register.
+    pub fn pitch(&self) -> u8 {
+	return self.pitch;
+    }
+
+    ///returns the number of bits of the pattern that are played every second, this depends on the pitch register.
+    pub fn pattern_playback_rate(&self) -> f64 {
+	return 4000.0 * 2f64.powf((self.pitch as f64 - 64.0) / 48.0);
+    }
+
+    ///loads a new XO-CHIP audio pattern, from then on the pattern is played instead of the classic beep.
+    pub fn set_pattern(&mut self, pattern: [u8; 16]) {
+	self.pattern = Some(pattern);
+    }
+
+    ///sets the XO-CHIP pitch register.
+    pub fn set_pitch(&mut self, pitch: u8) {
+	self.pitch = pitch;
+    }
+
+    ///turns the sound on or off, this is done by the sound timer.
+    pub fn set_sounding(&mut self, sounding: bool) {
+	self.sounding = sounding;
+    }
+
+    ///generates the next sample. The sample is 0 while nothing is being played.
+    pub fn next_sample(&mut self) -> i16 {
+	if !self.sounding {
+	    return 0;
+	}
+	let high = match self.pattern {
+	    Some(pattern) => {
+		let bit = self.phase as usize;
+		self.phase = (self.phase + self.pattern_playback_rate() / self.sample_rate as f64) % 128.0;
+		pattern[bit / 8] & (0x80 >> (bit % 8)) != 0
+	    },
+	    None => {
+		let high = self.phase < 0.5;
+		self.phase = (self.phase + BEEP_FREQUENCY / self.sample_rate as f64) % 1.0;
+		high
+	    }
+	};
+	return if high {AMPLITUDE} else {-AMPLITUDE};
+    }
+
+    ///fills the whole buffer with the next samples.
+    pub fn fill(&mut self, buffer: &mut [i16]) {
+	buffer.iter_mut().for_each(|sample| *sample = self.next_sample());
+    }
+}
@@ -0,0 +1,66 @@
+//! This module has the checksums used by the file formats the emulator writes.
+//! CRC-32 is the same one used by zip, gzip and png, so anything written with it can be checked by other tools too.
+//! Adler-32 is the checksum at the end of zlib data, which png uses for its pixels.
+
+///the reversed CRC-32 polynomial.
+const CRC32_POLYNOMIAL: u32 = 0xEDB88320;
+
+///the CRC-32 of every possible byte, so that the checksum can be worked out a byte at a time instead of a bit at a time.
+const CRC32_TABLE: [u32; 256] = crc32_table();
+
+const fn crc32_table() -> [u32; 256] {
+    let mut table = [0u32; 256];
+    let mut byte = 0;
+    while byte < 256 {
+	let mut crc = byte as u32;
+	let mut bit = 0;
+	while bit < 8 {
+	    crc = if crc & 1 == 1 {(crc >> 1) ^ CRC32_POLYNOMIAL} else {crc >> 1};
+	    bit += 1;
+	}
+	table[byte] = crc;
+	byte += 1;
+    }
+    return table;
+}
+
+///returns the CRC-32 checksum of the data.
+pub fn crc32(data: &[u8]) -> u32 {
+    return crc32_update(0, data);
+}
+
+///carries on a CRC-32 checksum with more data, for when the data is not all in one slice.
+///crc32_update(crc32(a), b) is the same as the crc32() of a and b joined together.
+pub fn crc32_update(crc: u32, data: &[u8]) -> u32 {
+    let mut crc = !crc;
+    for byte in data.iter() {
+	crc = CRC32_TABLE[((crc ^ *byte as u32) & 0xFF) as usize] ^ (crc >> 8);
+    }
+    return !crc;
+}
+
+///Adler-32 works modulo the biggest prime below 2^16.
+const ADLER32_MODULUS: u32 = 65521;
+
+///returns the Adler-32 checksum of the data.
+pub fn adler32(data: &[u8]) -> u32 {
+    let (mut low, mut high) = (1u32, 0u32);
+    for chunk in data.chunks(5552) { //the most bytes that can be added up before high could overflow a u32.
+	for byte in chunk.iter() {
+	    low += *byte as u32;
+	    high += low;
+	}
+	low %= ADLER32_MODULUS;
+	high %= ADLER32_MODULUS;
+    }
+    return (high << 16) | low;
+}
+
+const FNV_OFFSET_BASIS: u64 = 0xCBF29CE484222325;
+const FNV_PRIME: u64 = 0x100000001B3;
+
+///returns the 64 bit FNV-1a hash of the data. It is not a checksum for catching damage, but it is quick and good for telling apart
+/// things like two frames of the screen.
+pub fn fnv1a_64(data: &[u8]) -> u64 {
+    return data.iter().fold(FNV_OFFSET_BASIS, |hash, byte| (hash ^ *byte as u64).wrapping_mul(FNV_PRIME));
+}
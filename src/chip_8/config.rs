@@ -0,0 +1,146 @@
+//! This module reads the small config files (keymaps and themes). They are written in a little part of TOML:
+//!  # comments start with a hash
+//!  name = "a string"
+//!  list = ["strings", "in", "a list"]
+//!  [table]
+//!  name = "every setting after a [table] line is part of that table"
+//! Strings can have \" and \\ in them for quotes and backslashes, and a list has to be on one line.
+//! A table's name is everything between the brackets, so [ keys ] is a table called " keys ", not [keys].
+
+use std::fs;
+use std::path::Path;
+
+///The value of a setting.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Value {
+    String(String),
+    List(Vec<String>)
+}
+
+///One name = value line of a config file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Setting {
+    ///the line it is on, counting from 1, for error messages.
+    pub line: usize,
+    ///the [table] it is in, None for the settings before the first table.
+    pub table: Option<String>,
+    pub name: String,
+    pub value: Value
+}
+
+impl Setting {
+    ///returns an error message that says which line the setting is on.
+    pub fn error(&self, message: &str) -> String {
+	return format!("line {}: {}", self.line, message);
+    }
+
+    ///returns the value if it is a string, or an error message if it is a list.
+    pub fn string(&self) -> Result<&str, String> {
+	return match &self.value {
+	    Value::String(string) => Ok(string),
+	    Value::List(_) => Err(self.error(&format!("{} should be a string, not a list", self.name)))
+	};
+    }
+
+    ///returns the value as a list, a string on its own is a list of one.
+    pub fn list(&self) -> Vec<String> {
+	return match &self.value {
+	    Value::String(string) => vec![string.clone()],
+	    Value::List(list) => list.clone()
+	};
+    }
+}
+
+///reads every setting in a config file, in order. Any problem is returned as an Err() with a message that says which line it is on.
+pub fn parse(config: &str) -> Result<Vec<Setting>, String> {
+    let mut settings = Vec::new();
+    let mut table = None;
+    for (number, line) in config.lines().enumerate() {
+	let line = strip_comment(line).trim();
+	if line.is_empty() {
+	    continue;
+	}
+	let error = |message: String| format!("line {}: {}", number + 1, message);
+	if let Some(name) = line.strip_prefix('[').and_then(|line| line.strip_suffix(']')) {
+	    table = Some(name.to_string());
+	    continue;
+	}
+	let (name, value) = line.split_once('=').ok_or_else(|| error(format!("\"{}\" should be a name = \"value\"", line)))?;
+	let value = value.trim();
+	let value = match value.strip_prefix('[').and_then(|value| value.strip_suffix(']')) {
+	    Some(list) => Value::List(split_list(list).iter().map(|item| parse_string(item)).collect::<Result<Vec<String>, String>>().map_err(error)?),
+	    None => Value::String(parse_string(value).map_err(error)?)
+	};
+	settings.push(Setting {
+	    line: number + 1,
+	    table: table.clone(),
+	    name: name.trim().trim_matches('"').to_string(),
+	    value
+	});
+    }
+    return Ok(settings);
+}
+
+///reads a config file from disk and hands it to parse_config, which is usually a from_config() function. The errors start with the file's path.
+pub fn load_file<T>(path: &Path, parse_config: impl Fn(&str) -> Result<T, String>) -> Result<T, String> {
+    let config = fs::read_to_string(path).map_err(|error| format!("{}: {}", path.display(), error))?;
+    return parse_config(&config).map_err(|message| format!("{}: {}", path.display(), message));
+}
+
+///cuts the # comment off the end of a line, unless the # is inside a string.
+fn strip_comment(line: &str) -> &str {
+    let mut in_string = false;
+    let mut escaped = false;
+    for (position, character) in line.char_indices() {
+	match character {
+	    _ if escaped => escaped = false,
+	    '\\' if in_string => escaped = true,
+	    '"' => in_string = !in_string,
+	    '#' if !in_string => return &line[..position],
+	    _ => {}
+	}
+    }
+    return line;
+}
+
+///splits the inside of a list at the commas that are not inside a string. A comma after the last item is allowed.
+fn split_list(list: &str) -> Vec<&str> {
+    let mut items = Vec::new();
+    let mut in_string = false;
+    let mut escaped = false;
+    let mut start = 0;
+    for (position, character) in list.char_indices() {
+	match character {
+	    _ if escaped => escaped = false,
+	    '\\' if in_string => escaped = true,
+	    '"' => in_string = !in_string,
+	    ',' if !in_string => {
+		items.push(list[start..position].trim());
+		start = position + 1;
+	    },
+	    _ => {}
+	}
+    }
+    items.push(list[start..].trim());
+    return items.into_iter().filter(|item| !item.is_empty()).collect();
+}
+
+///reads a TOML basic string, "like this", with \" and \\ for quotes and backslashes.
+fn parse_string(value: &str) -> Result<String, String> {
+    let inner = value.strip_prefix('"').and_then(|value| value.strip_suffix('"'))
+	.ok_or(format!("{} should be a string in double quotes", value))?;
+    let mut string = String::new();
+    let mut characters = inner.chars();
+    while let Some(character) = characters.next() {
+	match character {
+	    '\\' => match characters.next() {
+		Some(escaped @ ('"' | '\\')) => string.push(escaped),
+		Some(other) => return Err(format!("\\{} is not an escape this understands, only \\\" and \\\\ are", other)),
+		None => return Err(format!("{} ends in the middle of an escape", value))
+	    },
+	    '"' => return Err(format!("{} has a quote in it that should be written as \\\"", value)),
+	    _ => string.push(character)
+	}
+    }
+    return Ok(string);
+}
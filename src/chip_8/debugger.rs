@@ -0,0 +1,453 @@
+//! This module is an interactive debugger for the chip 8.
+//! The Debugger reads commands line by line (from stdin usually) and can set breakpoints, step through the program one instruction
+//!  at a time, watch memory for reads, writes and execution, and print the registers, stack, timers, memory and screen of the chip 8.
+//! Since the debugger uses the terminal for its commands, the chip 8 gets a DebugDisplay and DebugKeypad instead of the normal drivers:
+//!  the screen is only printed when you ask for it with the "screen" command, and keys are held down with the "key" command.
+
+use std::cell::{Cell, RefCell};
+use std::fmt;
+use std::io::{self, BufRead, Write};
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use crate::chip_8::{TimedRunner, ChipSystem, Chip8Error, Watchpoint};
+use crate::chip_8::video::{VideoDriver, Frame, PLANE_COUNT};
+use crate::chip_8::keyboard::{self, KeyboardDriver, KeyEvent};
+
+///this is set by the SIGINT handler when ctrl-c is pressed, so that "continue" can stop and give you the prompt back.
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+const HELP: &str = "\
+break <address>           stop when the program counter reaches the address (b)
+break op <pattern>        stop before any instruction that matches the pattern, like FX0A or 2NNN (X, Y, N and K are wildcards)
+delete <number>           remove a breakpoint (d)
+breakpoints               list the breakpoints (bl)
+watch <address>[-<end>] [rwx]  stop after an instruction reads (r), writes (w) or executes (x) the addresses, rw by default (w)
+unwatch <number>          remove a watchpoint
+watchpoints               list the watchpoints (wl)
+step [count]              run one instruction, or count instructions (s)
+next                      like step, but runs a whole subroutine called with 2NNN (n)
+back [count]              go back one instruction, or count instructions, by replaying from the last snapshot (bk)
+continue                  run until a breakpoint is hit or ctrl-c is pressed (c)
+registers                 print V0 - VF, I and the program counter (r)
+stack                     print the return addresses on the stack
+timers                    print the delay and sound timers
+memory <address> [length] print memory as hex, 64 bytes by default (m)
+screen                    print the display
+key <hex keys | none>     hold down keys on the chip 8 keypad (key 5 or key 4 6), letting go of the rest
+help                      print this list (h)
+quit                      leave the debugger (q)";
+
+///installs a handler for SIGINT (ctrl-c), so that ctrl-c pauses a running program and goes back to the debugger prompt
+/// instead of killing the emulator.
+pub fn install_interrupt_handler() {
+    extern "C" fn handle_interrupt(_signal: libc::c_int) {
+	INTERRUPTED.store(true, Ordering::SeqCst);
+    }
+    unsafe {
+	libc::signal(libc::SIGINT, handle_interrupt as *const () as libc::sighandler_t);
+    }
+}
+
+///A place where the debugger stops the program.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Breakpoint {
+    ///stops when the program counter reaches the address.
+    Address(u16),
+    ///stops before an instruction which matches the pattern. The bits set in the mask have to be the same as in the pattern,
+    /// the others can be anything. The text is the pattern as it was typed, like "FX0A".
+    Opcode { pattern: u16, mask: u16, text: String }
+}
+
+impl Breakpoint {
+    ///reads an opcode pattern like "FX0A" or "2nnn" into a Breakpoint. Hex digits have to match exactly,
+    /// while X, Y, N, K, _ and ? stand for any nibble. Returns None if the text is not 4 nibbles long or has other characters.
+    pub fn from_opcode_pattern(text: &str) -> Option<Self> {
+	if text.chars().count() != 4 {
+	    return None;
+	}
+	let mut pattern = 0u16;
+	let mut mask = 0u16;
+	for character in text.chars() {
+	    pattern <<= 4;
+	    mask <<= 4;
+	    match character.to_ascii_uppercase() {
+		'X' | 'Y' | 'N' | 'K' | '_' | '?' => {},
+		digit => {
+		    pattern |= digit.to_digit(16)? as u16;
+		    mask |= 0xF;
+		}
+	    }
+	}
+	return Some(Breakpoint::Opcode { pattern, mask, text: text.to_ascii_uppercase() });
+    }
+
+    ///tells you if the program should stop before the given instruction at the given address.
+    pub fn matches(&self, address: u16, opcode: u16) -> bool {
+	return match self {
+	    Breakpoint::Address(breakpoint_address) => *breakpoint_address == address,
+	    Breakpoint::Opcode { pattern, mask, .. } => opcode & mask == *pattern
+	};
+    }
+}
+
+impl fmt::Display for Breakpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+	return match self {
+	    Breakpoint::Address(address) => write!(f, "address {:#06x}", address),
+	    Breakpoint::Opcode { text, .. } => write!(f, "opcode {}", text)
+	};
+    }
+}
+
+///A copy of the last frame the chip 8 drew, kept around so that the "screen" command can print it.
+struct SavedFrame {
+    width: usize,
+    height: usize,
+    planes: [[u128; 64]; PLANE_COUNT]
+}
+
+///Implements VideoDriver for the debugger. It does not draw anything, it just keeps the last frame so that the debugger can print it.
+pub struct DebugDisplay {
+    frame: Rc<RefCell<SavedFrame>>
+}
+
+impl VideoDriver for DebugDisplay {
+    fn draw(&mut self, frame: &Frame) {
+	let mut saved = self.frame.borrow_mut();
+	saved.width = frame.width;
+	saved.height = frame.height;
+	saved.planes = *frame.planes;
+    }
+}
+
+///Implements KeyboardDriver for the debugger. The keys that are held down are set with the debugger's "key" command.
+pub struct DebugKeypad {
+    keys: Rc<Cell<u16>>,
+    reported: u16 //the keys the chip 8 has been told are held down.
+}
+
+impl KeyboardDriver for DebugKeypad {
+    fn next_event(&mut self) -> Option<KeyEvent> {
+	return keyboard::next_mask_event(&mut self.reported, self.keys.get());
+    }
+}
+
+///Why the program stopped running after a step or continue.
+enum StopReason {
+    Finished,
+    Breakpoint(usize),
+    Halted,
+    Interrupted,
+    Failed(Chip8Error)
+}
+
+///The debugger itself. It holds the breakpoints, and shares the screen and keypad with the DebugDisplay and DebugKeypad it hands out.
+pub struct Debugger {
+    breakpoints: Vec<Breakpoint>,
+    frame: Rc<RefCell<SavedFrame>>,
+    keys: Rc<Cell<u16>>
+}
+
+impl Debugger {
+    ///returns a new debugger with no breakpoints.
+    pub fn new() -> Self {
+	return Debugger {
+	    breakpoints: Vec::new(),
+	    frame: Rc::new(RefCell::new(SavedFrame { width: 64, height: 32, planes: [[0u128; 64]; PLANE_COUNT] })),
+	    keys: Rc::new(Cell::new(0))
+	};
+    }
+
+    ///returns the VideoDriver that has to be given to the TimedRunner, so that the debugger can show the screen.
+    pub fn display(&self) -> DebugDisplay {
+	return DebugDisplay { frame: self.frame.clone() };
+    }
+
+    ///returns the KeyboardDriver that has to be given to the TimedRunner, so that the debugger can press keys.
+    pub fn keypad(&self) -> DebugKeypad {
+	return DebugKeypad { keys: self.keys.clone(), reported: 0 };
+    }
+
+    ///adds a breakpoint, and returns its number.
+    pub fn add_breakpoint(&mut self, breakpoint: Breakpoint) -> usize {
+	self.breakpoints.push(breakpoint);
+	return self.breakpoints.len() - 1;
+    }
+
+    ///runs the debugger prompt, reading commands from input and writing the results to output, until "quit" is typed or input ends.
+    pub fn run<R: BufRead, W: Write>(&mut self, runner: &mut TimedRunner, mut input: R, mut output: W) -> io::Result<()> {
+	writeln!(output, "chip 8 debugger, type \"help\" for the list of commands")?;
+	self.print_location(runner, &mut output)?;
+	loop {
+	    write!(output, "(chip8) ")?;
+	    output.flush()?;
+	    let mut line = String::new();
+	    if input.read_line(&mut line)? == 0 {
+		return Ok(());
+	    }
+	    if !self.execute(runner, line.trim(), &mut output)? {
+		return Ok(());
+	    }
+	}
+    }
+
+    ///runs a single debugger command. returns false if the debugger should quit.
+    fn execute<W: Write>(&mut self, runner: &mut TimedRunner, command: &str, output: &mut W) -> io::Result<bool> {
+	let words = command.split_whitespace().collect::<Vec<&str>>();
+	let Some(name) = words.first() else {
+	    return Ok(true);
+	};
+	match (*name, &words[1..]) {
+	    ("break" | "b", ["op", pattern]) => match Breakpoint::from_opcode_pattern(pattern) {
+		Some(breakpoint) => {
+		    let number = self.add_breakpoint(breakpoint);
+		    writeln!(output, "breakpoint {} at {}", number, self.breakpoints[number])?;
+		},
+		None => writeln!(output, "\"{}\" is not an opcode pattern, it should be 4 hex digits or X, Y, N or K, like FX0A", pattern)?
+	    },
+	    ("break" | "b", [address]) => match parse_hex(address).map(u16::try_from) {
+		Some(Ok(address)) => {
+		    let number = self.add_breakpoint(Breakpoint::Address(address));
+		    writeln!(output, "breakpoint {} at {}", number, self.breakpoints[number])?;
+		},
+		Some(Err(_)) => writeln!(output, "{} is too big, the program counter only goes up to 0xffff", address)?,
+		None => writeln!(output, "\"{}\" is not a hex address", address)?
+	    },
+	    ("delete" | "d", [number]) => match number.parse::<usize>() {
+		Ok(number) if number < self.breakpoints.len() => {
+		    writeln!(output, "deleted breakpoint {} at {}", number, self.breakpoints.remove(number))?;
+		},
+		_ => writeln!(output, "there is no breakpoint {}", number)?
+	    },
+	    ("breakpoints" | "bl", []) => {
+		if self.breakpoints.is_empty() {
+		    writeln!(output, "no breakpoints")?;
+		}
+		for (number, breakpoint) in self.breakpoints.iter().enumerate() {
+		    writeln!(output, "{}: {}", number, breakpoint)?;
+		}
+	    },
+	    ("watch" | "w", [addresses, accesses @ ..]) if accesses.len() <= 1 => {
+		match parse_watchpoint(addresses, accesses.first().copied().unwrap_or("rw")) {
+		    Some(watchpoint) => {
+			let number = runner.system_mut().add_watchpoint(watchpoint);
+			writeln!(output, "watchpoint {} on {}", number, runner.system().watchpoints()[number])?;
+		    },
+		    None => writeln!(output, "usage: watch <hex address>[-<hex end address>] [any of r, w and x]")?
+		}
+	    },
+	    ("unwatch", [number]) => match number.parse::<usize>().ok().and_then(|number| runner.system_mut().remove_watchpoint(number)) {
+		Some(watchpoint) => writeln!(output, "deleted watchpoint {} on {}", number, watchpoint)?,
+		None => writeln!(output, "there is no watchpoint {}", number)?
+	    },
+	    ("watchpoints" | "wl", []) => {
+		let watchpoints = runner.system().watchpoints();
+		if watchpoints.is_empty() {
+		    writeln!(output, "no watchpoints")?;
+		}
+		for (number, watchpoint) in watchpoints.iter().enumerate() {
+		    writeln!(output, "{}: {}", number, watchpoint)?;
+		}
+	    },
+	    ("step" | "s", arguments) => {
+		let count = match arguments.first().map(|count| count.parse::<u64>()) {
+		    None => 1,
+		    Some(Ok(count)) => count,
+		    Some(Err(_)) => {
+			writeln!(output, "the step count should be a number")?;
+			return Ok(true);
+		    }
+		};
+		let reason = self.run_instructions(runner, Some(count), false, |_| false);
+		self.report(runner, reason, output)?;
+	    },
+	    ("back" | "bk", arguments) => {
+		let count = match arguments.first().map(|count| count.parse::<u64>()) {
+		    None => 1,
+		    Some(Ok(count)) => count,
+		    Some(Err(_)) => {
+			writeln!(output, "the step count should be a number")?;
+			return Ok(true);
+		    }
+		};
+		for _ in 0..count {
+		    match runner.step_back() {
+			Ok(true) => {},
+			Ok(false) => {
+			    writeln!(output, "there is no snapshot far enough back to go to")?;
+			    break;
+			},
+			Err(error) => {
+			    writeln!(output, "error: {}", error)?;
+			    break;
+			}
+		    }
+		}
+		self.print_location(runner, output)?;
+	    },
+	    ("next" | "n", []) => {
+		let system = runner.system();
+		let reason = if system.next_opcode() & 0xF000 == 0x2000 { //a subroutine call, run until it returns to the instruction after it.
+		    let return_address = system.program_counter().wrapping_add(2);
+		    let depth = system.stack().len();
+		    self.run_instructions(runner, None, false, |system| system.program_counter() == return_address && system.stack().len() == depth)
+		} else {
+		    self.run_instructions(runner, Some(1), false, |_| false)
+		};
+		self.report(runner, reason, output)?;
+	    },
+	    ("continue" | "c", []) => {
+		INTERRUPTED.store(false, Ordering::SeqCst);
+		let reason = self.run_instructions(runner, None, true, |_| false);
+		self.report(runner, reason, output)?;
+	    },
+	    ("registers" | "r", []) => {
+		writeln!(output, "{}", format_registers(runner.system()))?;
+	    },
+	    ("stack", []) => {
+		let stack = runner.system().stack();
+		if stack.is_empty() {
+		    writeln!(output, "the stack is empty")?;
+		}
+		for (depth, address) in stack.iter().enumerate().rev() {
+		    writeln!(output, "{}: {:#06x}", depth, address)?;
+		}
+	    },
+	    ("timers", []) => {
+		let (delay, sound) = runner.system().timers();
+		writeln!(output, "delay={} sound={}", delay, sound)?;
+	    },
+	    ("memory" | "m", arguments) if !arguments.is_empty() && arguments.len() <= 2 => {
+		let start = parse_hex(arguments[0]);
+		let length = arguments.get(1).map_or(Some(64), |length| length.parse::<usize>().ok());
+		match (start, length) {
+		    (Some(start), Some(length)) => print_memory(runner.system(), start, length, output)?,
+		    _ => writeln!(output, "usage: memory <hex address> [length]")?
+		}
+	    },
+	    ("screen", []) => self.print_screen(output)?,
+	    ("key", ["none"]) => self.keys.set(0),
+	    ("key", keys) if !keys.is_empty() => match keys.iter().map(|key| parse_hex(key).filter(|key| *key < 16)).collect::<Option<Vec<usize>>>() {
+		Some(keys) => self.keys.set(keys.iter().fold(0, |mask, key| mask | (1 << key))),
+		None => writeln!(output, "the keys should be hex digits from 0 to F, or none")?
+	    },
+	    ("help" | "h", []) => writeln!(output, "{}", HELP)?,
+	    ("quit" | "q", []) => return Ok(false),
+	    _ => writeln!(output, "unknown command \"{}\", type \"help\" for the list of commands", command)?
+	}
+	return Ok(true);
+    }
+
+    ///runs instructions until limit instructions have run, until done() says the program got where it should, or until a breakpoint,
+    /// ctrl-c, the end of the program or an error stops it. if paced is true, the instructions run at the normal chip 8 speed.
+    fn run_instructions<F: Fn(&ChipSystem) -> bool>(&self, runner: &mut TimedRunner, limit: Option<u64>, paced: bool, done: F) -> StopReason {
+	let mut executed = 0;
+	loop {
+	    if runner.is_halted() {
+		return StopReason::Halted;
+	    }
+	    let result = if paced {runner.decode_next_timed(1.0)} else {runner.decode_next_immediately()};
+	    if let Err(error) = result {
+		return StopReason::Failed(error);
+	    }
+	    executed += 1;
+	    if done(runner.system()) || limit.is_some_and(|limit| executed >= limit) {
+		return StopReason::Finished;
+	    }
+	    if let Some(number) = self.breakpoint_hit(runner.system()) {
+		return StopReason::Breakpoint(number);
+	    }
+	    if INTERRUPTED.swap(false, Ordering::SeqCst) {
+		return StopReason::Interrupted;
+	    }
+	}
+    }
+
+    ///returns the number of the first breakpoint that matches the next instruction, if there is one.
+    fn breakpoint_hit(&self, system: &ChipSystem) -> Option<usize> {
+	let address = system.program_counter();
+	let opcode = system.next_opcode();
+	return self.breakpoints.iter().position(|breakpoint| breakpoint.matches(address, opcode));
+    }
+
+    ///tells you why the program stopped and where it is now.
+    fn report<W: Write>(&self, runner: &TimedRunner, reason: StopReason, output: &mut W) -> io::Result<()> {
+	match reason {
+	    StopReason::Finished => {},
+	    StopReason::Breakpoint(number) => writeln!(output, "hit breakpoint {} ({})", number, self.breakpoints[number])?,
+	    StopReason::Halted => writeln!(output, "the program has exited")?,
+	    StopReason::Interrupted => writeln!(output, "interrupted")?,
+	    StopReason::Failed(error @ Chip8Error::WatchpointHit { .. }) => writeln!(output, "{}", error)?,
+	    StopReason::Failed(error) => writeln!(output, "error: {}", error)?
+	}
+	return self.print_location(runner, output);
+    }
+
+    ///prints the address and opcode of the next instruction.
+    fn print_location<W: Write>(&self, runner: &TimedRunner, output: &mut W) -> io::Result<()> {
+	let system = runner.system();
+	let waiting = if runner.is_waiting_for_key() {"  (waiting for a key, use the key command to press one and then let go of it)"} else {""};
+	return writeln!(output, "{:#06x}: {:04x}  {}{}", system.program_counter(), system.next_opcode(), system.next_instruction(), waiting);
+    }
+
+    ///prints the last frame the chip 8 drew, a # is a lit pixel. with XO-CHIP colors, pixels lit in other planes are printed as their color number.
+    fn print_screen<W: Write>(&self, output: &mut W) -> io::Result<()> {
+	let saved = self.frame.borrow();
+	let frame = Frame { width: saved.width, height: saved.height, planes: &saved.planes };
+	return write!(output, "{}", frame);
+    }
+}
+
+///returns V0 - VF, eight to a line, followed by a line with I and the program counter. This is what the "registers" command prints.
+pub fn format_registers(system: &ChipSystem) -> String {
+    let mut lines = system.variable_registers().chunks(8).enumerate().map(|row| {
+	return row.1.iter()
+	    .enumerate()
+	    .map(|(i, value)| format!("V{:X}={:02x}", (row.0 * 8) + i, value))
+	    .collect::<Vec<String>>()
+	    .join(" ");
+    }).collect::<Vec<String>>();
+    lines.push(format!("I={:#06x} PC={:#06x}", system.index_register(), system.program_counter()));
+    return lines.join("\n");
+}
+
+///prints memory as rows of 16 hex bytes, each row starting with its address.
+fn print_memory<W: Write>(system: &ChipSystem, start: usize, length: usize, output: &mut W) -> io::Result<()> {
+    let memory = system.memory();
+    let end = start.saturating_add(length).min(memory.len());
+    if start >= end {
+	return writeln!(output, "the address is past the end of memory ({:#06x})", memory.len());
+    }
+    for row_start in (start..end).step_by(16) {
+	let bytes = memory[row_start..(row_start + 16).min(end)]
+	    .iter()
+	    .map(|byte| format!("{:02x}", byte))
+	    .collect::<Vec<String>>();
+	writeln!(output, "{:#06x}: {}", row_start, bytes.join(" "))?;
+    }
+    return Ok(());
+}
+
+///reads a watchpoint from an address or address range like "300" or "0x300-0x30f", and the accesses to watch like "rw".
+fn parse_watchpoint(addresses: &str, accesses: &str) -> Option<Watchpoint> {
+    let (start, end) = match addresses.split_once('-') {
+	Some((start, end)) => (parse_hex(start)?, parse_hex(end)?),
+	None => (parse_hex(addresses)?, parse_hex(addresses)?)
+    };
+    if start > end || accesses.is_empty() || accesses.chars().any(|access| !"rwx".contains(access)) {
+	return None;
+    }
+    return Some(Watchpoint {
+	start,
+	end,
+	on_read: accesses.contains('r'),
+	on_write: accesses.contains('w'),
+	on_execute: accesses.contains('x')
+    });
+}
+
+///reads a hex number, with or without a 0x in front of it.
+fn parse_hex(text: &str) -> Option<usize> {
+    let digits = text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")).unwrap_or(text);
+    return usize::from_str_radix(digits, 16).ok();
+}
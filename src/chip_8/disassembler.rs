@@ -0,0 +1,588 @@
+//! This module turns chip 8 machine code back into readable instructions.
+//! The Instruction enum is a decoded opcode, and it can be printed in the classic mnemonic syntax (LD V1, 0x20) or in Octo's syntax (v1 := 0x20).
+//! The disassemble() function goes through a whole program, following the jumps and calls from the start of the program
+//!  to figure out which bytes are code and which are sprite data, and gives labels to everything that is jumped to, called, or pointed at by I.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+///the address programs are loaded at, and where they start running.
+pub const PROGRAM_START: u16 = 0x200;
+
+///The two syntaxes instructions can be printed in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Syntax {
+    ///the classic mnemonics, like "LD V1, 0x20" and "DRW V0, V1, 5".
+    Classic,
+    ///the syntax of the Octo assembler, like "v1 := 0x20" and "sprite v0 v1 5".
+    Octo
+}
+
+///A single decoded chip 8 instruction, including the SUPER-CHIP and XO-CHIP ones.
+///x and y are register numbers, the other numbers are the immediate values of the instruction.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Instruction {
+    ///00E0: clear the screen
+    ClearScreen,
+    ///00EE: return from a subroutine
+    Return,
+    ///00CN: scroll the screen down n rows (SUPER-CHIP)
+    ScrollDown(u8),
+    ///00DN: scroll the screen up n rows (XO-CHIP)
+    ScrollUp(u8),
+    ///00FB: scroll the screen right 4 pixels (SUPER-CHIP)
+    ScrollRight,
+    ///00FC: scroll the screen left 4 pixels (SUPER-CHIP)
+    ScrollLeft,
+    ///00FD: exit the interpreter (SUPER-CHIP)
+    Exit,
+    ///00FE: switch to low resolution (SUPER-CHIP)
+    LowResolution,
+    ///00FF: switch to high resolution (SUPER-CHIP)
+    HighResolution,
+    ///0NNN: call a machine code routine on the original hardware, not supported by the emulator
+    MachineCall(u16),
+    ///1NNN: jump to nnn
+    Jump(u16),
+    ///2NNN: call the subroutine at nnn
+    Call(u16),
+    ///3XNN: skip the next instruction if vx == nn
+    SkipIfEqual(usize, u8),
+    ///4XNN: skip the next instruction if vx != nn
+    SkipIfNotEqual(usize, u8),
+    ///5XY0: skip the next instruction if vx == vy
+    SkipIfRegistersEqual(usize, usize),
+    ///5XY2: save vx to vy into memory at I (XO-CHIP)
+    SaveRange(usize, usize),
+    ///5XY3: load vx to vy from memory at I (XO-CHIP)
+    LoadRange(usize, usize),
+    ///6XNN: vx = nn
+    LoadImmediate(usize, u8),
+    ///7XNN: vx += nn
+    AddImmediate(usize, u8),
+    ///8XY0: vx = vy
+    Move(usize, usize),
+    ///8XY1: vx |= vy
+    Or(usize, usize),
+    ///8XY2: vx &= vy
+    And(usize, usize),
+    ///8XY3: vx ^= vy
+    Xor(usize, usize),
+    ///8XY4: vx += vy, vf is the carry
+    Add(usize, usize),
+    ///8XY5: vx -= vy, vf is the not borrow
+    Subtract(usize, usize),
+    ///8XY6: vx = vy >> 1
+    ShiftRight(usize, usize),
+    ///8XY7: vx = vy - vx, vf is the not borrow
+    SubtractReverse(usize, usize),
+    ///8XYE: vx = vy << 1
+    ShiftLeft(usize, usize),
+    ///9XY0: skip the next instruction if vx != vy
+    SkipIfRegistersNotEqual(usize, usize),
+    ///ANNN: I = nnn
+    LoadIndex(u16),
+    ///BNNN: jump to nnn + v0
+    JumpOffset(u16),
+    ///CXNN: vx = a random number & nn
+    Random(usize, u8),
+    ///DXYN: draw an n row sprite at vx, vy (n = 0 draws a 16x16 SUPER-CHIP sprite)
+    Draw(usize, usize, u8),
+    ///EX9E: skip the next instruction if the key in vx is pressed
+    SkipIfKey(usize),
+    ///EXA1: skip the next instruction if the key in vx is not pressed
+    SkipIfNotKey(usize),
+    ///F000 NNNN: I = nnnn, a four byte instruction (XO-CHIP)
+    LoadLongIndex(u16),
+    ///FN01: select the drawing planes n (XO-CHIP)
+    SelectPlanes(u8),
+    ///F002: load the audio pattern from I (XO-CHIP)
+    LoadAudio,
+    ///FX07: vx = the delay timer
+    ReadDelay(usize),
+    ///FX0A: wait for a key and put it in vx
+    WaitKey(usize),
+    ///FX15: the delay timer = vx
+    SetDelay(usize),
+    ///FX18: the sound timer = vx
+    SetSound(usize),
+    ///FX1E: I += vx
+    AddIndex(usize),
+    ///FX29: I = the address of the font character in vx
+    LoadFont(usize),
+    ///FX30: I = the address of the big font character in vx (SUPER-CHIP)
+    LoadBigFont(usize),
+    ///FX33: store the decimal digits of vx at I
+    StoreBcd(usize),
+    ///FX3A: the audio pitch = vx (XO-CHIP)
+    SetPitch(usize),
+    ///FX55: store v0 to vx at I
+    Store(usize),
+    ///FX65: load v0 to vx from I
+    Load(usize),
+    ///FX75: store v0 to vx in the flag registers (SUPER-CHIP)
+    StoreFlags(usize),
+    ///FX85: load v0 to vx from the flag registers (SUPER-CHIP)
+    LoadFlags(usize),
+    ///any opcode that is not an instruction
+    Unknown(u16)
+}
+
+impl Instruction {
+    ///decodes a two byte opcode. F000 is only an instruction together with the two bytes after it, so on its own it is Unknown,
+    /// see decode_long() and decode_at().
+    pub fn decode(opcode: u16) -> Self {
+	let x = ((opcode & 0x0F00) >> 8) as usize;
+	let y = ((opcode & 0x00F0) >> 4) as usize;
+	let n = (opcode & 0x000F) as u8;
+	let nn = (opcode & 0x00FF) as u8;
+	let nnn = opcode & 0x0FFF;
+	return match (opcode & 0xF000) >> 12 {
+	    0x0 => match nnn {
+		0x0E0 => Instruction::ClearScreen,
+		0x0EE => Instruction::Return,
+		0x0C0..=0x0CF => Instruction::ScrollDown(n),
+		0x0D0..=0x0DF => Instruction::ScrollUp(n),
+		0x0FB => Instruction::ScrollRight,
+		0x0FC => Instruction::ScrollLeft,
+		0x0FD => Instruction::Exit,
+		0x0FE => Instruction::LowResolution,
+		0x0FF => Instruction::HighResolution,
+		_ => Instruction::MachineCall(nnn)
+	    },
+	    0x1 => Instruction::Jump(nnn),
+	    0x2 => Instruction::Call(nnn),
+	    0x3 => Instruction::SkipIfEqual(x, nn),
+	    0x4 => Instruction::SkipIfNotEqual(x, nn),
+	    0x5 => match n {
+		0x0 => Instruction::SkipIfRegistersEqual(x, y),
+		0x2 => Instruction::SaveRange(x, y),
+		0x3 => Instruction::LoadRange(x, y),
+		_ => Instruction::Unknown(opcode)
+	    },
+	    0x6 => Instruction::LoadImmediate(x, nn),
+	    0x7 => Instruction::AddImmediate(x, nn),
+	    0x8 => match n {
+		0x0 => Instruction::Move(x, y),
+		0x1 => Instruction::Or(x, y),
+		0x2 => Instruction::And(x, y),
+		0x3 => Instruction::Xor(x, y),
+		0x4 => Instruction::Add(x, y),
+		0x5 => Instruction::Subtract(x, y),
+		0x6 => Instruction::ShiftRight(x, y),
+		0x7 => Instruction::SubtractReverse(x, y),
+		0xE => Instruction::ShiftLeft(x, y),
+		_ => Instruction::Unknown(opcode)
+	    },
+	    0x9 if n == 0 => Instruction::SkipIfRegistersNotEqual(x, y),
+	    0xA => Instruction::LoadIndex(nnn),
+	    0xB => Instruction::JumpOffset(nnn),
+	    0xC => Instruction::Random(x, nn),
+	    0xD => Instruction::Draw(x, y, n),
+	    0xE => match nn {
+		0x9E => Instruction::SkipIfKey(x),
+		0xA1 => Instruction::SkipIfNotKey(x),
+		_ => Instruction::Unknown(opcode)
+	    },
+	    0xF => match nn {
+		0x01 => Instruction::SelectPlanes(x as u8),
+		0x02 if x == 0 => Instruction::LoadAudio,
+		0x07 => Instruction::ReadDelay(x),
+		0x0A => Instruction::WaitKey(x),
+		0x15 => Instruction::SetDelay(x),
+		0x18 => Instruction::SetSound(x),
+		0x1E => Instruction::AddIndex(x),
+		0x29 => Instruction::LoadFont(x),
+		0x30 => Instruction::LoadBigFont(x),
+		0x33 => Instruction::StoreBcd(x),
+		0x3A => Instruction::SetPitch(x),
+		0x55 => Instruction::Store(x),
+		0x65 => Instruction::Load(x),
+		0x75 => Instruction::StoreFlags(x),
+		0x85 => Instruction::LoadFlags(x),
+		_ => Instruction::Unknown(opcode)
+	    },
+	    _ => Instruction::Unknown(opcode)
+	};
+    }
+
+    ///decodes an opcode together with the two bytes after it. This only makes a difference for the XO-CHIP F000 NNNN instruction,
+    /// every other opcode is decoded just like decode() does.
+    pub fn decode_long(opcode: u16, operand: u16) -> Self {
+	if opcode == 0xF000 {
+	    return Instruction::LoadLongIndex(operand);
+	}
+	return Instruction::decode(opcode);
+    }
+
+    ///decodes the instruction at the address in memory, or returns None if the instruction goes past the end of memory.
+    ///if xo_chip is true, F000 is read as the four byte F000 NNNN instruction.
+    pub fn decode_at(memory: &[u8], address: usize, xo_chip: bool) -> Option<Self> {
+	let word = |at: usize| Some(((*memory.get(at)? as u16) << 8) | *memory.get(at + 1)? as u16);
+	let opcode = word(address)?;
+	if xo_chip && opcode == 0xF000 {
+	    return Some(Instruction::decode_long(opcode, word(address + 2)?));
+	}
+	return Some(Instruction::decode(opcode));
+    }
+
+    ///returns how many bytes long the instruction is, 4 for F000 NNNN and 2 for everything else.
+    pub fn length(&self) -> usize {
+	return match self {
+	    Instruction::LoadLongIndex(_) => 4,
+	    _ => 2
+	};
+    }
+
+    ///turns the instruction back into its opcode, the reverse of decode(). Decoding the bytes that come out gives back the same instruction.
+    pub fn encode(&self) -> Vec<u8> {
+	let xy = |opcode: u16, x: usize, y: usize| opcode | ((x as u16) << 8) | ((y as u16) << 4);
+	let xnn = |opcode: u16, x: usize, nn: u8| opcode | ((x as u16) << 8) | nn as u16;
+	let opcode = match *self {
+	    Instruction::ClearScreen => 0x00E0,
+	    Instruction::Return => 0x00EE,
+	    Instruction::ScrollDown(n) => 0x00C0 | n as u16,
+	    Instruction::ScrollUp(n) => 0x00D0 | n as u16,
+	    Instruction::ScrollRight => 0x00FB,
+	    Instruction::ScrollLeft => 0x00FC,
+	    Instruction::Exit => 0x00FD,
+	    Instruction::LowResolution => 0x00FE,
+	    Instruction::HighResolution => 0x00FF,
+	    Instruction::MachineCall(nnn) => nnn,
+	    Instruction::Jump(nnn) => 0x1000 | nnn,
+	    Instruction::Call(nnn) => 0x2000 | nnn,
+	    Instruction::SkipIfEqual(x, nn) => xnn(0x3000, x, nn),
+	    Instruction::SkipIfNotEqual(x, nn) => xnn(0x4000, x, nn),
+	    Instruction::SkipIfRegistersEqual(x, y) => xy(0x5000, x, y),
+	    Instruction::SaveRange(x, y) => xy(0x5002, x, y),
+	    Instruction::LoadRange(x, y) => xy(0x5003, x, y),
+	    Instruction::LoadImmediate(x, nn) => xnn(0x6000, x, nn),
+	    Instruction::AddImmediate(x, nn) => xnn(0x7000, x, nn),
+	    Instruction::Move(x, y) => xy(0x8000, x, y),
+	    Instruction::Or(x, y) => xy(0x8001, x, y),
+	    Instruction::And(x, y) => xy(0x8002, x, y),
+	    Instruction::Xor(x, y) => xy(0x8003, x, y),
+	    Instruction::Add(x, y) => xy(0x8004, x, y),
+	    Instruction::Subtract(x, y) => xy(0x8005, x, y),
+	    Instruction::ShiftRight(x, y) => xy(0x8006, x, y),
+	    Instruction::SubtractReverse(x, y) => xy(0x8007, x, y),
+	    Instruction::ShiftLeft(x, y) => xy(0x800E, x, y),
+	    Instruction::SkipIfRegistersNotEqual(x, y) => xy(0x9000, x, y),
+	    Instruction::LoadIndex(nnn) => 0xA000 | nnn,
+	    Instruction::JumpOffset(nnn) => 0xB000 | nnn,
+	    Instruction::Random(x, nn) => xnn(0xC000, x, nn),
+	    Instruction::Draw(x, y, n) => xy(0xD000, x, y) | n as u16,
+	    Instruction::SkipIfKey(x) => xnn(0xE000, x, 0x9E),
+	    Instruction::SkipIfNotKey(x) => xnn(0xE000, x, 0xA1),
+	    Instruction::LoadLongIndex(nnnn) => return vec![0xF0, 0x00, (nnnn >> 8) as u8, nnnn as u8],
+	    Instruction::SelectPlanes(n) => xnn(0xF000, n as usize, 0x01),
+	    Instruction::LoadAudio => 0xF002,
+	    Instruction::ReadDelay(x) => xnn(0xF000, x, 0x07),
+	    Instruction::WaitKey(x) => xnn(0xF000, x, 0x0A),
+	    Instruction::SetDelay(x) => xnn(0xF000, x, 0x15),
+	    Instruction::SetSound(x) => xnn(0xF000, x, 0x18),
+	    Instruction::AddIndex(x) => xnn(0xF000, x, 0x1E),
+	    Instruction::LoadFont(x) => xnn(0xF000, x, 0x29),
+	    Instruction::LoadBigFont(x) => xnn(0xF000, x, 0x30),
+	    Instruction::StoreBcd(x) => xnn(0xF000, x, 0x33),
+	    Instruction::SetPitch(x) => xnn(0xF000, x, 0x3A),
+	    Instruction::Store(x) => xnn(0xF000, x, 0x55),
+	    Instruction::Load(x) => xnn(0xF000, x, 0x65),
+	    Instruction::StoreFlags(x) => xnn(0xF000, x, 0x75),
+	    Instruction::LoadFlags(x) => xnn(0xF000, x, 0x85),
+	    Instruction::Unknown(opcode) => opcode
+	};
+	return vec![(opcode >> 8) as u8, opcode as u8];
+    }
+
+    ///returns true for the instructions that skip the next instruction when their condition is met.
+    pub fn is_skip(&self) -> bool {
+	return matches!(self,
+	    Instruction::SkipIfEqual(..) | Instruction::SkipIfNotEqual(..) |
+	    Instruction::SkipIfRegistersEqual(..) | Instruction::SkipIfRegistersNotEqual(..) |
+	    Instruction::SkipIfKey(_) | Instruction::SkipIfNotKey(_));
+    }
+
+    ///returns the address the instruction uses, if it has one: the target of a jump or call, or what I is set to.
+    pub fn target(&self) -> Option<u16> {
+	return match self {
+	    Instruction::Jump(address) | Instruction::Call(address) | Instruction::JumpOffset(address) |
+	    Instruction::LoadIndex(address) | Instruction::LoadLongIndex(address) => Some(*address),
+	    _ => None
+	};
+    }
+
+    ///returns the instruction as text in the given syntax. label_for is asked for a name for the instruction's target address,
+    /// and if it gives None, the address is written as a number.
+    pub fn text<F: Fn(u16) -> Option<String>>(&self, syntax: Syntax, label_for: F) -> String {
+	let address = |address: u16| label_for(address).unwrap_or(format!("{:#05x}", address));
+	let long_address = |address: u16| label_for(address).unwrap_or(format!("{:#06x}", address));
+	return match syntax {
+	    Syntax::Classic => match *self {
+		Instruction::ClearScreen => "CLS".to_string(),
+		Instruction::Return => "RET".to_string(),
+		Instruction::ScrollDown(n) => format!("SCD {}", n),
+		Instruction::ScrollUp(n) => format!("SCU {}", n),
+		Instruction::ScrollRight => "SCR".to_string(),
+		Instruction::ScrollLeft => "SCL".to_string(),
+		Instruction::Exit => "EXIT".to_string(),
+		Instruction::LowResolution => "LOW".to_string(),
+		Instruction::HighResolution => "HIGH".to_string(),
+		Instruction::MachineCall(nnn) => format!("SYS {}", address(nnn)),
+		Instruction::Jump(nnn) => format!("JP {}", address(nnn)),
+		Instruction::Call(nnn) => format!("CALL {}", address(nnn)),
+		Instruction::SkipIfEqual(x, nn) => format!("SE V{:X}, {:#04x}", x, nn),
+		Instruction::SkipIfNotEqual(x, nn) => format!("SNE V{:X}, {:#04x}", x, nn),
+		Instruction::SkipIfRegistersEqual(x, y) => format!("SE V{:X}, V{:X}", x, y),
+		Instruction::SaveRange(x, y) => format!("SAVE V{:X}, V{:X}", x, y),
+		Instruction::LoadRange(x, y) => format!("LOAD V{:X}, V{:X}", x, y),
+		Instruction::LoadImmediate(x, nn) => format!("LD V{:X}, {:#04x}", x, nn),
+		Instruction::AddImmediate(x, nn) => format!("ADD V{:X}, {:#04x}", x, nn),
+		Instruction::Move(x, y) => format!("LD V{:X}, V{:X}", x, y),
+		Instruction::Or(x, y) => format!("OR V{:X}, V{:X}", x, y),
+		Instruction::And(x, y) => format!("AND V{:X}, V{:X}", x, y),
+		Instruction::Xor(x, y) => format!("XOR V{:X}, V{:X}", x, y),
+		Instruction::Add(x, y) => format!("ADD V{:X}, V{:X}", x, y),
+		Instruction::Subtract(x, y) => format!("SUB V{:X}, V{:X}", x, y),
+		Instruction::ShiftRight(x, y) => format!("SHR V{:X}, V{:X}", x, y),
+		Instruction::SubtractReverse(x, y) => format!("SUBN V{:X}, V{:X}", x, y),
+		Instruction::ShiftLeft(x, y) => format!("SHL V{:X}, V{:X}", x, y),
+		Instruction::SkipIfRegistersNotEqual(x, y) => format!("SNE V{:X}, V{:X}", x, y),
+		Instruction::LoadIndex(nnn) => format!("LD I, {}", address(nnn)),
+		Instruction::JumpOffset(nnn) => format!("JP V0, {}", address(nnn)),
+		Instruction::Random(x, nn) => format!("RND V{:X}, {:#04x}", x, nn),
+		Instruction::Draw(x, y, n) => format!("DRW V{:X}, V{:X}, {}", x, y, n),
+		Instruction::SkipIfKey(x) => format!("SKP V{:X}", x),
+		Instruction::SkipIfNotKey(x) => format!("SKNP V{:X}", x),
+		Instruction::LoadLongIndex(nnnn) => format!("LD I, LONG {}", long_address(nnnn)),
+		Instruction::SelectPlanes(n) => format!("PLANE {}", n),
+		Instruction::LoadAudio => "AUDIO".to_string(),
+		Instruction::ReadDelay(x) => format!("LD V{:X}, DT", x),
+		Instruction::WaitKey(x) => format!("LD V{:X}, K", x),
+		Instruction::SetDelay(x) => format!("LD DT, V{:X}", x),
+		Instruction::SetSound(x) => format!("LD ST, V{:X}", x),
+		Instruction::AddIndex(x) => format!("ADD I, V{:X}", x),
+		Instruction::LoadFont(x) => format!("LD F, V{:X}", x),
+		Instruction::LoadBigFont(x) => format!("LD HF, V{:X}", x),
+		Instruction::StoreBcd(x) => format!("LD B, V{:X}", x),
+		Instruction::SetPitch(x) => format!("PITCH V{:X}", x),
+		Instruction::Store(x) => format!("LD [I], V{:X}", x),
+		Instruction::Load(x) => format!("LD V{:X}, [I]", x),
+		Instruction::StoreFlags(x) => format!("LD R, V{:X}", x),
+		Instruction::LoadFlags(x) => format!("LD V{:X}, R", x),
+		Instruction::Unknown(opcode) => format!("DW {:#06x}", opcode)
+	    },
+	    Syntax::Octo => match *self {
+		Instruction::ClearScreen => "clear".to_string(),
+		Instruction::Return => "return".to_string(),
+		Instruction::ScrollDown(n) => format!("scroll-down {}", n),
+		Instruction::ScrollUp(n) => format!("scroll-up {}", n),
+		Instruction::ScrollRight => "scroll-right".to_string(),
+		Instruction::ScrollLeft => "scroll-left".to_string(),
+		Instruction::Exit => "exit".to_string(),
+		Instruction::LowResolution => "lores".to_string(),
+		Instruction::HighResolution => "hires".to_string(),
+		Instruction::MachineCall(nnn) => format!("{:#04x} {:#04x} # machine code call", nnn >> 8, nnn & 0xFF),
+		Instruction::Jump(nnn) => format!("jump {}", address(nnn)),
+		Instruction::Call(nnn) => match label_for(nnn) {
+		    Some(label) => label,
+		    None => format!(":call {:#05x}", nnn)
+		},
+		Instruction::SkipIfEqual(x, nn) => format!("if v{:x} != {:#04x} then", x, nn),
+		Instruction::SkipIfNotEqual(x, nn) => format!("if v{:x} == {:#04x} then", x, nn),
+		Instruction::SkipIfRegistersEqual(x, y) => format!("if v{:x} != v{:x} then", x, y),
+		Instruction::SaveRange(x, y) => format!("save v{:x} - v{:x}", x, y),
+		Instruction::LoadRange(x, y) => format!("load v{:x} - v{:x}", x, y),
+		Instruction::LoadImmediate(x, nn) => format!("v{:x} := {:#04x}", x, nn),
+		Instruction::AddImmediate(x, nn) => format!("v{:x} += {:#04x}", x, nn),
+		Instruction::Move(x, y) => format!("v{:x} := v{:x}", x, y),
+		Instruction::Or(x, y) => format!("v{:x} |= v{:x}", x, y),
+		Instruction::And(x, y) => format!("v{:x} &= v{:x}", x, y),
+		Instruction::Xor(x, y) => format!("v{:x} ^= v{:x}", x, y),
+		Instruction::Add(x, y) => format!("v{:x} += v{:x}", x, y),
+		Instruction::Subtract(x, y) => format!("v{:x} -= v{:x}", x, y),
+		Instruction::ShiftRight(x, y) => format!("v{:x} >>= v{:x}", x, y),
+		Instruction::SubtractReverse(x, y) => format!("v{:x} =- v{:x}", x, y),
+		Instruction::ShiftLeft(x, y) => format!("v{:x} <<= v{:x}", x, y),
+		Instruction::SkipIfRegistersNotEqual(x, y) => format!("if v{:x} == v{:x} then", x, y),
+		Instruction::LoadIndex(nnn) => format!("i := {}", address(nnn)),
+		Instruction::JumpOffset(nnn) => format!("jump0 {}", address(nnn)),
+		Instruction::Random(x, nn) => format!("v{:x} := random {:#04x}", x, nn),
+		Instruction::Draw(x, y, n) => format!("sprite v{:x} v{:x} {}", x, y, n),
+		Instruction::SkipIfKey(x) => format!("if v{:x} -key then", x),
+		Instruction::SkipIfNotKey(x) => format!("if v{:x} key then", x),
+		Instruction::LoadLongIndex(nnnn) => format!("i := long {}", long_address(nnnn)),
+		Instruction::SelectPlanes(n) => format!("plane {}", n),
+		Instruction::LoadAudio => "audio".to_string(),
+		Instruction::ReadDelay(x) => format!("v{:x} := delay", x),
+		Instruction::WaitKey(x) => format!("v{:x} := key", x),
+		Instruction::SetDelay(x) => format!("delay := v{:x}", x),
+		Instruction::SetSound(x) => format!("buzzer := v{:x}", x),
+		Instruction::AddIndex(x) => format!("i += v{:x}", x),
+		Instruction::LoadFont(x) => format!("i := hex v{:x}", x),
+		Instruction::LoadBigFont(x) => format!("i := bighex v{:x}", x),
+		Instruction::StoreBcd(x) => format!("bcd v{:x}", x),
+		Instruction::SetPitch(x) => format!("pitch := v{:x}", x),
+		Instruction::Store(x) => format!("save v{:x}", x),
+		Instruction::Load(x) => format!("load v{:x}", x),
+		Instruction::StoreFlags(x) => format!("saveflags v{:x}", x),
+		Instruction::LoadFlags(x) => format!("loadflags v{:x}", x),
+		Instruction::Unknown(opcode) => format!("{:#04x} {:#04x}", opcode >> 8, opcode & 0xFF)
+	    }
+	};
+    }
+
+    ///returns a wrapper that prints the instruction in Octo's syntax, the normal Display prints the classic syntax.
+    pub fn octo(&self) -> OctoSyntax<'_> {
+	return OctoSyntax(self);
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+	return write!(f, "{}", self.text(Syntax::Classic, |_| None));
+    }
+}
+
+///Prints an Instruction in Octo's syntax, see Instruction::octo().
+pub struct OctoSyntax <'i> (&'i Instruction);
+
+impl fmt::Display for OctoSyntax<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+	return write!(f, "{}", self.0.text(Syntax::Octo, |_| None));
+    }
+}
+
+///A piece of a disassembled program, it is either an instruction or some bytes of data.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Item {
+    Code(Instruction),
+    Data(Vec<u8>)
+}
+
+///A whole disassembled program. The items are in order of their address, and together they cover every byte of the program.
+pub struct Disassembly {
+    pub items: Vec<(u16, Item)>,
+    pub labels: BTreeMap<u16, String>
+}
+
+///the most bytes of data that are put on a single line.
+const DATA_BYTES_PER_LINE: usize = 8;
+
+impl Disassembly {
+    ///returns the whole disassembly as text in the given syntax, with a label line before every labelled address
+    /// and a comment with the address and raw bytes after every instruction.
+    pub fn to_text(&self, syntax: Syntax) -> String {
+	let label_for = |address: u16| self.labels.get(&address).cloned();
+	let mut text = String::new();
+	for (address, item) in self.items.iter() {
+	    if let Some(label) = self.labels.get(address) {
+		match syntax {
+		    Syntax::Classic => text.push_str(&format!("{}:\n", label)),
+		    Syntax::Octo => text.push_str(&format!(": {}\n", label))
+		}
+	    }
+	    let comment = match syntax {
+		Syntax::Classic => ";",
+		Syntax::Octo => "#"
+	    };
+	    match item {
+		Item::Code(instruction) => {
+		    let line = instruction.text(syntax, label_for);
+		    text.push_str(&format!("    {:<28} {} {:#06x}\n", line, comment, address));
+		},
+		Item::Data(bytes) => {
+		    for (row, chunk) in bytes.chunks(DATA_BYTES_PER_LINE).enumerate() {
+			let hex = chunk.iter().map(|byte| format!("{:#04x}", byte)).collect::<Vec<String>>();
+			let line = match syntax {
+			    Syntax::Classic => format!("DB {}", hex.join(", ")),
+			    Syntax::Octo => hex.join(" ")
+			};
+			text.push_str(&format!("    {:<28} {} {:#06x}\n", line, comment, *address as usize + (row * DATA_BYTES_PER_LINE)));
+		    }
+		}
+	    }
+	}
+	return text;
+    }
+}
+
+///Disassembles a program that is loaded at PROGRAM_START.
+///Starting from the first instruction, every instruction that can be reached is followed: jumps go to their target, calls go to their
+/// target and carry on after the call, skips carry on at both the next and the one after, and returns, exits and BNNN jumps stop.
+///Everything that is reached this way is code, and the rest is data. Labels are made for the targets of jumps and calls,
+/// and for the addresses I is pointed at, as long as they are inside the program.
+///if xo_chip is true, F000 is read as the four byte F000 NNNN instruction.
+pub fn disassemble(program: &[u8], xo_chip: bool) -> Disassembly {
+    let start = PROGRAM_START as usize;
+    let end = start + program.len();
+    let mut memory = vec![0u8; start];
+    memory.extend_from_slice(program);
+
+    let mut code = BTreeMap::new(); //the address of every instruction found, and the instruction.
+    let mut targets = BTreeSet::from([start]); //the start always gets a label, Octo programs begin at "main".
+    let mut to_visit = vec![start];
+    while let Some(address) = to_visit.pop() {
+	if address < start || address >= end || code.contains_key(&address) {
+	    continue;
+	}
+	let Some(instruction) = Instruction::decode_at(&memory, address, xo_chip) else {
+	    continue;
+	};
+	let next = address + instruction.length();
+	code.insert(address, instruction);
+	if let Some(target) = instruction.target() {
+	    targets.insert(target as usize);
+	}
+	match instruction {
+	    Instruction::Jump(target) => to_visit.push(target as usize),
+	    Instruction::Call(target) => {
+		to_visit.push(target as usize);
+		to_visit.push(next);
+	    },
+	    Instruction::Return | Instruction::Exit | Instruction::JumpOffset(_) | Instruction::Unknown(_) | Instruction::MachineCall(_) => {},
+	    skip if skip.is_skip() => {
+		to_visit.push(next);
+		if let Some(skipped) = Instruction::decode_at(&memory, next, xo_chip) {
+		    to_visit.push(next + skipped.length());
+		}
+	    },
+	    _ => to_visit.push(next)
+	}
+    }
+
+    //instructions that overlap an earlier one cant both be shown, the later one is dropped and its bytes are shown by the earlier one.
+    let mut covered_until = start;
+    code.retain(|address, instruction| {
+	if *address < covered_until {
+	    return false;
+	}
+	covered_until = *address + instruction.length();
+	return true;
+    });
+
+    //labels can only be put where an item starts, so an address in the middle of an instruction does not get a label.
+    let inside_instruction = |target: usize| code.range(..target).next_back().is_some_and(|(address, instruction)| target < address + instruction.length());
+    let labels = targets
+	.into_iter()
+	.filter(|target| *target >= start && *target < end && !inside_instruction(*target))
+	.map(|target| (target as u16, if target == start {"main".to_string()} else {format!("label_{:03x}", target)}))
+	.collect::<BTreeMap<u16, String>>();
+
+    let mut items = Vec::new();
+    let mut address = start;
+    while address < end {
+	if let Some(instruction) = code.get(&address) {
+	    items.push((address as u16, Item::Code(*instruction)));
+	    address += instruction.length();
+	} else {
+	    //data runs until the next instruction or label, so that every label lands on the start of an item.
+	    let next_code = code.range((address + 1)..).next().map_or(end, |(next, _)| *next);
+	    let next_label = labels.range((address as u16 + 1)..).next().map_or(end, |(next, _)| *next as usize);
+	    let data_end = next_code.min(next_label).min(end);
+	    items.push((address as u16, Item::Data(memory[address..data_end].to_vec())));
+	    address = data_end;
+	}
+    }
+    return Disassembly { items, labels };
+}
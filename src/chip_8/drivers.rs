@@ -3,83 +3,302 @@
 //!The following are provided already, other drivers can be created to use different methods of drawing the screen.
 //!You can do this by just implementing the VideoDriver, SoundDriver, and KeyboardDriver traits onto your driver, then supplying them to the chip.
 
-use crate::chip_8::video::VideoDriver;
+use crate::chip_8::video::{VideoDriver, Frame, PLANE_COUNT};
 use crate::chip_8::timers::SoundDriver;
-use crate::chip_8::keyboard::KeyboardDriver;
+use crate::chip_8::audio::SampleStream;
+use crate::chip_8::keyboard::{self, KeyboardDriver, KeyEvent};
+use crate::chip_8::keymap::Keymap;
+use crate::chip_8::palette::{Palette, COLOR_COUNT};
 
-///Implements VideoDriver to draw the chip 8 display in the terminal.
-///This driver is extremely rudimentary and simple, it just println's each line in the binary representation as it is stored.
+///How StdoutDisplay turns pixels into characters in the terminal.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Renderer {
+    ///picks the biggest of the others that fits in the terminal, and checks again whenever the terminal is resized.
+    Auto,
+    ///two full block characters per pixel, so the pixels are square. 128x32 characters, the high resolution screen uses half blocks in the same space.
+    Blocks,
+    ///one character per two pixels on top of each other, using the upper and lower half blocks. 64x16 characters, or 128x32 in high resolution.
+    HalfBlocks,
+    ///one Braille pattern per 2x4 pixels. 32x8 characters, or 64x16 in high resolution.
+    Braille
+}
+
+impl Renderer {
+    ///Returns the renderer with the given name ("auto", "blocks", "halfblocks" or "braille"), or None if there is none by that name.
+    pub fn from_name(name: &str) -> Option<Self> {
+	return match name.to_ascii_lowercase().as_str() {
+	    "auto" => Some(Renderer::Auto),
+	    "blocks" | "full" => Some(Renderer::Blocks),
+	    "halfblocks" | "halfblock" | "half" => Some(Renderer::HalfBlocks),
+	    "braille" => Some(Renderer::Braille),
+	    _ => None
+	};
+    }
+
+    ///returns how many pixels of a frame with the given height go across and down each character.
+    fn pixels_per_character(&self, frame_height: usize) -> (usize, usize) {
+	return match (self, frame_height > 32) {
+	    (Renderer::Blocks, false) => (1, 1), //the character is half a pixel wide, see character().
+	    (Renderer::Blocks | Renderer::HalfBlocks, _) => (1, 2),
+	    (Renderer::Braille, _) => (2, 4),
+	    (Renderer::Auto, _) => unreachable!("auto is replaced by a real renderer before drawing")
+	};
+    }
+
+    ///returns how many characters across and down a frame takes up.
+    fn size(&self, frame_width: usize, frame_height: usize) -> (usize, usize) {
+	let (across, down) = self.pixels_per_character(frame_height);
+	let width = if *self == Renderer::Blocks && frame_height <= 32 {frame_width * 2} else {frame_width / across};
+	return (width, frame_height / down);
+    }
+
+    ///returns the character at the given column and row. Without colors, lit pixels are full blocks and dark ones are light shade
+    /// or blank. With colors every pixel gets the color of its planes, in the foreground or background of the character.
+    fn cell(&self, frame: &Frame, column: usize, row: usize, colored: bool) -> Cell {
+	let plain = |character: char| Cell { character, foreground: 0, background: 0 };
+	return match (self, frame.height > 32) {
+	    (Renderer::Blocks, false) if colored => Cell { character: '\u{2588}', foreground: frame.color(column / 2, row), background: 0 },
+	    (Renderer::Blocks, false) => { //each pixel is two terminal chars wide so that the pixels are square.
+		plain(if frame.pixel(column / 2, row) {'\u{2588}'} else {'\u{2591}'}) //see unicode character u+2588 for bright pixels, u+2591 for dark ones.
+	    },
+	    (Renderer::Blocks | Renderer::HalfBlocks, _) if colored => { //the upper half block in the top pixel's color, on the bottom pixel's color.
+		Cell { character: '\u{2580}', foreground: frame.color(column, row * 2), background: frame.color(column, (row * 2) + 1) }
+	    },
+	    (Renderer::Blocks | Renderer::HalfBlocks, _) => { //each terminal char holds two pixels on top of each other using the half block characters.
+		let background = if *self == Renderer::Blocks {'\u{2591}'} else {' '};
+		plain(match (frame.pixel(column, row * 2), frame.pixel(column, (row * 2) + 1)) {
+		    (true, true) => '\u{2588}',
+		    (true, false) => '\u{2580}', //u+2580 is the upper half block.
+		    (false, true) => '\u{2584}', //u+2584 is the lower half block.
+		    (false, false) => background
+		})
+	    },
+	    (Renderer::Braille, _) => { //the braille patterns start at u+2800, and each of the 8 dots is one bit.
+		const DOTS: [[u32; 2]; 4] = [[0x01, 0x08], [0x02, 0x10], [0x04, 0x20], [0x40, 0x80]];
+		let mut pattern = 0;
+		let mut color_counts = [0u8; COLOR_COUNT];
+		for (y, row_dots) in DOTS.iter().enumerate() {
+		    for (x, dot) in row_dots.iter().enumerate() {
+			let color = frame.color((column * 2) + x, (row * 4) + y);
+			if color != 0 {
+			    pattern |= dot;
+			    color_counts[color as usize] += 1;
+			}
+		    }
+		}
+		//the dots can only have one color between them, so they get the one most of them are.
+		let foreground = (1..COLOR_COUNT).max_by_key(|color| (color_counts[*color], COLOR_COUNT - color)).unwrap_or(1) as u8;
+		Cell {
+		    character: char::from_u32(0x2800 + pattern).unwrap_or(' '),
+		    foreground: if colored {foreground} else {0},
+		    background: 0
+		}
+	    },
+	    (Renderer::Auto, _) => unreachable!("auto is replaced by a real renderer before drawing")
+	};
+    }
+}
+
+///One character on the screen, with the palette colors it is drawn in. The colors are ignored when drawing without a palette.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Cell {
+    character: char,
+    foreground: u8,
+    background: u8
+}
+
+///How many colors the terminal can show.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColorDepth {
+    ///any red, green and blue, with the 38;2;r;g;b escape sequences.
+    TrueColor,
+    ///the 256 color palette most terminals have, the colors are rounded to the nearest one in it.
+    Ansi256
+}
+
+impl ColorDepth {
+    ///works out what the terminal can do from the COLORTERM environment variable, which terminals with true color set to "truecolor" or "24bit".
+    pub fn detect() -> Self {
+	return match std::env::var("COLORTERM").as_deref() {
+	    Ok("truecolor" | "24bit") => ColorDepth::TrueColor,
+	    _ => ColorDepth::Ansi256
+	};
+    }
+
+    ///returns the escape sequence that sets the foreground (38) or background (48) color.
+    fn escape(&self, layer: u8, [red, green, blue]: [u8; 3]) -> String {
+	return match self {
+	    ColorDepth::TrueColor => format!("\x1b[{};2;{};{};{}m", layer, red, green, blue),
+	    ColorDepth::Ansi256 => format!("\x1b[{};5;{}m", layer, nearest_ansi256([red, green, blue]))
+	};
+    }
+}
+
+///returns the color in the 256 color palette that is closest to the given color. Colors 16 to 231 are a 6x6x6 cube
+/// and 232 to 255 are grays, the first 16 are left out since every terminal shows them differently.
+fn nearest_ansi256(color: [u8; 3]) -> u8 {
+    const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+    let distance = |other: [u8; 3]| -> u32 {
+	return color.iter().zip(other.iter()).map(|(a, b)| (*a as i32 - *b as i32).pow(2) as u32).sum();
+    };
+    let nearest_level = |channel: u8| (0..6).min_by_key(|level| (CUBE_LEVELS[*level] as i32 - channel as i32).abs()).unwrap_or(0);
+    let (red, green, blue) = (nearest_level(color[0]), nearest_level(color[1]), nearest_level(color[2]));
+    let cube = [CUBE_LEVELS[red], CUBE_LEVELS[green], CUBE_LEVELS[blue]];
+    let average = color.iter().map(|channel| *channel as u32).sum::<u32>() / 3;
+    let gray_step = (average.saturating_sub(3) / 10).min(23) as u8; //the grays go from 8 to 238 in steps of 10.
+    let gray = 8 + (gray_step * 10);
+    return if distance([gray, gray, gray]) < distance(cube) {232 + gray_step} else {16 + (36 * red as u8) + (6 * green as u8) + blue as u8};
+}
+
+///returns the size of the terminal in columns and rows, or None if stdout is not a terminal.
+fn terminal_size() -> Option<(usize, usize)> {
+    let mut termsize = libc::winsize { //the terminal state will be stored in here.
+	ws_row: 0,
+	ws_col: 0,
+	ws_xpixel: 0,
+	ws_ypixel: 0
+    };
+    let result = unsafe {
+	libc::ioctl(1, libc::TIOCGWINSZ, &mut termsize as *const _ as *const libc::c_void) //gets the terminal current state.
+    };
+    return if result == 0 && termsize.ws_col > 0 {Some((termsize.ws_col as usize, termsize.ws_row as usize))} else {None};
+}
+
+///Implements VideoDriver to draw the chip 8 display in the terminal, with one of the Renderers, in black and white or in the colors of a Palette.
+///Drawing a frame only keeps a copy of it. Once a frame (60 times a second) it is turned into characters, and the ones that changed
+/// since the last frame are written to stdout in one go, moving the cursor to them with escape sequences, so the screen does not flicker
+/// and a frame with lots of sprites drawn in it only goes out once. It should be used with a TerminalGuard, which hides the cursor
+/// and draws on the alternate screen.
+///The screen is put in the middle of the terminal, and if the terminal is too small for it, only the top left part that fits is drawn.
+///The terminal is checked every frame, so when it is resized the screen is moved (and with Renderer::Auto, resized) to fit.
 ///The VideoDriver trait has more info on how the display is stored.
 pub struct StdoutDisplay {
-    display_array: [u8; 12322]
+    renderer: Renderer,
+    palette: Option<(Palette, ColorDepth)>,
+    frame: (usize, usize, [[u128; 64]; PLANE_COUNT]), //the width, height and planes of the last frame that was drawn.
+    layout: Option<Layout>, //where the screen is in the terminal, None until the first frame is shown.
+    cells: Vec<Cell>, //the characters that should be on the screen, row by row.
+    shown: Vec<Cell>, //the characters that are on the screen, '\0' for the ones that have not been written yet.
+    changed: bool //if anything was drawn since the last frame was shown.
+}
+
+///Where the screen goes in the terminal, and how it is drawn there.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Layout {
+    terminal: (usize, usize),
+    renderer: Renderer, //never Auto.
+    size: (usize, usize), //how many characters across and down are drawn, cut down to what fits.
+    offset: (usize, usize) //the column and row of the top left character.
+}
+
+impl Layout {
+    ///works out the layout for a frame of the given size, in a terminal of the given size.
+    fn new(renderer: Renderer, frame_width: usize, frame_height: usize, terminal: (usize, usize)) -> Self {
+	let renderer = match renderer {
+	    Renderer::Auto => *[Renderer::Blocks, Renderer::HalfBlocks]
+		.iter()
+		.find(|renderer| {
+		    let (width, height) = renderer.size(frame_width, frame_height);
+		    width <= terminal.0 && height <= terminal.1
+		})
+		.unwrap_or(&Renderer::Braille),
+	    renderer => renderer
+	};
+	let (width, height) = renderer.size(frame_width, frame_height);
+	let size = (width.min(terminal.0), height.min(terminal.1));
+	return Layout {
+	    terminal,
+	    renderer,
+	    size,
+	    offset: ((terminal.0 - size.0) / 2, (terminal.1 - size.1) / 2)
+	};
+    }
 }
 
 impl StdoutDisplay {
-    pub fn new() -> Self {
-	let mut termsize = libc::winsize { //the terminal state will be stored in here.
-	    ws_row: 0,
-	    ws_col: 0,
-	    ws_xpixel: 0,
-	    ws_ypixel: 0
+    ///returns a display that draws with the renderer. With Renderer::Auto it picks whichever fits the terminal.
+    ///With a palette the screen is drawn in its colors, as well as the terminal can show them, otherwise it is drawn in the terminal's own colors.
+    pub fn new(renderer: Renderer, palette: Option<Palette>) -> Self {
+	return StdoutDisplay {
+	    renderer,
+	    palette: palette.map(|palette| (palette, ColorDepth::detect())),
+	    frame: (64, 32, [[0u128; 64]; PLANE_COUNT]),
+	    layout: None,
+	    cells: Vec::new(),
+	    shown: Vec::new(),
+	    changed: true
 	};
-	unsafe {
-	    libc::ioctl(1, libc::TIOCGWINSZ, &mut termsize as *const _ as *const libc::c_void); //gets the terminal current state.
+    }
+}
+
+impl VideoDriver for StdoutDisplay {
+    fn draw(&mut self, frame: &Frame) {
+	self.frame = (frame.width, frame.height, *frame.planes);
+	self.changed = true;
+    }
+
+    fn present(&mut self) {
+	let mut output = String::new();
+	let (frame_width, frame_height, planes) = &self.frame;
+	let terminal = terminal_size().unwrap_or((TERMINAL_COLUMNS, TERMINAL_ROWS));
+	let layout = Layout::new(self.renderer, *frame_width, *frame_height, terminal);
+	if self.layout != Some(layout) { //the terminal was resized or the resolution changed, so start over on a clear screen.
+	    self.layout = Some(layout);
+	    self.cells = vec![Cell { character: ' ', foreground: 0, background: 0 }; layout.size.0 * layout.size.1];
+	    self.shown = vec![Cell { character: '\0', foreground: 0, background: 0 }; layout.size.0 * layout.size.1];
+	    self.changed = true;
+	    output.push_str("\x1b[2J");
 	}
-	if termsize.ws_row < 32 {
-	    panic!("Your terminal is not tall enough: needed 32 lines, only got {}\n try fullscreening terminal.", termsize.ws_row);
+	if !self.changed {
+	    return;
 	}
-	if termsize.ws_col < 128 {
-	    panic!("Your terminal is not wide enough: needed 128 lines, only got {}\n try putting it in fullscreen", termsize.ws_col);
+	self.changed = false;
+
+	let frame = Frame { width: *frame_width, height: *frame_height, planes };
+	for row in 0..layout.size.1 { //for every line in the terminal
+	    for column in 0..layout.size.0 { //for every character in the current line
+		self.cells[(row * layout.size.0) + column] = layout.renderer.cell(&frame, column, row, self.palette.is_some());
+	    }
 	}
-	return StdoutDisplay {
-	    display_array: {
-		let mut array = [0u8; 12322]; //this represents a 128*32 screen (two chars per pixel -> 64 * 2), each char is 3 bytes (because unicode).
-		for i in 0..32 {
-		    for u in 0..128 { //this is initializing the screen with "blank" characters (see u+2591 unicode character).
-			array[2 + (i * 385) + (u * 3)] = 0xE2;
-			array[2 + (i * 385) + (u * 3) + 1] = 0x96;
-			array[2 + (i * 385) + (u * 3) + 2] = 0x91;
-		    }
-		    array[2 + (i * 385) + 384] = b'\n'; //there should be a newline character after every 64 pixels (1 pixel = 2 characters = 6 bytes).
+	let mut cursor = None; //where the terminal's cursor is, if we know.
+	let mut colors = None; //the foreground and background colors the terminal is drawing in.
+	for (position, (cell, shown)) in self.cells.iter().zip(self.shown.iter_mut()).enumerate() {
+	    if cell == shown {
+		continue;
+	    }
+	    let (column, row) = (position % layout.size.0, position / layout.size.0);
+	    if cursor != Some(position) { //escape sequences count rows and columns from 1.
+		output.push_str(&format!("\x1b[{};{}H", layout.offset.1 + row + 1, layout.offset.0 + column + 1));
+	    }
+	    if let Some((palette, depth)) = &self.palette {
+		if colors != Some((cell.foreground, cell.background)) {
+		    output.push_str(&depth.escape(38, palette.color(cell.foreground)));
+		    output.push_str(&depth.escape(48, palette.color(cell.background)));
+		    colors = Some((cell.foreground, cell.background));
 		}
-		array[0] = 0x1B; //to make it easy, the first two bytes of the screen is an escape/redraw command, causing screen to clear every frame.
-		array[1] = b'c';
-		array
 	    }
+	    output.push(cell.character);
+	    *shown = *cell;
+	    //at the end of a line the cursor goes on to the next line of the terminal, not of the screen, so that needs an escape sequence.
+	    cursor = if column + 1 == layout.size.0 {None} else {Some(position + 1)};
+	}
+	if colors.is_some() {
+	    output.push_str("\x1b[0m"); //back to the terminal's own colors, for anything else that is printed.
+	}
+	if !output.is_empty() {
+	    let mut stdout = std::io::stdout().lock();
+	    let _ = std::io::Write::write_all(&mut stdout, output.as_bytes()); //a closed terminal is nothing to stop the chip 8 over.
+	    let _ = std::io::Write::flush(&mut stdout);
 	}
     }
 }
 
-impl VideoDriver for StdoutDisplay {
-    fn draw(&mut self, bitmap: &[u64; 32]) {
-	let mut changed = false;
-	bitmap.iter().enumerate().for_each(|(i, val)| { //for every row in the display
-	    for offset in 0..64 { //for every pixel in the current row
-		if *val & (0x1u64 << (63 - offset)) != 0 {  //a pixel in a 64 pixel line is a bit in a 64 byte number. if a bit is 1, then pixel should be on.
-		    if self.display_array[2 + (i * 385) + (offset * 3 * 2) + 2] == 0x91 { //if the pixel is off, change it to on, otherwise no action needed.
-			self.display_array[2 + (i * 385) + (offset * 3 * 2) + 2] = 0x88; //see unicode character u+2588 for bright pixels.
-			self.display_array[2 + (i * 385) + (offset * 3 * 2) + 3 + 2] = 0x88; //there are two terminal chars per pixel, we want square pixels.
-			changed = true; //sometimes the redraw function is called but nothing is actually changed, this tells if it is nessecary to actually redraw.
-		    }
-		} else {
-		    if self.display_array[2 + (i * 385) + (offset * 3 * 2) + 2] == 0x88 { //if pixel is on, we need to change it to off, otherwise no action needed.
-			self.display_array[2 + (i * 385) + (offset * 3 * 2) + 2] = 0x91;
-			self.display_array[2 + (i * 385) + (offset * 3 * 2) + 3 + 2] = 0x91;
-			changed = true;
-		    }
-		}
-	    }
-	});
-	if changed { //if there was actually a change to the buffer and we need to update the screen, call the write() syscall to update display.
-	    unsafe {
-		libc::write(0, &self.display_array as *const _ as *const libc::c_void, 12322);
-	    }
-	}	
-    }
-}
+///the terminal size to use when stdout is not a terminal, the size the Blocks renderer needs.
+const TERMINAL_COLUMNS: usize = 128;
+const TERMINAL_ROWS: usize = 32;
 
 ///Implements SoundDriver to make beeps for the chip 8. This just uses the standard terminal alarm ("\x07").
-///This driver is extremely rudimentary and a hacky way of beeping.
+///This driver is extremely rudimentary and a hacky way of beeping, it never pulls any samples and only checks if the sound is on.
 pub struct TerminalBeep;
 
 impl TerminalBeep {
@@ -89,29 +308,206 @@ impl TerminalBeep {
 }
 
 impl SoundDriver for TerminalBeep {
-    fn set_beep(&self, state: bool) {
-	if state {
+    fn update(&mut self, stream: &mut SampleStream) {
+	if stream.is_sounding() {
 	    print!("\x07");
 	}
     }
 }
 
+///Something the user asked the terminal frontend to do with a key that is not on the keypad, StdinKeysender sends these.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TerminalCommand {
+    ///save a state into the numbered slot (1 to 5).
+    SaveState(u8),
+    ///load the state in the numbered slot (1 to 5).
+    LoadState(u8),
+    ///go back in time a little.
+    Rewind,
+    ///save a picture of the screen.
+    Screenshot,
+    ///stop running and put the terminal back the way it was.
+    Quit
+}
+
+///the keys that save into slots 1 to 5 and the keys that load them, they are the number keys 5 to 9 and the same keys with shift held.
+const SAVE_SLOT_KEYS: [u8; 5] = [b'5', b'6', b'7', b'8', b'9'];
+const LOAD_SLOT_KEYS: [u8; 5] = [b'%', b'^', b'&', b'*', b'('];
+const REWIND_KEY: u8 = b'-';
+const SCREENSHOT_KEY: u8 = b'=';
+const QUIT_KEY: u8 = 0x1B; //Esc
+///after an Esc, how long to wait for the rest of an escape sequence. Keys like the arrow keys send Esc followed by a few more bytes all at once,
+/// so if nothing else comes in this time, Esc itself was pressed.
+const ESCAPE_SEQUENCE_WAIT: libc::c_int = 25; //milliseconds
+///a terminal only sends a key when it is pressed, and then again and again while it is held down (key repeat), it never says when
+/// a key is let go of. So a key counts as held down until nothing has come from it for this long, once the terminal has started repeating it.
+const KEY_HOLD_TIME: std::time::Duration = std::time::Duration::from_millis(150);
+///terminals wait a lot longer before the first repeat than between the repeats after it, about half a second usually.
+/// So until a key has come a second time, it is held for this long instead, otherwise it would be let go of and pressed again in that gap.
+const FIRST_KEY_HOLD_TIME: std::time::Duration = std::time::Duration::from_millis(500);
+
 ///implements the KeyboardDriver to send key presses to the chip8. This is a very rudimentary driver also.
-///you press one of keys (1234,qwer,asdf,zxvc make up the 4x4 keypad).
+///you press one of keys of the keymap (on QWERTY 1234,qwer,asdf,zxvc make up the 4x4 keypad). Several keys can be held down at once, but most terminals only
+/// repeat the last key pressed, so the others are let go of after KEY_HOLD_TIME.
+///Since the terminal never says when a key is let go of, this is all guessed from the key repeat: a tap holds the key down for
+/// FIRST_KEY_HOLD_TIME, and a key tapped again within that time looks like the terminal repeating it, so the two taps become one press.
+/// A terminal set to wait longer than FIRST_KEY_HOLD_TIME before repeating lets go of a long press once before its repeats start.
+///The number keys 5 to 9 save a state into slots 1 to 5, and with shift held (%^&*( on a US keyboard) they load it back,
+/// the - key rewinds, = takes a screenshot and Esc quits. These come out of the receiver from take_commands() instead of going to the chip 8, unless the keymap uses them.
 ///Like the SoundDriver implementation above, this is a hacky way of getting keyboard input I think, but I didnt want to use
 /// some well-made library and bring in all these dependencies, when I could try to make this myself and learn a bit about stdin and stdout.
-///This driver uses libc functions, namely the tcsetattr() to disable the terminal canonical mode. This causes the stdin to be basically unbuffered,
+///This driver needs the terminal canonical mode to be disabled, which a TerminalGuard does. This causes the stdin to be basically unbuffered,
 /// allowing the program to instantly read a keypress the moment you press it. Without it, you would have to press enter after every keystroke to
 /// put a newline in the stdin for the program to recieve the input.
 pub struct StdinKeysender {
     _cleanerthread: std::thread::JoinHandle<()>,
-    current_pressed_reader: std::sync::Arc<std::sync::Mutex<Option<u8>>>
+    key_presses: std::sync::mpsc::Receiver<u8>,
+    last_seen: [Option<(std::time::Instant, bool)>; 16], //when each key last came from the terminal and if it has been repeated, None if it is not held down.
+    reported: u16, //the keys the chip 8 has been told are held down.
+    commands: Option<std::sync::mpsc::Receiver<TerminalCommand>>
 }
 
 impl StdinKeysender {
     ///this version of the driver requires that you call the new() function, you cant generate an instance yourself.
-    ///a new thread is spawned whose only purpose is to read the most recent key on the keypad that is pressed, and
-    /// then set that as the current key (current_pressed_reader).
+    ///a new thread is spawned whose only purpose is to read the keys from the terminal, and send the ones on the keypad
+    /// over to the driver (key_presses), after looking them up in the keymap.
+    pub fn new(keymap: Keymap) -> Self {
+	let (key_sender, key_presses) = std::sync::mpsc::channel(); //the new thread below uses this to tell the driver about every key that comes in.
+	let (command_sender, commands) = std::sync::mpsc::channel();
+	return StdinKeysender {
+	    _cleanerthread: std::thread::spawn(move || {
+		loop {
+		    let Some(character) = read_character() else {
+			std::thread::sleep(std::time::Duration::from_millis(10)); //stdin was closed or interrupted, dont spin on it.
+			continue;
+		    };
+		    if let Some(key) = keymap.key_for(character) { //match that key to the keypad.
+			if key_sender.send(key).is_err() {
+			    return; //the driver is gone.
+			}
+			continue;
+		    }
+		    let byte = if character.is_ascii() {character as u8} else {0};
+		    if let Some(slot) = SAVE_SLOT_KEYS.iter().position(|key| *key == byte) {
+			let _ = command_sender.send(TerminalCommand::SaveState(slot as u8 + 1)); //nobody might be listening, which is fine.
+		    }
+		    if let Some(slot) = LOAD_SLOT_KEYS.iter().position(|key| *key == byte) {
+			let _ = command_sender.send(TerminalCommand::LoadState(slot as u8 + 1));
+		    }
+		    if byte == REWIND_KEY {
+			let _ = command_sender.send(TerminalCommand::Rewind);
+		    }
+		    if byte == SCREENSHOT_KEY {
+			let _ = command_sender.send(TerminalCommand::Screenshot);
+		    }
+		    if byte == QUIT_KEY && !skip_escape_sequence() {
+			let _ = command_sender.send(TerminalCommand::Quit);
+		    }
+		}
+	    }),
+	    key_presses,
+	    last_seen: [None; 16],
+	    reported: 0,
+	    commands: Some(commands)
+	}
+    }
+
+    ///hands over the receiver of the save and load commands, this can only be done once so it returns None after the first time.
+    pub fn take_commands(&mut self) -> Option<std::sync::mpsc::Receiver<TerminalCommand>> {
+	return self.commands.take();
+    }
+}
+
+///reads one character from stdin, waiting until there is one. Characters outside of ASCII (like é) take more than one byte in UTF-8,
+/// so the rest of their bytes are read too. Returns None if nothing could be read.
+fn read_character() -> Option<char> {
+    let mut readbuffer = [0u8; 4];
+    readbuffer[0] = read_byte()?; //read a key from the keyboard.
+    let length = match readbuffer[0].leading_ones() {
+	0 => 1,
+	length @ 2..=4 => length as usize,
+	_ => return None //a byte from the middle of a character, which cant start one.
+    };
+    for byte in readbuffer.iter_mut().take(length).skip(1) {
+	*byte = read_byte()?;
+    }
+    return std::str::from_utf8(&readbuffer[..length]).ok().and_then(|text| text.chars().next());
+}
+
+///reads one byte from stdin, waiting until there is one. Returns None if nothing could be read.
+fn read_byte() -> Option<u8> {
+    let mut readbuffer = [0u8; 1];
+    let read = unsafe {
+	libc::read(0, &mut readbuffer as *mut _ as *mut libc::c_void, 1)
+    };
+    return if read > 0 {Some(readbuffer[0])} else {None};
+}
+
+///after an Esc, checks if more bytes come in straight away, which means a key like an arrow key sent an escape sequence instead of Esc being pressed.
+///The rest of the sequence is read and thrown away. Returns true if it was a sequence.
+fn skip_escape_sequence() -> bool {
+    let mut waiting = libc::pollfd { fd: 0, events: libc::POLLIN, revents: 0 };
+    let ready = unsafe {
+	libc::poll(&mut waiting as *mut libc::pollfd, 1, ESCAPE_SEQUENCE_WAIT)
+    };
+    if ready <= 0 {
+	return false;
+    }
+    match read_byte() {
+	Some(b'[') => { //a control sequence, like ESC [ A for the up arrow, it ends with a byte from @ to ~.
+	    while let Some(byte) = read_byte() {
+		if (0x40..=0x7E).contains(&byte) {
+		    break;
+		}
+	    }
+	},
+	Some(b'O') => { //ESC O A is the up arrow in some terminals, and ESC O P is F1.
+	    read_byte();
+	},
+	_ => {} //alt held with a key, it is thrown away too.
+    }
+    return true;
+}
+
+impl KeyboardDriver for StdinKeysender {
+    fn next_event(&mut self) -> Option<KeyEvent> {
+	let now = std::time::Instant::now();
+	while let Ok(key) = self.key_presses.try_recv() {
+	    let repeated = self.last_seen[key as usize].is_some();
+	    self.last_seen[key as usize] = Some((now, repeated));
+	}
+	let mut held = 0u16;
+	for (key, seen) in self.last_seen.iter_mut().enumerate() {
+	    match seen {
+		Some((time, repeated)) if now.duration_since(*time) < if *repeated {KEY_HOLD_TIME} else {FIRST_KEY_HOLD_TIME} => held |= 1 << key,
+		_ => *seen = None
+	    }
+	}
+	return keyboard::next_mask_event(&mut self.reported, held);
+    }
+}
+
+///the terminal settings from before the TerminalGuard changed them, so that they can be put back from a signal handler or a panic.
+static ORIGINAL_TERMINAL: std::sync::OnceLock<libc::termios> = std::sync::OnceLock::new();
+///set while a TerminalGuard has the terminal, so that it is only put back once.
+static TERMINAL_TAKEN: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
+///set when SIGINT (ctrl-c) or SIGTERM comes in, the run loop should check it every frame and shut down.
+static QUIT_REQUESTED: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
+///switches to the alternate screen (so whatever was in the terminal is still there afterwards), hides the cursor and clears the screen.
+const ENTER_SCREEN: &[u8] = b"\x1b[?1049h\x1b[?25l\x1b[2J";
+///shows the cursor again and goes back to the normal screen.
+const LEAVE_SCREEN: &[u8] = b"\x1b[?25h\x1b[?1049l";
+
+///Sets up the terminal for running the chip 8, and puts it back the way it was when the guard is dropped.
+///While the guard is alive, the terminal is in non canonical mode without echo (so StdinKeysender gets every key the moment it is pressed),
+/// the chip 8 is drawn on the alternate screen, and the cursor is hidden. The terminal is also put back if the program panics,
+/// and SIGINT and SIGTERM ask the run loop to stop (see quit_requested()) instead of killing the emulator with the terminal still changed.
+///If the loop does not stop and a second signal comes in, the terminal is put back and the emulator exits right away.
+pub struct TerminalGuard {
+    _private: ()
+}
+
+impl TerminalGuard {
     pub fn new() -> Self {
 	let mut termsettings = libc::termios { //the numbers here are just placeholders, none of them will actually be used.
 	    c_iflag: 0,
@@ -125,49 +521,139 @@ impl StdinKeysender {
 	};
 	unsafe {
 	    libc::tcgetattr(0, &mut termsettings as *mut libc::termios); //we get the current stdin configuration and store it in termsettings.
-	    termsettings.c_lflag &= u32::MAX ^ libc::ICANON; //we keep all settings same except ICANON, we dont want canonical mode, we want instant input.
-	    termsettings.c_lflag &= u32::MAX ^ libc::ECHO; //we also dont need to echo all of our keystrokes back into the terminal.
-	    libc::tcsetattr(0, libc::TCSANOW, &mut termsettings as *mut libc::termios); //we then apply the modified settings back on stdin (fd 0).
 	}
-	let current_pressed_reader = std::sync::Arc::new(std::sync::Mutex::new(None)); //the current key that is being pressed is in here
-	let current_pressed = current_pressed_reader.clone(); //the new thread below uses this to communicate with the main thread about most recent keys.
-	return StdinKeysender {
-	    _cleanerthread: std::thread::spawn(move || {
-		loop {
-		    std::thread::sleep(std::time::Duration::from_millis(10));
-		    let mut new_key = current_pressed.lock().expect("unable to block this thread");
-		    let mut readbuffer = [0u8; 1]; 
-		    unsafe {		    
-			libc::read(0, &mut readbuffer as *mut _ as *mut libc::c_void, 1); //read a key from the keyboard.
+	let _ = ORIGINAL_TERMINAL.set(termsettings); //only the settings from before the first guard are the original ones.
+	termsettings.c_lflag &= u32::MAX ^ libc::ICANON; //we keep all settings same except ICANON, we dont want canonical mode, we want instant input.
+	termsettings.c_lflag &= u32::MAX ^ libc::ECHO; //we also dont need to echo all of our keystrokes back into the terminal.
+	unsafe {
+	    libc::tcsetattr(0, libc::TCSANOW, &termsettings as *const libc::termios); //we then apply the modified settings back on stdin (fd 0).
+	    libc::write(1, ENTER_SCREEN.as_ptr() as *const libc::c_void, ENTER_SCREEN.len());
+	}
+	TERMINAL_TAKEN.store(true, std::sync::atomic::Ordering::SeqCst);
+
+	let previous_hook = std::panic::take_hook();
+	std::panic::set_hook(Box::new(move |info| {
+	    restore_terminal(); //first, so the panic message ends up on the normal screen where it can be read.
+	    previous_hook(info);
+	}));
+	extern "C" fn handle_quit_signal(_signal: libc::c_int) {
+	    if QUIT_REQUESTED.swap(true, std::sync::atomic::Ordering::SeqCst) {
+		restore_terminal();
+		unsafe {
+		    libc::_exit(130);
+		}
+	    }
+	}
+	unsafe {
+	    libc::signal(libc::SIGINT, handle_quit_signal as *const () as libc::sighandler_t);
+	    libc::signal(libc::SIGTERM, handle_quit_signal as *const () as libc::sighandler_t);
+	}
+	return TerminalGuard { _private: () };
+    }
+
+    ///tells you if SIGINT or SIGTERM asked the emulator to stop.
+    pub fn quit_requested(&self) -> bool {
+	return QUIT_REQUESTED.load(std::sync::atomic::Ordering::SeqCst);
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+	let _ = std::io::Write::flush(&mut std::io::stdout()); //anything still buffered belongs on the alternate screen.
+	restore_terminal();
+    }
+}
+
+///puts the terminal settings and screen back, if a TerminalGuard changed them. This only uses tcsetattr() and write(),
+/// which are safe to call from a signal handler.
+fn restore_terminal() {
+    if TERMINAL_TAKEN.swap(false, std::sync::atomic::Ordering::SeqCst) {
+	unsafe {
+	    if let Some(original) = ORIGINAL_TERMINAL.get() {
+		libc::tcsetattr(0, libc::TCSANOW, original as *const libc::termios);
+	    }
+	    libc::write(1, LEAVE_SCREEN.as_ptr() as *const libc::c_void, LEAVE_SCREEN.len());
+	}
+    }
+}
+
+///Implements VideoDriver without drawing anything, for running programs headless. The screen can still be looked at with ChipSystem::screen().
+pub struct NullDisplay;
+
+impl NullDisplay {
+    pub fn new() -> Self {
+	return NullDisplay;
+    }
+}
+
+impl VideoDriver for NullDisplay {
+    fn draw(&mut self, _frame: &Frame) {}
+}
+
+///Implements SoundDriver without making any sound, for running programs headless.
+pub struct NullSound;
+
+impl NullSound {
+    pub fn new() -> Self {
+	return NullSound;
+    }
+}
+
+impl SoundDriver for NullSound {
+    fn update(&mut self, _stream: &mut SampleStream) {}
+}
+
+///Implements KeyboardDriver by following a script of which keys are held down on which frame, so that a headless run always gets the same input.
+///The driver cant see the frames go by itself, whoever runs the chip 8 has to set the frame number in the frame_counter() after every frame.
+pub struct ScriptedKeyboard {
+    script: Vec<(u64, u16)>, //from each frame on, the mask of the keys that are held down, sorted by frame.
+    frame: std::rc::Rc<std::cell::Cell<u64>>,
+    reported: u16 //the keys the chip 8 has been told are held down.
+}
+
+impl ScriptedKeyboard {
+    ///returns a ScriptedKeyboard that follows the script. Each entry is a frame number and the mask of the keys that are held down
+    /// from that frame on (bit N for key N), 0 lets go of them all. No key is held down before the first entry.
+    pub fn new(mut script: Vec<(u64, u16)>) -> Self {
+	script.sort_by_key(|(frame, _)| *frame);
+	return ScriptedKeyboard {
+	    script,
+	    frame: std::rc::Rc::new(std::cell::Cell::new(0)),
+	    reported: 0
+	};
+    }
+
+    ///reads a script written like "30:5,45:-,100:a+b", which holds down key 5 on frame 30, lets go of it on frame 45,
+    /// then holds down keys A and B together from frame 100 on. Any problem with the script is returned as an Err() with a message for the user.
+    pub fn from_script(script: &str) -> Result<Self, String> {
+	let mut entries = Vec::new();
+	for entry in script.split(',').map(|entry| entry.trim()).filter(|entry| !entry.is_empty()) {
+	    let (frame, key) = entry.split_once(':').ok_or(format!("\"{}\" should be a frame and a key, like 30:5", entry))?;
+	    let frame = frame.trim().parse::<u64>().map_err(|_| format!("\"{}\" is not a frame number", frame))?;
+	    let mut keys = 0u16;
+	    if key.trim() != "-" {
+		for key in key.split('+').map(|key| key.trim()) {
+		    match u8::from_str_radix(key, 16) {
+			Ok(key) if key < 16 => keys |= 1 << key,
+			_ => return Err(format!("\"{}\" is not a key, it should be a hex digit from 0 to f, or - to let go", key))
 		    }
-		    (*new_key) = match readbuffer[0] { //match that key, set it as the current key being pressed.
-			b'1' => Some(0x1),
-			b'2' => Some(0x2),
-			b'3' => Some(0x3),
-			b'4' => Some(0xC),
-			b'q' => Some(0x4),
-			b'w' => Some(0x5),
-			b'e' => Some(0x6),
-			b'r' => Some(0xD),
-			b'a' => Some(0x7),
-			b's' => Some(0x8),
-			b'd' => Some(0x9),
-			b'f' => Some(0xE),
-			b'z' => Some(0xA),
-			b'x' => Some(0x0),
-			b'c' => Some(0xB),
-			b'v' => Some(0xF),
-			_ => None //if no key was found, then new_key should be None.
-		    };
 		}
-	    }),
-	    current_pressed_reader
+	    }
+	    entries.push((frame, keys));
 	}
+	return Ok(ScriptedKeyboard::new(entries));
+    }
+
+    ///returns the frame number the driver follows the script by, it is shared so it can still be set after the driver is handed to the chip 8.
+    pub fn frame_counter(&self) -> std::rc::Rc<std::cell::Cell<u64>> {
+	return self.frame.clone();
     }
 }
 
-impl KeyboardDriver for StdinKeysender {
-    fn get_key_pressed(&mut self) -> Option<u8> {
-	return self.current_pressed_reader.lock().expect("unable to block the cleaner thread").take();
+impl KeyboardDriver for ScriptedKeyboard {
+    fn next_event(&mut self) -> Option<KeyEvent> {
+	let frame = self.frame.get();
+	let held = self.script.iter().take_while(|(start, _)| *start <= frame).last().map_or(0, |(_, keys)| *keys);
+	return keyboard::next_mask_event(&mut self.reported, held);
     }
 }
@@ -0,0 +1,83 @@
+//! This module has the errors that the chip 8 can run into.
+//! A Fault is what went wrong while an instruction was running, and a Chip8Error is what the TimedRunner hands back to you,
+//!  which also tells you where in the program the fault happened.
+
+use std::fmt;
+use crate::chip_8::memory::Access;
+
+///Something that went wrong while executing a single instruction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fault {
+    ///a subroutine was called (2NNN) while the stack was already full.
+    StackOverflow,
+    ///a subroutine returned (00EE) while the stack was empty.
+    StackUnderflow,
+    ///the instruction is not one the chip 8 (or the enabled extensions) knows about.
+    UnknownOpcode,
+    ///the instruction tried to read or write memory past the end of the ram.
+    MemoryOutOfBounds { address: usize }
+}
+
+///An error from the chip 8, this is what the TimedRunner returns instead of panicking.
+#[derive(Debug)]
+pub enum Chip8Error {
+    ///an instruction could not be executed. pc is the address of the instruction and opcode is the instruction itself.
+    Execution { pc: u16, opcode: u16, fault: Fault },
+    ///a watchpoint fired. The instruction at pc (the opcode) did the access to the address, and it ran to the end before the chip 8 stopped.
+    WatchpointHit { pc: u16, opcode: u16, address: usize, access: Access },
+    ///the program does not fit in the memory after address 0x200.
+    ProgramTooLarge { size: usize, max_size: usize },
+    ///a save state could not be loaded, the reason says what is wrong with it.
+    InvalidSaveState { reason: String },
+    ///the program file could not be read.
+    Io(std::io::Error)
+}
+
+impl Chip8Error {
+    ///returns the address of the instruction that caused the error, if the error came from an instruction.
+    pub fn pc(&self) -> Option<u16> {
+	return match self {
+	    Chip8Error::Execution { pc, .. } | Chip8Error::WatchpointHit { pc, .. } => Some(*pc),
+	    _ => None
+	};
+    }
+
+    ///returns the instruction that caused the error, if the error came from an instruction.
+    pub fn opcode(&self) -> Option<u16> {
+	return match self {
+	    Chip8Error::Execution { opcode, .. } | Chip8Error::WatchpointHit { opcode, .. } => Some(*opcode),
+	    _ => None
+	};
+    }
+}
+
+impl fmt::Display for Fault {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+	return match self {
+	    Fault::StackOverflow => write!(f, "stack overflow"),
+	    Fault::StackUnderflow => write!(f, "stack underflow, returned with an empty stack"),
+	    Fault::UnknownOpcode => write!(f, "unknown opcode"),
+	    Fault::MemoryOutOfBounds { address } => write!(f, "memory access out of bounds at address {:#06x}", address)
+	};
+    }
+}
+
+impl fmt::Display for Chip8Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+	return match self {
+	    Chip8Error::Execution { pc, opcode, fault } => write!(f, "{} (opcode {:#06x} at address {:#06x})", fault, opcode, pc),
+	    Chip8Error::WatchpointHit { pc, opcode, address, access } => write!(f, "watchpoint hit, {} of address {:#06x} (opcode {:#06x} at address {:#06x})", access, address, opcode, pc),
+	    Chip8Error::ProgramTooLarge { size, max_size } => write!(f, "program is {} bytes, but only {} bytes fit in memory", size, max_size),
+	    Chip8Error::InvalidSaveState { reason } => write!(f, "invalid save state: {}", reason),
+	    Chip8Error::Io(error) => write!(f, "error with reading program file: {}", error)
+	};
+    }
+}
+
+impl std::error::Error for Chip8Error {}
+
+impl From<std::io::Error> for Chip8Error {
+    fn from(error: std::io::Error) -> Self {
+	return Chip8Error::Io(error);
+    }
+}
@@ -0,0 +1,235 @@
+//! This module turns the screen into an image, for screenshots. It can write two formats, without needing any other crates:
+//!  PPM (binary P6), which is about the simplest image format there is, and is read by most image programs.
+//!  PNG, which everything can show. The pixels are put in zlib data without compressing them (deflate's "stored" blocks),
+//!   which makes the file bigger, but a chip 8 screen is small enough that it doesnt matter.
+//!
+//! Each chip 8 pixel becomes a square of scale by scale pixels in the image, and its color comes from a palette.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+use crate::chip_8::checksum;
+use crate::chip_8::palette::Palette;
+use crate::chip_8::video::Frame;
+
+///the 8 bytes every PNG file starts with.
+const PNG_SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1A, b'\n'];
+///the most bytes one stored deflate block can hold, its length is a u16.
+const MAX_STORED_BLOCK: usize = 0xFFFF;
+
+///The formats an image can be written in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ImageFormat {
+    Png,
+    Ppm
+}
+
+impl ImageFormat {
+    ///picks the format from a file's extension, .ppm is a PPM and anything else is a PNG.
+    pub fn from_path(path: &Path) -> Self {
+	return match path.extension().and_then(|extension| extension.to_str()) {
+	    Some(extension) if extension.eq_ignore_ascii_case("ppm") => ImageFormat::Ppm,
+	    _ => ImageFormat::Png
+	};
+    }
+}
+
+///An RGB image, the rows go from the top down and the pixels in a row go from the left.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<[u8; 3]>
+}
+
+impl Image {
+    ///draws the frame in the palette's colors, with every chip 8 pixel made scale pixels wide and tall. A scale of 0 is taken as 1.
+    pub fn from_frame(frame: &Frame, palette: &Palette, scale: usize) -> Self {
+	let scale = scale.max(1);
+	let (width, height) = (frame.width * scale, frame.height * scale);
+	let mut pixels = Vec::with_capacity(width * height);
+	for y in 0..height {
+	    pixels.extend((0..width).map(|x| palette.color(frame.color(x / scale, y / scale))));
+	}
+	return Image { width, height, pixels };
+    }
+
+    ///returns the image as a binary PPM file.
+    pub fn to_ppm(&self) -> Vec<u8> {
+	let mut file = format!("P6\n{} {}\n255\n", self.width, self.height).into_bytes();
+	file.extend(self.pixels.iter().flatten());
+	return file;
+    }
+
+    ///returns the image as an 8 bit RGB PNG file.
+    pub fn to_png(&self) -> Vec<u8> {
+	let mut header = Vec::with_capacity(13);
+	header.extend((self.width as u32).to_be_bytes());
+	header.extend((self.height as u32).to_be_bytes());
+	header.extend([8, 2, 0, 0, 0]); //8 bits a channel, RGB, deflate, the usual filtering, not interlaced.
+
+	let mut scanlines = Vec::with_capacity(self.height * (1 + (self.width * 3)));
+	for row in self.pixels.chunks(self.width.max(1)) {
+	    scanlines.push(0); //every row starts with its filter, 0 is none.
+	    scanlines.extend(row.iter().flatten());
+	}
+
+	let mut file = PNG_SIGNATURE.to_vec();
+	write_chunk(&mut file, b"IHDR", &header);
+	write_chunk(&mut file, b"IDAT", &zlib_stored(&scanlines));
+	write_chunk(&mut file, b"IEND", &[]);
+	return file;
+    }
+
+    ///writes the image to a file, in the format that goes with the file's extension (see ImageFormat::from_path()).
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+	return fs::write(path, match ImageFormat::from_path(path) {
+	    ImageFormat::Png => self.to_png(),
+	    ImageFormat::Ppm => self.to_ppm()
+	});
+    }
+}
+
+///adds a PNG chunk to the file: the length of its data, its type, the data and then the CRC-32 of the type and the data.
+fn write_chunk(file: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    file.extend((data.len() as u32).to_be_bytes());
+    let start = file.len();
+    file.extend(kind);
+    file.extend(data);
+    let crc = checksum::crc32(&file[start..]);
+    file.extend(crc.to_be_bytes());
+}
+
+///wraps the data in a zlib stream made of stored deflate blocks, so it is not compressed at all.
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    let mut stream = vec![0x78, 0x01]; //deflate with a 32K window, and no preset dictionary. 0x7801 is a multiple of 31, as zlib wants.
+    let mut blocks = data.chunks(MAX_STORED_BLOCK).peekable();
+    if blocks.peek().is_none() {
+	stream.extend([0x01, 0x00, 0x00, 0xFF, 0xFF]); //an empty final block.
+    }
+    while let Some(block) = blocks.next() {
+	stream.push(if blocks.peek().is_none() {0x01} else {0x00}); //bit 0 says if it is the final block, and the type 00 is stored.
+	stream.extend((block.len() as u16).to_le_bytes());
+	stream.extend((!(block.len() as u16)).to_le_bytes());
+	stream.extend(block);
+    }
+    stream.extend(checksum::adler32(data).to_be_bytes());
+    return stream;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    ///the CRC-32 from the PNG spec, a bit at a time, to check the chunks against.
+    fn slow_crc32(bytes: &[u8]) -> u32 {
+	let mut crc = !0u32;
+	for byte in bytes {
+	    crc ^= *byte as u32;
+	    for _ in 0..8 {
+		crc = if crc & 1 != 0 {(crc >> 1) ^ 0xEDB8_8320} else {crc >> 1};
+	    }
+	}
+	return !crc;
+    }
+
+    ///an image where every pixel has its own color, so pixels in the wrong place show up.
+    fn gradient(width: usize, height: usize) -> Image {
+	let pixels = (0..(width * height)).map(|pixel| [(pixel % 251) as u8, (pixel / 251) as u8, (pixel % 7) as u8]).collect();
+	return Image { width, height, pixels };
+    }
+
+    ///splits a PNG file into its chunks, checking every chunk's CRC on the way.
+    fn chunks(file: &[u8]) -> Vec<([u8; 4], Vec<u8>)> {
+	assert_eq!(&file[..8], &PNG_SIGNATURE);
+	let mut chunks = Vec::new();
+	let mut position = 8;
+	while position < file.len() {
+	    let length = u32::from_be_bytes(file[position..(position + 4)].try_into().unwrap()) as usize;
+	    let kind_and_data = &file[(position + 4)..(position + 8 + length)];
+	    let crc = u32::from_be_bytes(file[(position + 8 + length)..(position + 12 + length)].try_into().unwrap());
+	    assert_eq!(crc, slow_crc32(kind_and_data), "the CRC of the {} chunk is wrong", String::from_utf8_lossy(&kind_and_data[..4]));
+	    chunks.push((kind_and_data[..4].try_into().unwrap(), kind_and_data[4..].to_vec()));
+	    position += 12 + length;
+	}
+	return chunks;
+    }
+
+    ///reads the stored deflate blocks out of a zlib stream, checking the block headers and the adler32 at the end.
+    ///returns the data and how many blocks it was in.
+    fn unzlib_stored(stream: &[u8]) -> (Vec<u8>, usize) {
+	assert_eq!(&stream[..2], &[0x78, 0x01]);
+	assert_eq!(((stream[0] as u16) << 8 | stream[1] as u16) % 31, 0);
+	let mut data = Vec::new();
+	let mut blocks = 0;
+	let mut position = 2;
+	loop {
+	    let header = stream[position];
+	    let length = u16::from_le_bytes([stream[position + 1], stream[position + 2]]);
+	    let complement = u16::from_le_bytes([stream[position + 3], stream[position + 4]]);
+	    assert_eq!(header & 0b110, 0, "every block should be a stored one");
+	    assert_eq!(length, !complement);
+	    data.extend_from_slice(&stream[(position + 5)..(position + 5 + length as usize)]);
+	    position += 5 + length as usize;
+	    blocks += 1;
+	    if header & 1 != 0 {
+		break;
+	    }
+	}
+	let (a, b) = data.iter().fold((1u32, 0u32), |(a, b), byte| ((a + *byte as u32) % 65521, (b + ((a + *byte as u32) % 65521)) % 65521));
+	assert_eq!(&stream[position..], &((b << 16) | a).to_be_bytes(), "the adler32 is wrong, or there is something after it");
+	return (data, blocks);
+    }
+
+    ///the rows of the image as a PNG has them, each starting with filter 0.
+    fn scanlines(image: &Image) -> Vec<u8> {
+	return image.pixels.chunks(image.width).flat_map(|row| [0].into_iter().chain(row.iter().flatten().copied())).collect();
+    }
+
+    #[test]
+    fn a_ppm_is_a_header_then_the_pixels() {
+	let image = Image { width: 2, height: 1, pixels: vec![[1, 2, 3], [250, 251, 252]] };
+	assert_eq!(image.to_ppm(), b"P6\n2 1\n255\n\x01\x02\x03\xFA\xFB\xFC".to_vec());
+    }
+
+    #[test]
+    fn a_frame_is_scaled_and_colored() {
+	let mut planes = [[0u128; 64]; crate::chip_8::video::PLANE_COUNT];
+	planes[0][1] = 1 << 126; //the pixel at 1, 1
+	let frame = Frame { width: 64, height: 32, planes: &planes };
+	let image = Image::from_frame(&frame, &Palette::monochrome(), 3);
+	assert_eq!((image.width, image.height), (192, 96));
+	let lit = image.pixels.iter().enumerate().filter(|(_, pixel)| **pixel == [0xFF, 0xFF, 0xFF]).map(|(index, _)| (index % 192, index / 192)).collect::<Vec<(usize, usize)>>();
+	assert_eq!(lit.len(), 9);
+	assert!(lit.iter().all(|(x, y)| (3..6).contains(x) && (3..6).contains(y)));
+    }
+
+    #[test]
+    fn a_png_has_a_header_the_pixels_and_an_end() {
+	let image = gradient(5, 3);
+	let chunks = chunks(&image.to_png());
+	assert_eq!(chunks.iter().map(|(kind, _)| kind).collect::<Vec<&[u8; 4]>>(), vec![b"IHDR", b"IDAT", b"IEND"]);
+	assert_eq!(chunks[0].1, vec![0, 0, 0, 5, 0, 0, 0, 3, 8, 2, 0, 0, 0]);
+	assert!(chunks[2].1.is_empty());
+	assert_eq!(checksum::crc32(b"IEND"), 0xAE42_6082);
+	let (data, blocks) = unzlib_stored(&chunks[1].1);
+	assert_eq!(blocks, 1);
+	assert_eq!(data, scanlines(&image));
+    }
+
+    #[test]
+    fn a_big_png_is_split_into_several_blocks() {
+	let image = gradient(200, 150); //90150 bytes of scanlines, more than one stored block can hold.
+	let chunks = chunks(&image.to_png());
+	let (data, blocks) = unzlib_stored(&chunks[1].1);
+	assert_eq!(blocks, 2);
+	assert_eq!(data, scanlines(&image));
+    }
+
+    #[test]
+    fn the_format_comes_from_the_extension() {
+	assert_eq!(ImageFormat::from_path(Path::new("shot.PPM")), ImageFormat::Ppm);
+	assert_eq!(ImageFormat::from_path(Path::new("shot.png")), ImageFormat::Png);
+	assert_eq!(ImageFormat::from_path(Path::new("shot")), ImageFormat::Png);
+    }
+}
@@ -2,7 +2,14 @@
 //! this module implements the instruction decoder for the chip 8.
 //! it also implements the various registers of the chip 8.
 
-use crate::chip_8::{memory, timers, video, keyboard};
+use crate::chip_8::{memory, timers, video, keyboard, quirks, random};
+use crate::chip_8::errors::{Chip8Error, Fault};
+use crate::chip_8::disassembler::Instruction;
+use crate::chip_8::save_state::SaveState;
+use crate::chip_8::audio::SampleStream;
+
+///the type of the closures which execute each instruction, see DECODED_INSTRUCTIONS.
+type InstructionClosure = fn(&mut ChipSystem, u16) -> Result<(), Fault>;
 
 ///Holds a list of closures which execute the decoded instruciton
 ///the closures are strategically ordered to match the chip instructions order,
@@ -10,7 +17,7 @@ use crate::chip_8::{memory, timers, video, keyboard};
 ///you just insert that first hex number into the array to get the desired corresponding closure.
 ///with that closure, you just need to provide it with access to the ChipSystem and the instruction details,
 /// so that the instruction can have the desired outcome.
-const DECODED_INSTRUCTIONS: [fn(&mut ChipSystem, u16) -> Result<(), String>; 16] = [
+const DECODED_INSTRUCTIONS: [InstructionClosure; 16] = [
     |system, input| { //instruction 0
 	match input & 0x0FFF {
 	    0x0E0 => {
@@ -20,7 +27,34 @@ const DECODED_INSTRUCTIONS: [fn(&mut ChipSystem, u16) -> Result<(), String>; 16]
 	    0x0EE => {
 		system.program_counter = system.stack.pop()?;
 	    }
-	    _ => return Err(format!("unimplemented usage of opcode {:#06x}", input))
+	    0x0D0..=0x0DF if system.quirks.xo_chip => { //XO-CHIP: scroll the screen up by n rows
+		system.video.scroll_up(get_n(input));
+		system.video.update_screen();
+	    }
+	    0x0C0..=0x0CF if system.quirks.super_chip => { //SUPER-CHIP: scroll the screen down by n rows
+		system.video.scroll_down(get_n(input));
+		system.video.update_screen();
+	    }
+	    0x0FB if system.quirks.super_chip => { //SUPER-CHIP: scroll the screen right by 4 pixels
+		system.video.scroll_right(4);
+		system.video.update_screen();
+	    }
+	    0x0FC if system.quirks.super_chip => { //SUPER-CHIP: scroll the screen left by 4 pixels
+		system.video.scroll_left(4);
+		system.video.update_screen();
+	    }
+	    0x0FD if system.quirks.super_chip => { //SUPER-CHIP: exit the interpreter
+		system.halted = true;
+	    }
+	    0x0FE if system.quirks.super_chip => { //SUPER-CHIP: switch to the 64 x 32 low resolution
+		system.video.set_high_resolution(false);
+		system.video.update_screen();
+	    }
+	    0x0FF if system.quirks.super_chip => { //SUPER-CHIP: switch to the 128 x 64 high resolution
+		system.video.set_high_resolution(true);
+		system.video.update_screen();
+	    }
+	    _ => return Err(Fault::UnknownOpcode)
 	}
 	return Ok(());
     },
@@ -35,13 +69,13 @@ const DECODED_INSTRUCTIONS: [fn(&mut ChipSystem, u16) -> Result<(), String>; 16]
     },
     |system, input| { //instruction 3
 	if system.registers.variable_register[get_x(input)] == get_nn(input) {
-	    system.program_counter += 2;
+	    skip_next_instruction(system);
 	}
 	return Ok(());
     },
     |system, input| { //instruction 4
 	if system.registers.variable_register[get_x(input)] != get_nn(input) {
-	    system.program_counter += 2;
+	    skip_next_instruction(system);
 	}
 	return Ok(());
     },
@@ -51,10 +85,22 @@ const DECODED_INSTRUCTIONS: [fn(&mut ChipSystem, u16) -> Result<(), String>; 16]
 		let x = system.registers.variable_register[get_x(input)];
 		let y = system.registers.variable_register[get_y(input)];
 		if x == y {
-		    system.program_counter += 2;
+		    skip_next_instruction(system);
 		}
 	    },
-	    _ => return Err(format!("unimplemented usage of opcode {:#06x}", input))
+	    0x2 if system.quirks.xo_chip => { //XO-CHIP: save VX to VY into memory starting at I, I is left untouched
+		let i = system.registers.index_register as usize;
+		for (offset, register) in register_range(get_x(input), get_y(input)).into_iter().enumerate() {
+		    system.ram.write(i + offset, system.registers.variable_register[register])?;
+		}
+	    },
+	    0x3 if system.quirks.xo_chip => { //XO-CHIP: load VX to VY from memory starting at I, I is left untouched
+		let i = system.registers.index_register as usize;
+		for (offset, register) in register_range(get_x(input), get_y(input)).into_iter().enumerate() {
+		    system.registers.variable_register[register] = system.ram.read(i + offset)?;
+		}
+	    },
+	    _ => return Err(Fault::UnknownOpcode)
 	}
 	return Ok(());
     },
@@ -72,14 +118,23 @@ const DECODED_INSTRUCTIONS: [fn(&mut ChipSystem, u16) -> Result<(), String>; 16]
 	    0x0 => { //set x to y
 		system.registers.variable_register[get_x(input)] = system.registers.variable_register[get_y(input)];
 	    },
-	    0x1 => { //x |= y
+	    0x1 => { //x |= y, vf is reset if the quirk asks for it
 		system.registers.variable_register[get_x(input)] |= system.registers.variable_register[get_y(input)];
+		if system.quirks.logic_resets_vf {
+		    system.registers.variable_register[0xF] = 0;
+		}
 	    },
-	    0x2 => { //x &= y
+	    0x2 => { //x &= y, vf is reset if the quirk asks for it
 		system.registers.variable_register[get_x(input)] &= system.registers.variable_register[get_y(input)];
+		if system.quirks.logic_resets_vf {
+		    system.registers.variable_register[0xF] = 0;
+		}
 	    },
-	    0x3 => { //x ^= y
+	    0x3 => { //x ^= y, vf is reset if the quirk asks for it
 		system.registers.variable_register[get_x(input)] ^= system.registers.variable_register[get_y(input)];
+		if system.quirks.logic_resets_vf {
+		    system.registers.variable_register[0xF] = 0;
+		}
 	    },
 	    0x4 => { //x += y, sets vf to 1 if overflow
 		let x = system.registers.variable_register[get_x(input)] as usize;
@@ -93,15 +148,19 @@ const DECODED_INSTRUCTIONS: [fn(&mut ChipSystem, u16) -> Result<(), String>; 16]
 		system.registers.variable_register[get_x(input)] = x.wrapping_sub(y);
 		system.registers.variable_register[0xF] = if x > y {1} else {0};
 	    },
-	    0x6 => { //x = (y >> 1), set vf to y & 0x1
-		system.registers.variable_register[get_x(input)] = system.registers.variable_register[get_y(input)] >> 1;
-		system.registers.variable_register[0xF] = system.registers.variable_register[get_y(input)] & 0x01;
+	    0x6 => { //x = (y >> 1), set vf to the bit that was shifted out. the quirk decides if y or x is shifted.
+		let source = if system.quirks.shift_uses_vy {get_y(input)} else {get_x(input)};
+		let value = system.registers.variable_register[source];
+		system.registers.variable_register[get_x(input)] = value >> 1;
+		system.registers.variable_register[0xF] = value & 0x01;
 	    }
-	    0xE => { //x = (y << 1), set vf to y & 0x80
-		system.registers.variable_register[get_x(input)] = system.registers.variable_register[get_y(input)] << 1;
-		system.registers.variable_register[0xF] = system.registers.variable_register[get_y(input)] & 0x80;
+	    0xE => { //x = (y << 1), set vf to the bit that was shifted out. the quirk decides if y or x is shifted.
+		let source = if system.quirks.shift_uses_vy {get_y(input)} else {get_x(input)};
+		let value = system.registers.variable_register[source];
+		system.registers.variable_register[get_x(input)] = value << 1;
+		system.registers.variable_register[0xF] = value >> 7;
 	    }
-	    _ => return Err(format!("unimplemented usage of opcode {:#06x}", input))
+	    _ => return Err(Fault::UnknownOpcode)
 	}
 	return Ok(());
     },
@@ -111,10 +170,10 @@ const DECODED_INSTRUCTIONS: [fn(&mut ChipSystem, u16) -> Result<(), String>; 16]
 		let x = system.registers.variable_register[get_x(input)];
 		let y = system.registers.variable_register[get_y(input)];
 		if x != y {
-		    system.program_counter += 2;
+		    skip_next_instruction(system);
 		}
 	    },
-	    _ => return Err(format!("unimplemented usage of opcode {:#06x}", input))
+	    _ => return Err(Fault::UnknownOpcode)
 	}
 	return Ok(());
     },
@@ -123,50 +182,53 @@ const DECODED_INSTRUCTIONS: [fn(&mut ChipSystem, u16) -> Result<(), String>; 16]
 	return Ok(());
     },
     |system, input| { //instruciton B
-	system.program_counter = get_nnn(input) + system.registers.variable_register[0] as u16;
+	let offset_register = if system.quirks.jump_uses_vx {get_x(input)} else {0};
+	system.program_counter = get_nnn(input) + system.registers.variable_register[offset_register] as u16;
 	return Ok(());
     },
     |system, input| { //instruciton C
-	let nanosec = match std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH) {
-	    Ok(duration) => duration.subsec_nanos(),
-	    Err(error) => return Err(error.to_string())
-	};
-	system.registers.variable_register[get_x(input)] = get_nn(input) & ((nanosec & 0xFF) as u8);
+	system.registers.variable_register[get_x(input)] = get_nn(input) & system.random.next_byte();
 	return Ok(());
     },
     |system, input| { //instruciton D
 	let vx = system.registers.variable_register[get_x(input)];
 	let vy = system.registers.variable_register[get_y(input)];
-	let mut height = get_n(input);
-	let i = system.registers.index_register as usize;
-	let sprite_lines = &system.ram.memory_array[i..(i + height)] as *const [u8];
-	system.video.draw_sprite(vx, vy, move || {
-	    if height > 0 {
-		height -= 1;
-		unsafe {
-		    return Some(((*sprite_lines)[(height) as usize], (height) as u8));
+	//DXY0 draws a SUPER-CHIP 16x16 sprite, which takes two bytes per row. Without SUPER-CHIP it is a sprite 0 rows tall.
+	let (sprite_height, sprite_width) = match get_n(input) {
+	    0 if system.quirks.super_chip => (16, 16),
+	    n => (n, 8)
+	};
+	let bytes_per_line = sprite_width / 8;
+	let sprite_size = sprite_height * bytes_per_line;
+	//with XO-CHIP, every selected plane gets its own sprite, they are stored one after another starting at I.
+	let mut i = system.registers.index_register as usize;
+	let mut collision = false;
+	for plane in system.video.selected_planes() {
+	    let sprite_lines = system.ram.read_range(i, sprite_size)?;
+	    let mut height = sprite_height;
+	    collision |= system.video.draw_sprite(plane, vx, vy, sprite_width as u32, system.quirks.clip_sprites, move || {
+		if height > 0 {
+		    height -= 1;
+		    let line = &sprite_lines[(height * bytes_per_line)..((height + 1) * bytes_per_line)];
+		    let line_bits = line.iter().fold(0u16, |bits, byte| (bits << 8) | *byte as u16);
+		    return Some((line_bits, height as u8));
 		}
-	    }
-	    return None;
-	});
+		return None;
+	    });
+	    i += sprite_size;
+	}
+	system.registers.variable_register[0xF] = if collision {1} else {0}; //vf is set to 1 if any pixel was turned off.
 	system.video.update_screen();
 	return Ok(());
     },
     |system, input| { //instruciton E
-	let skip_if_equal = match get_nn(input) {
+	let skip_if_pressed = match get_nn(input) {
 	    0x9E => true,
 	    0xA1 => false,
-	    _ => return Err(format!("unimplemented usage of opcode {:#06x}", input))
+	    _ => return Err(Fault::UnknownOpcode)
 	};
-	match system.keyboard.which_key_pressed() {
-	    Some(key) => {
-		if (key == system.registers.variable_register[get_x(input)]) == skip_if_equal {
-		    system.program_counter += 2;
-		}
-	    },
-	    None => {
-		system.program_counter += 2
-	    }
+	if system.keyboard.is_pressed(system.registers.variable_register[get_x(input)]) == skip_if_pressed {
+	    skip_next_instruction(system);
 	}
 	return Ok(());
     },
@@ -175,6 +237,24 @@ const DECODED_INSTRUCTIONS: [fn(&mut ChipSystem, u16) -> Result<(), String>; 16]
 	    0x07 => system.registers.variable_register[get_x(input)] = system.delay_timer.time_value,
 	    0x15 => system.delay_timer.time_value = system.registers.variable_register[get_x(input)],
 	    0x18 => system.sound_timer.time_value = system.registers.variable_register[get_x(input)],
+	    0x00 if system.quirks.xo_chip && get_x(input) == 0 => { //XO-CHIP: F000 NNNN, I is set to the 16 bit address right after the instruction
+		system.registers.index_register = system.ram.read_word(system.program_counter.wrapping_sub(2));
+	    },
+	    0x01 if system.quirks.xo_chip => { //XO-CHIP: FN01 selects the planes to draw to, N is a bit mask of the planes
+		system.video.select_planes(get_x(input) as u8);
+	    },
+	    0x02 if system.quirks.xo_chip && get_x(input) == 0 => { //XO-CHIP: F002 loads the 16 byte audio pattern starting at I
+		let i = system.registers.index_register as usize;
+		let mut pattern = [0u8; 16];
+		pattern.copy_from_slice(system.ram.read_range(i, 16)?);
+		system.sound_timer.stream.set_pattern(pattern);
+	    },
+	    0x3A if system.quirks.xo_chip => { //XO-CHIP: sets the audio pitch register to VX
+		system.sound_timer.stream.set_pitch(system.registers.variable_register[get_x(input)]);
+	    },
+	    0x1E if system.quirks.xo_chip => { //XO-CHIP can address all 64 kilobytes, so I just wraps around at the end
+		system.registers.index_register = system.registers.index_register.wrapping_add(system.registers.variable_register[get_x(input)] as u16);
+	    },
 	    0x1E => {
 		system.registers.index_register += system.registers.variable_register[get_x(input)] as u16;
 		if system.registers.index_register & 0x1000 == 0x1000 {
@@ -182,42 +262,51 @@ const DECODED_INSTRUCTIONS: [fn(&mut ChipSystem, u16) -> Result<(), String>; 16]
 		    system.registers.index_register &= 0x0FFF;
 		}
 	    },
-	    0x0A => {
-		loop {
-		    match system.keyboard.which_key_pressed() {
-			Some(key) => {
-			    system.registers.variable_register[get_x(input)] = key;
-			    break;
-			},
-			None => {}
-		    }
+	    0x0A => { //this does not wait here, it goes back to run itself again until a key has been pressed and let go of.
+		match system.keyboard.poll_key_wait() {
+		    Some(key) => system.registers.variable_register[get_x(input)] = key,
+		    None => system.program_counter = system.program_counter.wrapping_sub(2)
 		}
 	    },
 	    0x29 => {
 		system.registers.index_register = system.ram.get_character(system.registers.variable_register[get_x(input)] as usize);
 	    },
+	    0x30 if system.quirks.super_chip => { //SUPER-CHIP: point I to the big font character
+		system.registers.index_register = system.ram.get_big_character((system.registers.variable_register[get_x(input)] & 0xF) as usize);
+	    },
+	    //SUPER-CHIP only has 8 flag registers, XO-CHIP has all 16.
+	    0x75 if system.quirks.super_chip && (get_x(input) < 8 || system.quirks.xo_chip) => { //SUPER-CHIP: save V0 to VX in the flag registers
+		let final_register = get_x(input);
+		system.registers.flag_register[0..=final_register].copy_from_slice(&system.registers.variable_register[0..=final_register]);
+	    },
+	    0x85 if system.quirks.super_chip && (get_x(input) < 8 || system.quirks.xo_chip) => { //SUPER-CHIP: load V0 to VX from the flag registers
+		let final_register = get_x(input);
+		system.registers.variable_register[0..=final_register].copy_from_slice(&system.registers.flag_register[0..=final_register]);
+	    },
 	    0x33 => {
 		let number = system.registers.variable_register[get_x(input)];
 		let i = system.registers.index_register as usize;
-		system.ram.memory_array[i] = number / 100;
-		system.ram.memory_array[i + 1] = (number / 10) % 10;
-		system.ram.memory_array[i + 2] = number % 10;		    
+		system.ram.write(i, number / 100)?;
+		system.ram.write(i + 1, (number / 10) % 10)?;
+		system.ram.write(i + 2, number % 10)?;
 	    },
 	    0x55 => {
 		let i = system.registers.index_register as usize;
 		let final_register = get_x(input);
 		for x in 0..(final_register + 1) {
-		    system.ram.memory_array[i + x] = system.registers.variable_register[x];
+		    system.ram.write(i + x, system.registers.variable_register[x])?;
 		}
+		system.registers.index_register = system.registers.index_register.wrapping_add(system.quirks.load_store_index.amount(final_register));
 	    },
 	    0x65 => {
 		let i = system.registers.index_register as usize;
 		let final_register = get_x(input);
 		for x in 0..(final_register + 1) {
-		    system.registers.variable_register[x] = system.ram.memory_array[i + x];
+		    system.registers.variable_register[x] = system.ram.read(i + x)?;
 		}
+		system.registers.index_register = system.registers.index_register.wrapping_add(system.quirks.load_store_index.amount(final_register));
 	    }
-	    _ => return Err(format!("unimplemented usage of opcode {:#06x}", input))
+	    _ => return Err(Fault::UnknownOpcode)
 	}
 	return Ok(());
     }
@@ -233,13 +322,17 @@ pub struct ChipSystem <'a> {
     video: video::VideoDisplay<'a>,
     sound_timer: timers::SoundTimer<'a>,
     delay_timer: timers::DelayTimer,
-    keyboard: keyboard::Keyboard<'a>
+    keyboard: keyboard::Keyboard<'a>,
+    quirks: quirks::Quirks,
+    random: Box<dyn random::RandomSource + 'a>,
+    halted: bool
 }
 
 impl <'a> ChipSystem <'a> {
     ///returns a new uninitialized ChipSystem. You will have to provide the program after the initialization
     ///see mod.rs TimedRunner struct for the init() function it has.
-    pub fn new<T, U, V>(video_driver: T, sound_driver: U, keyboard_driver: V) -> Self  where
+    ///The quirks decide how the ambiguous instructions behave, see the quirks module.
+    pub fn new<T, U, V>(video_driver: T, sound_driver: U, keyboard_driver: V, quirks: quirks::Quirks) -> Self  where
 	T: video::VideoDriver + 'a,
 	U: timers::SoundDriver + 'a,
 	V: keyboard::KeyboardDriver + 'a {
@@ -248,13 +341,85 @@ impl <'a> ChipSystem <'a> {
 	    program_counter: 0,
 	    registers: memory::RegisterSet::new(),
 	    stack: memory::Stack::new(),
-	    ram: memory::EntireMemory::new(),
+	    ram: memory::EntireMemory::new(if quirks.xo_chip {memory::EXTENDED_MEMORY_SIZE} else {memory::STANDARD_MEMORY_SIZE}),
 	    video: video::VideoDisplay::new(video_driver),
 	    sound_timer: timers::SoundTimer::new(sound_driver),
 	    delay_timer: timers::DelayTimer::new(),
-	    keyboard: keyboard::Keyboard::new(keyboard_driver)
+	    keyboard: keyboard::Keyboard::new(keyboard_driver),
+	    quirks,
+	    random: Box::new(random::XorShiftRandom::new(random::XorShiftRandom::seed_from_clock())),
+	    halted: false
 	}
     }
+
+    ///replaces the random number generator that CXNN uses. By default it is an XorShiftRandom seeded from the clock.
+    pub fn set_random_source<R: random::RandomSource + 'a>(&mut self, random: R) {
+	self.random = Box::new(random);
+    }
+
+    ///returns the address of the next instruction that will be decoded.
+    pub fn program_counter(&self) -> u16 {
+	return self.program_counter;
+    }
+
+    ///returns the next instruction that will be decoded.
+    pub fn next_opcode(&self) -> u16 {
+	return self.ram.peek_word(self.program_counter);
+    }
+
+    ///returns the next instruction that will be decoded, decoded into an Instruction.
+    pub fn next_instruction(&self) -> Instruction {
+	let operand = self.ram.peek_word(self.program_counter.wrapping_add(2));
+	if self.quirks.xo_chip {
+	    return Instruction::decode_long(self.next_opcode(), operand);
+	}
+	return Instruction::decode(self.next_opcode());
+    }
+
+    ///returns the index register (I).
+    pub fn index_register(&self) -> u16 {
+	return self.registers.index_register;
+    }
+
+    ///returns the 16 variable registers (V0 - VF).
+    pub fn variable_registers(&self) -> [u8; 16] {
+	return self.registers.variable_register;
+    }
+
+    ///returns the return addresses that are on the stack, the most recent one is last.
+    pub fn stack(&self) -> &[u16] {
+	return self.stack.contents();
+    }
+
+    ///returns the current values of the delay timer and the sound timer.
+    pub fn timers(&self) -> (u8, u8) {
+	return (self.delay_timer.time_value, self.sound_timer.time_value);
+    }
+
+    ///returns what is currently on the screen.
+    pub fn screen(&self) -> video::Frame<'_> {
+	return self.video.frame();
+    }
+
+    ///returns the whole memory of the chip 8.
+    pub fn memory(&self) -> &[u8] {
+	return self.ram.contents();
+    }
+
+    ///returns the watchpoints that are set on the memory.
+    pub fn watchpoints(&self) -> &[memory::Watchpoint] {
+	return self.ram.watchpoints();
+    }
+
+    ///adds a watchpoint on the memory, and returns its number.
+    pub fn add_watchpoint(&mut self, watchpoint: memory::Watchpoint) -> usize {
+	return self.ram.add_watchpoint(watchpoint);
+    }
+
+    ///removes the watchpoint with the given number and returns it, or None if there is no watchpoint with that number.
+    pub fn remove_watchpoint(&mut self, number: usize) -> Option<memory::Watchpoint> {
+	return self.ram.remove_watchpoint(number);
+    }
 }
 
 impl std::fmt::Debug for ChipSystem<'_> {
@@ -268,18 +433,57 @@ impl std::fmt::Debug for ChipSystem<'_> {
 
 ///This takes a vector of bytes which make up the program and pushes them into memory.
 ///It is never called directly, see init() in mod.rs.
-pub fn load_program_from_vector(system: &mut ChipSystem, program_array: Vec<u8>) {
-    system.program_counter = system.ram.load_program(program_array);
+pub fn load_program_from_vector(system: &mut ChipSystem, program_array: Vec<u8>) -> Result<(), Chip8Error> {
+    system.program_counter = system.ram.load_program(program_array)?;
+    return Ok(());
 }
 
 ///decodes the next instruction at the program_counter.
 ///also ticks the timer when needed.
-pub fn decode_next_instruction(system: &mut ChipSystem) -> Result<(), String> {
-    let instruction_first_byte = system.ram.memory_array[system.program_counter as usize] as u16;
-    let instruction_second_byte = system.ram.memory_array[(system.program_counter + 1) as usize] as u16;
-    let combined_instruction = (instruction_first_byte << 8) + instruction_second_byte;
-    system.program_counter += 2;
-    return DECODED_INSTRUCTIONS[get_instruction_category(combined_instruction)](system, combined_instruction);
+///nothing happens once the program has exited with 00FD.
+///If the instruction fails, the returned error tells you the address and opcode of the instruction,
+/// and the program counter is left pointing at the failed instruction.
+///If the instruction set off a watchpoint, it is finished and then a WatchpointHit error is returned.
+pub fn decode_next_instruction(system: &mut ChipSystem) -> Result<(), Chip8Error> {
+    if system.halted {
+	return Ok(());
+    }
+    let instruction_address = system.program_counter;
+    let combined_instruction = system.ram.fetch_instruction(instruction_address);
+    system.program_counter = system.program_counter.wrapping_add(instruction_length(system, combined_instruction));
+    let result = DECODED_INSTRUCTIONS[get_instruction_category(combined_instruction)](system, combined_instruction);
+    let watch_hit = system.ram.take_watch_hit();
+    if let Err(fault) = result {
+	system.program_counter = instruction_address;
+	return Err(Chip8Error::Execution { pc: instruction_address, opcode: combined_instruction, fault });
+    }
+    if let Some((address, access)) = watch_hit {
+	return Err(Chip8Error::WatchpointHit { pc: instruction_address, opcode: combined_instruction, address, access });
+    }
+    return Ok(());
+}
+
+///tells you if the program has exited with the SUPER-CHIP 00FD instruction.
+pub fn is_halted(system: &ChipSystem) -> bool {
+    return system.halted;
+}
+
+///tells you if the program is stuck on FX0A, waiting for a key to be pressed and let go of.
+pub fn is_waiting_for_key(system: &ChipSystem) -> bool {
+    return system.keyboard.is_waiting();
+}
+
+///returns how many bytes long the instruction is. Every instruction is 2 bytes long, except for the XO-CHIP F000 NNNN instruction,
+/// which has the 2 byte address right after it.
+fn instruction_length(system: &ChipSystem, instruction: u16) -> u16 {
+    return if system.quirks.xo_chip && instruction == 0xF000 {4} else {2};
+}
+
+///moves the program counter past the next instruction, this is what all the skip instructions do.
+///the next instruction can be a 4 byte XO-CHIP instruction, so it has to be looked at to know how far to skip.
+fn skip_next_instruction(system: &mut ChipSystem) {
+    let next_instruction = system.ram.peek_word(system.program_counter);
+    system.program_counter = system.program_counter.wrapping_add(instruction_length(system, next_instruction));
 }
 
 ///Takes the chipsystem timers and decrements them exactly once if they have a value above 0.
@@ -288,6 +492,98 @@ pub fn tick_timers(system: &mut ChipSystem) {
     system.delay_timer.tick_down();
 }
 
+///ticks the timers like tick_timers(), but the sound driver is not given the sound, since a replayed frame has already been heard.
+pub fn tick_timers_quietly(system: &mut ChipSystem) {
+    system.sound_timer.tick_down_quietly();
+    system.delay_timer.tick_down();
+}
+
+///returns the keys held down and the FX0A wait, which the rewind buffer keeps with each snapshot.
+pub fn key_state(system: &ChipSystem) -> keyboard::KeyState {
+    return system.keyboard.key_state();
+}
+
+///hands over the key events the driver has given since the last time this was called.
+pub fn take_key_events(system: &mut ChipSystem) -> Vec<keyboard::KeyEvent> {
+    return system.keyboard.take_recorded_events();
+}
+
+///makes the keyboard use the keys from a snapshot instead of the driver, until end_replay() is called.
+pub fn start_replay(system: &mut ChipSystem, keys: keyboard::KeyState) {
+    system.keyboard.start_replay(keys);
+}
+
+///gives the replay a key event, the next instruction that looks at the keys gets it.
+pub fn replay_key_event(system: &mut ChipSystem, event: keyboard::KeyEvent) {
+    system.keyboard.replay_event(event);
+}
+
+///makes the keyboard go back to the keys that are really held down.
+pub fn end_replay(system: &mut ChipSystem) {
+    system.keyboard.end_replay();
+}
+
+///tells the video driver that the frame is over, so it can put what was drawn on the screen.
+pub fn present_frame(system: &mut ChipSystem) {
+    system.video.present();
+}
+
+///takes a snapshot of the whole chip 8. instructions_in_frame is left at 0, it belongs to the TimedRunner which fills it in.
+pub fn save_state(system: &ChipSystem) -> SaveState {
+    return SaveState {
+	quirks: system.quirks,
+	halted: system.halted,
+	program_counter: system.program_counter,
+	index_register: system.registers.index_register,
+	variable_registers: system.registers.variable_register,
+	flag_registers: system.registers.flag_register,
+	stack: system.stack.contents().to_vec(),
+	delay_timer: system.delay_timer.time_value,
+	sound_timer: system.sound_timer.time_value,
+	audio_pattern: system.sound_timer.stream.pattern(),
+	pitch: system.sound_timer.stream.pitch(),
+	high_resolution: system.video.is_high_resolution(),
+	selected_planes: system.video.plane_mask(),
+	planes: *system.video.planes(),
+	memory: system.ram.contents().to_vec(),
+	random_state: system.random.save(),
+	instructions_in_frame: 0
+    };
+}
+
+///puts the chip 8 back into the state of a snapshot, and redraws the screen. The drivers and the watchpoints are kept.
+///If the snapshot is not possible for this chip 8, then an Err() is returned and nothing is changed.
+pub fn load_state(system: &mut ChipSystem, state: &SaveState) -> Result<(), Chip8Error> {
+    let expected_size = if state.quirks.xo_chip {memory::EXTENDED_MEMORY_SIZE} else {memory::STANDARD_MEMORY_SIZE};
+    if state.memory.len() != expected_size {
+	return Err(Chip8Error::InvalidSaveState { reason: format!("the memory is {} bytes, but it should be {}", state.memory.len(), expected_size) });
+    }
+    if system.stack.restore(&state.stack).is_err() {
+	return Err(Chip8Error::InvalidSaveState { reason: "the stack is too deep".to_string() });
+    }
+    system.quirks = state.quirks;
+    system.halted = state.halted;
+    system.keyboard.cancel_wait();
+    system.program_counter = state.program_counter;
+    system.registers.index_register = state.index_register;
+    system.registers.variable_register = state.variable_registers;
+    system.registers.flag_register = state.flag_registers;
+    system.delay_timer.time_value = state.delay_timer;
+    system.sound_timer.time_value = state.sound_timer;
+    let mut stream = SampleStream::new(system.sound_timer.stream.sample_rate());
+    if let Some(pattern) = state.audio_pattern {
+	stream.set_pattern(pattern);
+    }
+    stream.set_pitch(state.pitch);
+    system.sound_timer.stream = stream;
+    system.ram.restore(state.memory.clone());
+    if !state.random_state.is_empty() {
+	system.random.restore(&state.random_state);
+    }
+    system.video.restore(state.planes, state.selected_planes, state.high_resolution);
+    return Ok(());
+}
+
 ///takes an input instrucition and returns the second nibble from the left.
 ///this second nibble usually represents a variable register (there are 16 of them so 0-F to choose one of them).
 const fn get_x(input: u16) -> usize {
@@ -316,6 +612,15 @@ const fn get_nnn(input: u16) -> u16 {
     return input & 0x0FFF;
 }
 
+///returns the registers from x to y, in order. if x is bigger than y, the registers are given backwards, like XO-CHIP 5XY2 and 5XY3 expect.
+fn register_range(x: usize, y: usize) -> Vec<usize> {
+    if x <= y {
+	return (x..=y).collect();
+    } else {
+	return (y..=x).rev().collect();
+    }
+}
+
 ///takes an input instruction and returns the very first nibble
 ///this is used to index the closures array to get the desired closure which executes the desired instruction.
 const fn get_instruction_category(input: u16) -> usize {
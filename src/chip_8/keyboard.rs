@@ -1,29 +1,160 @@
-
 //! This is the keyboard for the chip8.
 //! it provides a struct with the basic functions of a keyboard, and a KeyboardDriver trait to implement a keyboard.
+//! The chip 8 keypad has 16 keys, 0 to F, and any number of them can be held down at once. The keyboard keeps track of which ones are down
+//!  as a 16 bit mask, where bit N is set while key N is held down, and the driver tells it whenever a key goes down or comes back up.
+
+use std::collections::VecDeque;
+
+///something that happened to one of the 16 keys.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KeyEvent {
+    Pressed(u8),
+    Released(u8)
+}
 
 ///this trait is used to interface with different implementations of the chip8 keyboard.
 ///see the drivers.rs file for a simple implentation.
 pub trait KeyboardDriver {
-    fn get_key_pressed(&mut self) -> Option<u8>;
+    ///returns the next key that was pressed or let go since the last call, in the order it happened, or None once nothing else has happened.
+    fn next_event(&mut self) -> Option<KeyEvent>;
+}
+
+///returns the event that brings the keys the driver has reported (reported) one step closer to the keys that are actually down (held),
+/// and updates reported to match. This is for drivers that know which keys are down rather than when they went down,
+/// they can call this from next_event() until it returns None.
+pub fn next_mask_event(reported: &mut u16, held: u16) -> Option<KeyEvent> {
+    let changed = *reported ^ held;
+    if changed == 0 {
+	return None;
+    }
+    let key = changed.trailing_zeros() as u8;
+    *reported ^= 1 << key;
+    return Some(if held & (1 << key) != 0 {KeyEvent::Pressed(key)} else {KeyEvent::Released(key)});
+}
+
+///The keys held down and the FX0A wait. These are not part of a save state, but the rewind buffer keeps them with every snapshot,
+/// so that replaying the instructions after a snapshot starts from the same keys the program had the first time.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct KeyState {
+    pub pressed: u16,
+    pub waiting: Option<u16>
 }
 
 ///this is a keyboard object which used a KeyboardDriver to get and interpret keypresses.
 pub struct Keyboard <'a> {
-    keyboard_driver: Box<dyn KeyboardDriver + 'a>
+    keyboard_driver: Box<dyn KeyboardDriver + 'a>,
+    pressed: u16, //bit N is set while key N is held down.
+    waiting: Option<u16>, //while FX0A is waiting for a key, the keys that have been pressed since it started.
+    recorded_events: Vec<KeyEvent>, //the events from the driver since take_recorded_events() was last called.
+    live_keys: Option<u16>, //while replaying, the keys that are really held down. The driver is not read until the replay is over.
+    replay_events: VecDeque<KeyEvent> //while replaying, the events to give the program instead of the driver's.
 }
 
 impl <'a> Keyboard <'a> {
     ///returns a new keyboard which uses the desired keyboard.
     pub fn new<T: KeyboardDriver + 'a>(keyboard_driver: T) -> Self{
 	return Keyboard {
-	    keyboard_driver: Box::new(keyboard_driver)
+	    keyboard_driver: Box::new(keyboard_driver),
+	    pressed: 0,
+	    waiting: None,
+	    recorded_events: Vec::new(),
+	    live_keys: None,
+	    replay_events: VecDeque::new()
 	}
     }
 
-    ///tells you which key is pressed.
-    pub fn which_key_pressed(&mut self) -> Option<u8> {
-	return self.keyboard_driver.get_key_pressed();
+    ///applies an event to the mask of the keys that are held down.
+    fn apply(&mut self, event: KeyEvent) {
+	match event {
+	    KeyEvent::Pressed(key) => self.pressed |= 1 << (key & 0xF),
+	    KeyEvent::Released(key) => self.pressed &= !(1 << (key & 0xF))
+	}
+    }
+
+    ///returns the next event from the driver, and keeps it for take_recorded_events(). While replaying, the events come from replay_event() instead.
+    fn next_event(&mut self) -> Option<KeyEvent> {
+	if self.live_keys.is_some() {
+	    return self.replay_events.pop_front();
+	}
+	let event = self.keyboard_driver.next_event();
+	if let Some(event) = event {
+	    self.recorded_events.push(event);
+	}
+	return event;
+    }
+
+    ///returns the mask of the keys that are held down, bit N is set if key N is.
+    pub fn pressed_keys(&mut self) -> u16 {
+	while let Some(event) = self.next_event() {
+	    self.apply(event);
+	}
+	return self.pressed;
+    }
+
+    ///tells you if a key is held down. There are only 16 keys, so anything above F is never held down.
+    pub fn is_pressed(&mut self, key: u8) -> bool {
+	return key < 16 && self.pressed_keys() & (1 << key) != 0;
+    }
+
+    ///checks if a key has been pressed and then let go of since the wait started, like the COSMAC VIP waited for, and returns that key.
+    ///If not, the keyboard is left waiting and None is returned, so this should be called again until a key comes back.
+    ///keys that were already held down when the wait started only count once they are pressed again.
+    pub fn poll_key_wait(&mut self) -> Option<u8> {
+	let mut pressed_while_waiting = self.waiting.unwrap_or(0);
+	while let Some(event) = self.next_event() {
+	    self.apply(event);
+	    match event {
+		KeyEvent::Pressed(key) => pressed_while_waiting |= 1 << (key & 0xF),
+		KeyEvent::Released(key) if pressed_while_waiting & (1 << (key & 0xF)) != 0 => {
+		    self.waiting = None;
+		    return Some(key & 0xF);
+		},
+		KeyEvent::Released(_) => ()
+	    }
+	}
+	self.waiting = Some(pressed_while_waiting);
+	return None;
+    }
+
+    ///tells you if the keyboard is in the middle of waiting for a key.
+    pub fn is_waiting(&self) -> bool {
+	return self.waiting.is_some();
+    }
+
+    ///returns the keys held down and the FX0A wait, for the rewind buffer to keep with a snapshot.
+    pub fn key_state(&self) -> KeyState {
+	return KeyState { pressed: self.pressed, waiting: self.waiting };
+    }
+
+    ///hands over the events the driver has given since the last time this was called.
+    pub fn take_recorded_events(&mut self) -> Vec<KeyEvent> {
+	return std::mem::take(&mut self.recorded_events);
     }
-}
 
+    ///puts the keys back the way they were in a snapshot and stops reading the driver, so the instructions after the snapshot
+    /// can be replayed with the same keys. end_replay() goes back to the keys that are really held down.
+    pub fn start_replay(&mut self, state: KeyState) {
+	self.live_keys = Some(self.live_keys.unwrap_or(self.pressed));
+	self.pressed = state.pressed;
+	self.waiting = state.waiting;
+	self.replay_events.clear();
+    }
+
+    ///gives an event to the replay, the next time the program looks at the keys it gets it, like it did from the driver the first time.
+    pub fn replay_event(&mut self, event: KeyEvent) {
+	self.replay_events.push_back(event);
+    }
+
+    ///goes back to the keys that are really held down after a replay, and to reading the driver. The FX0A wait is kept as the replay left it.
+    pub fn end_replay(&mut self) {
+	if let Some(live_keys) = self.live_keys.take() {
+	    self.pressed = live_keys;
+	}
+	self.replay_events.clear();
+    }
+
+    ///stops waiting for a key, for when the chip 8 is put somewhere else, like when a save state is loaded.
+    pub fn cancel_wait(&mut self) {
+	self.waiting = None;
+    }
+}
@@ -0,0 +1,113 @@
+//! This module holds the keymaps, which say which keys on the computer's keyboard press which of the 16 chip 8 keys.
+//! The chip 8 keypad is a 4x4 grid, and the usual way to play is with the 4x4 block of keys on the left of the keyboard:
+//!  1 2 3 C        1 2 3 4
+//!  4 5 6 D   is   q w e r   on a QWERTY keyboard.
+//!  7 8 9 E        a s d f
+//!  A 0 B F        z x c v
+//! Other keyboards have other letters in those places, so there are built-in keymaps for them, and a keymap can also be loaded from a file.
+//!
+//! A keymap file is a small TOML file like this one, any keys that are not listed keep the layout's characters:
+//!  # start from a built-in layout (qwerty if this is left out)
+//!  layout = "azerty"
+//!  [keys]
+//!  5 = "w"      # key 5 is pressed with w
+//!  8 = "s"
+//!  7 = "ad"     # either a or d presses key 7
+//!  0 = ""       # nothing presses key 0
+
+use std::path::Path;
+use crate::chip_8::config;
+
+///Which characters press each of the 16 keys. A character only ever presses one key, and upper and lower case letters press the same key.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Keymap {
+    keys: [String; 16] //the characters that press each key, key 0 first.
+}
+
+impl Keymap {
+    ///The 1234 qwer asdf zxcv block on a QWERTY keyboard.
+    pub fn qwerty() -> Self {
+	return Keymap::from_characters(["x", "1", "2", "3", "q", "w", "e", "a", "s", "d", "z", "c", "4", "r", "f", "v"]);
+    }
+
+    ///The same block on an AZERTY keyboard, where the number row types &é"' without shift, so both those and the numbers work.
+    pub fn azerty() -> Self {
+	return Keymap::from_characters(["x", "1&", "2é", "3\"", "a", "z", "e", "q", "s", "d", "w", "c", "4'", "r", "f", "v"]);
+    }
+
+    ///The same block on a Dvorak keyboard, 1234 ',.p aoeu ;qjk.
+    pub fn dvorak() -> Self {
+	return Keymap::from_characters(["q", "1", "2", "3", "'", ",", ".", "a", "o", "e", ";", "j", "4", "p", "u", "k"]);
+    }
+
+    ///Every key is pressed with its own hex digit, like the labels on the COSMAC VIP hex keypad. The digits 5 to 9 are
+    /// then keys instead of save slots.
+    pub fn cosmac_vip() -> Self {
+	return Keymap::from_characters(["0", "1", "2", "3", "4", "5", "6", "7", "8", "9", "a", "b", "c", "d", "e", "f"]);
+    }
+
+    ///Returns the built-in keymap with the given name ("qwerty", "azerty", "dvorak" or "cosmac_vip"), or None if there is none by that name.
+    pub fn from_name(name: &str) -> Option<Self> {
+	return match name.to_ascii_lowercase().as_str() {
+	    "qwerty" => Some(Keymap::qwerty()),
+	    "azerty" => Some(Keymap::azerty()),
+	    "dvorak" => Some(Keymap::dvorak()),
+	    "cosmac_vip" | "vip" | "hex" => Some(Keymap::cosmac_vip()),
+	    _ => None
+	};
+    }
+
+    fn from_characters(keys: [&str; 16]) -> Self {
+	return Keymap {
+	    keys: keys.map(|characters| characters.to_lowercase())
+	};
+    }
+
+    ///returns the key that a character presses, if it presses one.
+    pub fn key_for(&self, character: char) -> Option<u8> {
+	let character = character.to_lowercase().next().unwrap_or(character);
+	return self.keys.iter().position(|characters| characters.contains(character)).map(|key| key as u8);
+    }
+
+    ///makes the characters press a key instead of the ones that pressed it before. The characters stop pressing any other key.
+    pub fn set_key(&mut self, key: u8, characters: &str) {
+	let characters = characters.to_lowercase();
+	for other in self.keys.iter_mut() {
+	    other.retain(|character| !characters.contains(character));
+	}
+	self.keys[(key & 0xF) as usize] = characters;
+    }
+
+    ///reads a keymap file, see the module documentation for what it looks like.
+    ///Any problem with the file is returned as an Err() with a message for the user, that says which line it is on.
+    pub fn from_config(config: &str) -> Result<Self, String> {
+	let mut keymap = Keymap::qwerty();
+	for setting in config::parse(config)? {
+	    match (setting.table.as_deref(), setting.name.as_str()) {
+		(None, "layout") => {
+		    let layout = setting.string()?;
+		    keymap = Keymap::from_name(layout).ok_or_else(|| setting.error(&format!("unknown layout \"{}\", expected qwerty, azerty, dvorak or cosmac_vip", layout)))?;
+		},
+		(Some("keys"), name) => {
+		    let key = u8::from_str_radix(name, 16).ok().filter(|key| *key < 16)
+			.ok_or_else(|| setting.error(&format!("\"{}\" is not a key, it should be a hex digit from 0 to f", name)))?;
+		    keymap.set_key(key, setting.string()?);
+		},
+		(None, name) => return Err(setting.error(&format!("unknown setting \"{}\"", name))),
+		(Some(table), _) => return Err(setting.error(&format!("unknown table [{}], only [keys] is allowed", table)))
+	    }
+	}
+	return Ok(keymap);
+    }
+
+    ///reads a keymap file from disk, the errors start with the file's path.
+    pub fn load(path: &Path) -> Result<Self, String> {
+	return config::load_file(path, Keymap::from_config);
+    }
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+	return Keymap::qwerty();
+    }
+}
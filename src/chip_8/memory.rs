@@ -3,6 +3,10 @@
 //! A Stack struct and and EntireMemory struct are provided to deal with these two components
 //! A Registers struct is also used to store the various regusters of the chip8.
 
+use std::cell::Cell;
+use std::fmt;
+use crate::chip_8::errors::{Chip8Error, Fault};
+
 ///This array contains a default font for the chip 8.
 const CHIP_FONT: [u8; 80] = [
     0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
@@ -23,48 +27,246 @@ const CHIP_FONT: [u8; 80] = [
     0xF0, 0x80, 0xF0, 0x80, 0x80  // F
 ];
 
+///This array contains the big 8x10 font used by the SUPER-CHIP FX30 instruction.
+const CHIP_BIG_FONT: [u8; 160] = [
+    0xFF, 0xFF, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, // 0
+    0x18, 0x78, 0x78, 0x18, 0x18, 0x18, 0x18, 0x18, 0xFF, 0xFF, // 1
+    0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, // 2
+    0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 3
+    0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0x03, 0x03, 0x03, 0x03, // 4
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 5
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, // 6
+    0xFF, 0xFF, 0x03, 0x03, 0x06, 0x0C, 0x18, 0x18, 0x18, 0x18, // 7
+    0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, // 8
+    0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 9
+    0x7E, 0xFF, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xC3, // A
+    0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, // B
+    0x3C, 0xFF, 0xC3, 0xC0, 0xC0, 0xC0, 0xC0, 0xC3, 0xFF, 0x3C, // C
+    0xFC, 0xFE, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFE, 0xFC, // D
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, // E
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xC0, 0xC0  // F
+];
+
+///the amount of memory the original chip 8 and SUPER-CHIP have (4 kilobytes).
+pub const STANDARD_MEMORY_SIZE: usize = 0x1000;
+///the amount of memory XO-CHIP programs can address (64 kilobytes).
+pub const EXTENDED_MEMORY_SIZE: usize = 0x10000;
+
+///The ways an instruction can touch memory, a Watchpoint can be set to fire on any of them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Access {
+    ///memory was read, by FX65, 5XY3, F002, the sprite fetch of DXYN, or by fetching an instruction.
+    Read,
+    ///memory was written, by FX33, FX55 or 5XY2.
+    Write,
+    ///the instruction at the address was executed.
+    Execute
+}
+
+impl fmt::Display for Access {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+	return match self {
+	    Access::Read => write!(f, "read"),
+	    Access::Write => write!(f, "write"),
+	    Access::Execute => write!(f, "execute")
+	};
+    }
+}
+
+///Watches a range of memory addresses (start and end are both included), and fires when one of the chosen kinds of access happens to them.
+///When a watchpoint fires, the chip 8 finishes the instruction that caused it and then stops, see Chip8Error::WatchpointHit.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Watchpoint {
+    pub start: usize,
+    pub end: usize,
+    pub on_read: bool,
+    pub on_write: bool,
+    pub on_execute: bool
+}
+
+impl Watchpoint {
+    ///tells you if the watchpoint should fire for the given access to the given address.
+    pub fn triggers(&self, address: usize, access: Access) -> bool {
+	let watched_access = match access {
+	    Access::Read => self.on_read,
+	    Access::Write => self.on_write,
+	    Access::Execute => self.on_execute
+	};
+	return watched_access && self.start <= address && address <= self.end;
+    }
+}
+
+impl fmt::Display for Watchpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+	let accesses = [(self.on_read, "r"), (self.on_write, "w"), (self.on_execute, "x")]
+	    .iter()
+	    .filter(|(watched, _)| *watched)
+	    .map(|(_, name)| *name)
+	    .collect::<String>();
+	return write!(f, "{:#06x}-{:#06x} ({})", self.start, self.end, accesses);
+    }
+}
+
 ///This struct takes care of the RAM for the chip8
+///All the instructions go through the read and write methods, so that the watchpoints can see every access.
 pub struct EntireMemory {
-    pub memory_array: [u8; 4096], //the full 4 kilobytes of memory is stored in a single array.
-    font_beginning_index: u16
+    memory_array: Vec<u8>, //the full memory (4 kilobytes, or 64 for XO-CHIP) is stored in a single array.
+    font_beginning_index: u16,
+    big_font_beginning_index: u16,
+    watchpoints: Vec<Watchpoint>,
+    watch_hit: Cell<Option<(usize, Access)>> //the first watched access since the last take_watch_hit(), reads only borrow the memory so this is a Cell.
 }
 
 /// This defines the methods for the Entirememory
 impl EntireMemory {
     ///This function will generate a new ram setup for the chip8 with the default font already loaded
-    pub fn new() -> Self {
+    ///the size is the number of bytes of memory, usually STANDARD_MEMORY_SIZE or EXTENDED_MEMORY_SIZE.
+    pub fn new(size: usize) -> Self {
 	let mut new_memory = EntireMemory {
-	    memory_array: [0u8; 4096],
+	    memory_array: vec![0u8; size],
 	    font_beginning_index: 0,
+	    big_font_beginning_index: 80,
+	    watchpoints: Vec::new(),
+	    watch_hit: Cell::new(None)
 	};
 	new_memory.apply_font(&CHIP_FONT);
+	new_memory.apply_big_font(&CHIP_BIG_FONT);
 	
 	return new_memory;
     }
 
     ///This function will apply a font to the memory
     pub fn apply_font(&mut self, font: &[u8; 80]) {
-	self.memory_array[0..79]
+	let start = self.font_beginning_index as usize;
+	self.memory_array[start..(start + 80)]
+	    .iter_mut()
+	    .enumerate()
+	    .for_each(|(i, val)| *val = font[i]);
+    }
+
+    ///This function will apply a big font to the memory, it goes right after the normal font.
+    ///The big font has 10 bytes per character instead of 5.
+    pub fn apply_big_font(&mut self, font: &[u8; 160]) {
+	let start = self.big_font_beginning_index as usize;
+	self.memory_array[start..(start + 160)]
 	    .iter_mut()
 	    .enumerate()
 	    .for_each(|(i, val)| *val = font[i]);
     }
 
     ///this function takes in a Vector of bytes which make up a program, and loads them into the correct position in memory.
-    pub fn load_program(&mut self, data: Vec<u8>) -> u16 {
+    ///If the program is too big to fit between 0x200 and the end of memory, then an Err() is returned and nothing is loaded.
+    pub fn load_program(&mut self, data: Vec<u8>) -> Result<u16, Chip8Error> {
+	let max_size = self.memory_array.len() - 0x200;
+	if data.len() > max_size {
+	    return Err(Chip8Error::ProgramTooLarge { size: data.len(), max_size });
+	}
 	data
 	    .iter()
 	    .enumerate()
 	    .for_each(|(i, val)| {
 		self.memory_array[i + 0x200] = *val;
 	    });
-	return (0x200) as u16;
+	return Ok(0x200);
+    }
+
+    ///returns the whole memory, without setting off any watchpoints.
+    pub fn contents(&self) -> &[u8] {
+	return &self.memory_array;
+    }
+
+    ///replaces the whole memory, fonts included, with the contents of a save state. The watchpoints are kept.
+    pub fn restore(&mut self, contents: Vec<u8>) {
+	self.memory_array = contents;
+	self.watch_hit.set(None);
+    }
+
+    ///reads the byte at the given address. If the address is past the end of memory, then an Err() is returned.
+    pub fn read(&self, address: usize) -> Result<u8, Fault> {
+	self.watch(address, Access::Read);
+	return self.memory_array.get(address).copied().ok_or(Fault::MemoryOutOfBounds { address });
+    }
+
+    ///writes the byte to the given address. If the address is past the end of memory, then an Err() is returned.
+    pub fn write(&mut self, address: usize, value: u8) -> Result<(), Fault> {
+	self.watch(address, Access::Write);
+	match self.memory_array.get_mut(address) {
+	    Some(byte) => *byte = value,
+	    None => return Err(Fault::MemoryOutOfBounds { address })
+	}
+	return Ok(());
+    }
+
+    ///returns the given number of bytes starting at the given address.
+    ///If any of them are past the end of memory, then an Err() is returned with the first address that does not exist.
+    pub fn read_range(&self, address: usize, length: usize) -> Result<&[u8], Fault> {
+	(address..(address + length)).for_each(|watched| self.watch(watched, Access::Read));
+	return self.memory_array.get(address..(address + length)).ok_or(Fault::MemoryOutOfBounds { address: address.max(self.memory_array.len()) });
+    }
+
+    ///reads the two bytes at the given address as one big endian number, the address wraps around at the end of memory.
+    ///this is how instructions, and the XO-CHIP F000 NNNN long address, are stored.
+    pub fn read_word(&self, address: u16) -> u16 {
+	let size = self.memory_array.len();
+	self.watch(address as usize % size, Access::Read);
+	self.watch((address as usize + 1) % size, Access::Read);
+	return self.peek_word(address);
+    }
+
+    ///reads the instruction at the given address. This is the same as read_word(), but it also sets off the execute watchpoints.
+    pub fn fetch_instruction(&self, address: u16) -> u16 {
+	self.watch(address as usize % self.memory_array.len(), Access::Execute);
+	return self.read_word(address);
+    }
+
+    ///reads two bytes like read_word(), without setting off any watchpoints. This is for looking ahead at instructions.
+    pub fn peek_word(&self, address: u16) -> u16 {
+	let size = self.memory_array.len();
+	let first_byte = self.memory_array[address as usize % size] as u16;
+	let second_byte = self.memory_array[(address as usize + 1) % size] as u16;
+	return (first_byte << 8) + second_byte;
+    }
+
+    ///adds a watchpoint, and returns its number.
+    pub fn add_watchpoint(&mut self, watchpoint: Watchpoint) -> usize {
+	self.watchpoints.push(watchpoint);
+	return self.watchpoints.len() - 1;
+    }
+
+    ///removes the watchpoint with the given number and returns it, or None if there is no watchpoint with that number.
+    pub fn remove_watchpoint(&mut self, number: usize) -> Option<Watchpoint> {
+	if number < self.watchpoints.len() {
+	    return Some(self.watchpoints.remove(number));
+	}
+	return None;
+    }
+
+    ///returns all the watchpoints.
+    pub fn watchpoints(&self) -> &[Watchpoint] {
+	return &self.watchpoints;
+    }
+
+    ///returns the address and kind of the first watched access since this was last called, and forgets about it.
+    pub fn take_watch_hit(&self) -> Option<(usize, Access)> {
+	return self.watch_hit.take();
+    }
+
+    ///remembers the access if a watchpoint fires on it, only the first one is kept until take_watch_hit() is called.
+    fn watch(&self, address: usize, access: Access) {
+	if self.watch_hit.get().is_none() && self.watchpoints.iter().any(|watchpoint| watchpoint.triggers(address, access)) {
+	    self.watch_hit.set(Some((address, access)));
+	}
     }
 
     ///this function will return the location of the specified font character in memory.
     pub fn get_character(&self, input: usize) -> u16 {
 	return self.font_beginning_index +  (input as u16 * 5);
     }
+
+    ///this function will return the location of the specified big font character in memory.
+    pub fn get_big_character(&self, input: usize) -> u16 {
+	return self.big_font_beginning_index + (input as u16 * 10);
+    }
 }
 
 ///This stack comes with 64 bytes of space, and can store up to 32 addresses (the addresses are 16 bit each).
@@ -85,9 +287,9 @@ impl Stack {
 
     ///This method will push an address onto the stack, the address can only be 12 bits long maximum (max number 4096).
     ///If there is no more space on the stack, then an Err() is returned.
-    pub fn push(&mut self, value: u16) -> Result<(), String> {
+    pub fn push(&mut self, value: u16) -> Result<(), Fault> {
 	if self.stack_position == 32 {
-	    return Err("Stack Overflow".to_string());
+	    return Err(Fault::StackOverflow);
 	} else {
 	    self.stack_array[self.stack_position] = value;
 	    self.stack_position += 1;
@@ -97,21 +299,39 @@ impl Stack {
 
     ///pops an address from the stack.
     ///If there is no more things to be popped, then an Err() is returned.
-    pub fn pop(&mut self) -> Result<u16, String> {
+    pub fn pop(&mut self) -> Result<u16, Fault> {
 	if self.stack_position == 0 {
-	    return Err("Stack completely empty".to_string());
+	    return Err(Fault::StackUnderflow);
 	} else {
 	    self.stack_position -= 1;
 	    return Ok(self.stack_array[self.stack_position]);
 	}
     }
+
+    ///returns the addresses that are currently on the stack, the most recently pushed one is last.
+    pub fn contents(&self) -> &[u16] {
+	return &self.stack_array[0..self.stack_position];
+    }
+
+    ///replaces the stack with the addresses from a save state, the most recently pushed one is last.
+    ///If there are more addresses than fit on the stack, then an Err() is returned and the stack is left as it was.
+    pub fn restore(&mut self, contents: &[u16]) -> Result<(), Fault> {
+	if contents.len() > self.stack_array.len() {
+	    return Err(Fault::StackOverflow);
+	}
+	self.stack_array[0..contents.len()].copy_from_slice(contents);
+	self.stack_position = contents.len();
+	return Ok(());
+    }
 }
 
 ///This implements the registers for the chip 8.
 ///It includes the index register (I) and the 16 one-byte variable registers (V0 - VF).
+///The SUPER-CHIP flag registers are here too, programs can save variable registers into them with FX75 and read them back with FX85.
 pub struct RegisterSet {
     pub index_register: u16,
-    pub variable_register: [u8; 16]
+    pub variable_register: [u8; 16],
+    pub flag_register: [u8; 16]
 }
 
 impl RegisterSet {
@@ -119,7 +339,8 @@ impl RegisterSet {
     pub fn new() -> Self {
 	return RegisterSet {
 	    index_register: 0,
-	    variable_register: [0u8; 16]
+	    variable_register: [0u8; 16],
+	    flag_register: [0u8; 16]
 	}
     }
 }
@@ -4,90 +4,557 @@
 mod memory;
 mod video;
 mod timers;
+mod audio;
+mod errors;
 mod instruction_decoders;
 mod keyboard;
+mod keymap;
+mod config;
+mod palette;
+mod quirks;
+mod checksum;
+mod save_state;
+mod rewind;
+mod random;
+mod image;
+mod recorder;
+mod wav;
 pub mod drivers;
+pub mod debugger;
+pub mod disassembler;
+pub mod assembler;
 
-pub use video::VideoDriver;
+pub use video::{VideoDriver, Frame};
 pub use timers::SoundDriver;
-pub use keyboard::KeyboardDriver;
+pub use audio::SampleStream;
+pub use keyboard::{KeyboardDriver, KeyEvent, next_mask_event};
+pub use quirks::{Quirks, IndexIncrement};
+pub use keymap::Keymap;
+pub use palette::Palette;
+pub use errors::{Chip8Error, Fault};
+pub use instruction_decoders::ChipSystem;
+pub use memory::{Access, Watchpoint};
+pub use save_state::SAVE_STATE_VERSION;
+pub use random::{RandomSource, XorShiftRandom};
+pub use image::{Image, ImageFormat};
+pub use recorder::{Recorder, RecordingFormat};
+pub use wav::WavWriter;
 
 use std::fs::File;
 use std::io::{BufReader, Read};
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use std::thread;
 
-const TIMING_LCM: u64 = 700 * 60; //instructions need to be decoded at 700hz, the timers need to decrement at 60hz. (not actually the lcm but makes math easier)
-const MICROSECONDS_PER_TICK: u64 = 1000000 / TIMING_LCM; //every second can be divided into 1/TIMING_LCM pieces.
-const MICROSECONDS_PER_TIMER_DECREMENT: u64 = 1000000 / 60; //the timers should decrement every 1/60 of a second.
-const MICROSECONDS_PER_INSTRUCTION_DECODE: u64 = 1000000 / 700; //an instruction should be decoded every 1/700 of a second.
+///the instructions run every frame unless set_instructions_per_frame() is used, 12 frames at 60 frames a second is about 700 instructions a second.
+pub const DEFAULT_INSTRUCTIONS_PER_FRAME: u32 = 12;
+///the timers tick, and the screen is shown, 60 times a second.
+const FRAMES_PER_SECOND: f64 = 60.0;
+///if the runner falls further behind than this (the computer was busy or asleep), it gives up on catching up instead of rushing through frames.
+const MAX_FRAME_LAG: Duration = Duration::from_millis(100);
 
 ///this is a chip 8 runner that runs the chip8 instructions at the desired speed, so that the programs dont run too fast or slow.
 ///it initializes the system with a program File that you supply.
+///
+///The chip 8 runs in frames, 60 of them a second. Every frame, exactly instructions_per_frame instructions are run and then the timers tick once,
+/// so what a program does only depends on its input and never on how fast the computer is. The timed methods then wait for each frame's
+/// turn on a monotonic clock, and every deadline follows on from the one before it, so small delays in sleeping dont add up over time.
 pub struct TimedRunner <'a> {
-    system: instruction_decoders::ChipSystem<'a>,
-    time_since_timer_decrement: u64,
-    time_since_last_decode: u64
+    system: ChipSystem<'a>,
+    instructions_per_frame: u32,
+    instructions_in_frame: u32, //how many of this frame's instructions have already been run.
+    next_frame_deadline: Option<Instant>,
+    instructions_executed: u64,
+    frames_elapsed: u64,
+    rewind_buffer: Option<rewind::RewindBuffer> //a snapshot is taken every frame while rewinding is turned on.
 }
 
 impl <'a> TimedRunner <'a> {
     ///returns a new TimedRunner which runs the chip8.
-    pub fn new<T, U, V>(video_driver: T, sound_driver: U, keyboard_driver: V) -> Self where
+    ///the quirks pick how the ambiguous instructions behave, Quirks::cosmac_vip() is the original chip 8 behavior.
+    pub fn new<T, U, V>(video_driver: T, sound_driver: U, keyboard_driver: V, quirks: Quirks) -> Self where
 	T: VideoDriver + 'a,
 	U: SoundDriver + 'a,
 	V: KeyboardDriver + 'a {
 	
 	return TimedRunner {
-	    system: instruction_decoders::ChipSystem::new(video_driver, sound_driver, keyboard_driver),
-	    time_since_timer_decrement: 0,
-	    time_since_last_decode: 0
+	    system: ChipSystem::new(video_driver, sound_driver, keyboard_driver, quirks),
+	    instructions_per_frame: DEFAULT_INSTRUCTIONS_PER_FRAME,
+	    instructions_in_frame: 0,
+	    next_frame_deadline: None,
+	    instructions_executed: 0,
+	    frames_elapsed: 0,
+	    rewind_buffer: None
 	};
     }
 
-    ///this is a private method, it ensures that the chip8 timers and chip8 instruction cycles happen at roughly 60:700 speed.
-    fn tick_chip(&mut self) {
-	self.time_since_timer_decrement += MICROSECONDS_PER_TICK;
-	self.time_since_last_decode += MICROSECONDS_PER_TICK;
-	
-	if self.time_since_timer_decrement > MICROSECONDS_PER_TIMER_DECREMENT {
-	    instruction_decoders::tick_timers(&mut self.system);
-	    self.time_since_timer_decrement = 0;
-	}
-	if self.time_since_last_decode > MICROSECONDS_PER_INSTRUCTION_DECODE {
-	    match instruction_decoders::decode_next_instruction(&mut self.system) {
-		Ok(_) => {},
-		Err(error) => panic!("error decoding instruction: {}\n, debug info: {:?}", error, self.system)
-	    }
-	    self.time_since_last_decode = 0;
+    ///sets how many instructions are run every frame, which is how fast the chip 8 is. Different programs were made for different speeds,
+    /// usually somewhere between 7 and 30 instructions per frame, and some XO-CHIP programs want 1000 or more. At least 1 is always run.
+    pub fn set_instructions_per_frame(&mut self, instructions_per_frame: u32) {
+	self.instructions_per_frame = instructions_per_frame.max(1);
+    }
+
+    ///makes CXNN use an XorShiftRandom with the given seed, so the program gets the same random numbers every time it is run with it.
+    pub fn set_seed(&mut self, seed: u64) {
+	self.system.set_random_source(XorShiftRandom::new(seed));
+    }
+
+    ///returns how many instructions are run every frame.
+    pub fn instructions_per_frame(&self) -> u32 {
+	return self.instructions_per_frame;
+    }
+
+    ///this is a private method, it runs a single instruction, and ends the frame if that was the last instruction of it.
+    ///returns true if the frame ended.
+    fn step(&mut self) -> Result<bool, Chip8Error> {
+	let (result, frame_ended) = self.run_instruction();
+	if frame_ended {
+	    self.end_frame();
+	}
+	result?;
+	return Ok(frame_ended);
+    }
+
+    ///runs a single instruction and counts it. Returns the instruction's result, and true if it was the last instruction of the frame.
+    fn run_instruction(&mut self) -> (Result<(), Chip8Error>, bool) {
+	let result = instruction_decoders::decode_next_instruction(&mut self.system);
+	self.record_key_events();
+	if matches!(result, Err(Chip8Error::Execution { .. })) { //a watchpoint hit still ran its instruction, a failed one did not.
+	    return (result, false);
+	}
+	self.instructions_executed += 1;
+	self.instructions_in_frame += 1;
+	//while FX0A waits for a key, running it again and again for the rest of the frame would not change anything, so the frame ends early.
+	let frame_ended = self.instructions_in_frame >= self.instructions_per_frame || instruction_decoders::is_waiting_for_key(&self.system);
+	return (result, frame_ended);
+    }
+
+    ///runs a single instruction again, for step_back(). The frames it ends are not shown, the sound driver is not given their sound
+    /// and no snapshots are taken, since all of that already happened the first time the instructions ran.
+    fn replay_step(&mut self) -> Result<(), Chip8Error> {
+	let (result, frame_ended) = self.run_instruction();
+	if frame_ended {
+	    instruction_decoders::tick_timers_quietly(&mut self.system);
+	    self.instructions_in_frame = 0;
+	    self.frames_elapsed += 1;
+	}
+	return result;
+    }
+
+    ///ticks the timers, shows the frame and starts the next one. the rewind snapshot is taken here, so replaying from it goes exactly the same way.
+    fn end_frame(&mut self) {
+	instruction_decoders::tick_timers(&mut self.system);
+	instruction_decoders::present_frame(&mut self.system);
+	self.instructions_in_frame = 0;
+	self.frames_elapsed += 1;
+	self.record_snapshot();
+    }
+
+    ///waits until it is time for the next frame to start. The frame is 1/60 of a second times the speed_multiplier, so 2.0 runs at half speed
+    /// and 0.0 does not wait at all.
+    fn wait_for_next_frame(&mut self, speed_multiplier: f64) {
+	if speed_multiplier <= 0.0 || !speed_multiplier.is_finite() {
+	    return;
+	}
+	let now = Instant::now();
+	let frame_length = Duration::from_secs_f64(speed_multiplier / FRAMES_PER_SECOND);
+	let deadline = self.next_frame_deadline.unwrap_or(now) + frame_length;
+	if deadline > now {
+	    thread::sleep(deadline - now);
+	    self.next_frame_deadline = Some(deadline);
+	} else if now - deadline > MAX_FRAME_LAG {
+	    self.next_frame_deadline = Some(now);
+	} else {
+	    self.next_frame_deadline = Some(deadline); //a little late, the next frames start sooner to make up for it.
 	}
     }
 
     ///if you want to debug a program, you can use this function to step the chip8 through the program instructions.
+    ///Exactly one instruction is decoded, and if it was the last one of the frame, the timers tick.
     ///This method is not desined to be put in a loop{} without some kind of thread::sleep(), otherwise the
     /// instructions will fly by much too fast.
-    pub fn decode_next_immediately(&mut self) {
-	while self.time_since_timer_decrement != 0 {
-	    self.tick_chip();
+    ///If an instruction fails, the error is returned and the chip 8 stays at the failed instruction.
+    pub fn decode_next_immediately(&mut self) -> Result<(), Chip8Error> {
+	self.step()?;
+	return Ok(());
+    }
+
+    ///this function is designed to run in a loop, just simply place it in the loop{} you want. It decodes one instruction, and at the end of
+    /// every frame it waits for the next frame's turn, so the chip 8 runs at the correct speed.
+    ///the speed_multiplier stretches the length of a frame: 1.0 is normal speed, 2.0 is half speed, and 0.0 runs as fast as possible.
+    ///If an instruction fails, the error is returned, see Chip8Error for what can go wrong.
+    pub fn decode_next_timed(&mut self, speed_multiplier: f64) -> Result<(), Chip8Error> {
+	if self.step()? {
+	    self.wait_for_next_frame(speed_multiplier);
+	}
+	return Ok(());
+    }
+
+    ///runs the rest of the current frame, then waits for the next frame's turn like decode_next_timed() does.
+    pub fn run_frame_timed(&mut self, speed_multiplier: f64) -> Result<(), Chip8Error> {
+	self.run_frame()?;
+	self.wait_for_next_frame(speed_multiplier);
+	return Ok(());
+    }
+
+    ///runs the chip 8 until the end of the current frame, as fast as it can without sleeping.
+    ///this is for running programs headless, like in tests, where nobody is watching and the speed does not matter.
+    pub fn run_frame(&mut self) -> Result<(), Chip8Error> {
+	while !self.step()? {}
+	return Ok(());
+    }
+
+    ///returns how many frames (timer ticks) have gone by since the runner was made.
+    pub fn frames_elapsed(&self) -> u64 {
+	return self.frames_elapsed;
+    }
+
+    ///gives you a look at the state of the chip 8 (the registers, stack, timers and memory), this is useful for debugging.
+    pub fn system(&self) -> &ChipSystem<'a> {
+	return &self.system;
+    }
+
+    ///gives you the state of the chip 8 to change, this is how watchpoints are added and removed.
+    pub fn system_mut(&mut self) -> &mut ChipSystem<'a> {
+	return &mut self.system;
+    }
+
+    ///tells you if the program has exited (with the SUPER-CHIP 00FD instruction). Once it has, no more instructions are decoded.
+    pub fn is_halted(&self) -> bool {
+	return instruction_decoders::is_halted(&self.system);
+    }
+
+    ///tells you if the program is waiting on FX0A for a key to be pressed and let go of. The timers and the frames keep going
+    /// while it waits, and the instruction is run again every frame until the key comes.
+    pub fn is_waiting_for_key(&self) -> bool {
+	return instruction_decoders::is_waiting_for_key(&self.system);
+    }
+
+    ///takes a snapshot of the whole chip 8 (registers, stack, memory, screen, timers and sound) and returns it as the bytes of a save state file.
+    ///the format is described in the save_state module.
+    pub fn save_state(&self) -> Vec<u8> {
+	let mut state = instruction_decoders::save_state(&self.system);
+	state.instructions_in_frame = self.instructions_in_frame;
+	return state.to_bytes();
+    }
+
+    ///carries on from a save state that was made with save_state(), the screen is redrawn straight away.
+    ///The quirks are taken from the save state too, since the program was running with them.
+    ///The rewind buffer starts over from the loaded state, since its states lead up to where the chip 8 was, not to the loaded state.
+    ///An InvalidSaveState error is returned if the bytes are not a usable save state, and then the chip 8 is left as it was.
+    pub fn load_state(&mut self, state: &[u8]) -> Result<(), Chip8Error> {
+	self.restore_state(state)?;
+	self.restart_rewind();
+	return Ok(());
+    }
+
+    ///loads a save state, without touching the rewind buffer.
+    fn restore_state(&mut self, state: &[u8]) -> Result<(), Chip8Error> {
+	let state = save_state::SaveState::from_bytes(state)?;
+	instruction_decoders::load_state(&mut self.system, &state)?;
+	self.instructions_in_frame = state.instructions_in_frame.min(self.instructions_per_frame - 1);
+	return Ok(());
+    }
+
+    ///gives the key events the last instruction got to the rewind buffer, so that step_back() can give them to it again when it is replayed.
+    fn record_key_events(&mut self) {
+	let events = instruction_decoders::take_key_events(&mut self.system);
+	if let Some(buffer) = self.rewind_buffer.as_mut() {
+	    buffer.push_key_events(self.instructions_executed, events);
+	}
+    }
+
+    ///adds a snapshot to the rewind buffer if rewinding is turned on.
+    fn record_snapshot(&mut self) {
+	if self.rewind_buffer.is_none() {
+	    return;
+	}
+	let state = self.save_state();
+	let info = rewind::SnapshotInfo {
+	    instructions_executed: self.instructions_executed,
+	    frames_elapsed: self.frames_elapsed,
+	    keys: instruction_decoders::key_state(&self.system)
+	};
+	if let Some(buffer) = self.rewind_buffer.as_mut() {
+	    buffer.push(info, state);
+	}
+    }
+
+    ///empties the rewind buffer and takes a snapshot of the current state as the first one.
+    fn restart_rewind(&mut self) {
+	if let Some(buffer) = self.rewind_buffer.as_mut() {
+	    buffer.clear();
 	}
+	self.record_snapshot();
+    }
+
+    ///starts keeping a snapshot of the chip 8 every frame (60 times a second), so that it can be rewound up to frames frames back.
+    ///older snapshots are dropped once there are that many, so the memory used stays bounded. Giving 0 frames turns rewinding off.
+    ///The first snapshot is taken straight away, so the chip 8 can always be rewound back to where it was when this was called.
+    pub fn enable_rewind(&mut self, frames: usize) {
+	self.rewind_buffer = if frames > 0 {Some(rewind::RewindBuffer::new(frames))} else {None};
+	self.record_snapshot();
     }
 
-    ///this function is designed to run in a loop, just simply place it in the loop{} you want, and it has an integrated thread::sleep() to
-    /// time the chip 8 instruction cycles to be at the correct speed.
-    pub fn decode_next_timed(&mut self, speed_multiplier: f64) {
-	self.tick_chip();
-	thread::sleep(Duration::from_micros((MICROSECONDS_PER_TICK as f64 * speed_multiplier) as u64));
+    ///goes back the given number of frames, using the snapshots taken while rewinding was turned on.
+    ///If there are not that many snapshots it goes back as far as it can. The snapshot it goes back to is kept, so rewinding can carry on
+    /// from there, and returns the number of frames it actually went back. frames_elapsed() goes back with it.
+    ///The keys that are held down are kept, since they are being held down now and not when the snapshot was taken.
+    pub fn rewind(&mut self, frames: usize) -> Result<usize, Chip8Error> {
+	let Some(buffer) = self.rewind_buffer.as_mut() else {
+	    return Ok(0);
+	};
+	let mut rewound = 0;
+	while rewound < frames && buffer.len() > 1 {
+	    buffer.pop();
+	    rewound += 1;
+	}
+	if let Some((info, state)) = buffer.newest() {
+	    let state = state.to_vec();
+	    buffer.forget_key_events_from(info.instructions_executed);
+	    self.restore_state(&state)?;
+	    self.instructions_executed = info.instructions_executed;
+	    self.frames_elapsed = info.frames_elapsed;
+	}
+	return Ok(rewound);
+    }
+
+    ///goes back to right before the last instruction that was decoded. This loads the newest snapshot from before it and then
+    /// replays the instructions from there, so rewinding has to be turned on with enable_rewind().
+    ///The replay gives the instructions the same key events they got the first time, so it takes the same path the program took,
+    /// and it does not show frames, make sound or take snapshots (so recordings are not written to twice).
+    ///Afterwards the keys are the ones held down now, since those are what the keyboard driver will carry on from.
+    ///Watchpoints are not stopped at during the replay. Returns false if there is no snapshot far enough back to go to.
+    pub fn step_back(&mut self) -> Result<bool, Chip8Error> {
+	let Some(buffer) = self.rewind_buffer.as_mut() else {
+	    return Ok(false);
+	};
+	let Some(target) = self.instructions_executed.checked_sub(1) else {
+	    return Ok(false);
+	};
+	while buffer.newest().is_some_and(|(info, _)| info.instructions_executed > target) {
+	    buffer.pop();
+	}
+	let Some((info, state)) = buffer.newest() else {
+	    return Ok(false);
+	};
+	let state = state.to_vec();
+	let mut key_events = buffer.key_events_between(info.instructions_executed, target).into_iter().peekable();
+	buffer.forget_key_events_from(target);
+	self.restore_state(&state)?;
+	self.instructions_executed = info.instructions_executed;
+	self.frames_elapsed = info.frames_elapsed;
+	instruction_decoders::start_replay(&mut self.system, info.keys);
+	let mut result = Ok(());
+	while result.is_ok() && self.instructions_executed < target {
+	    while let Some((_, event)) = key_events.next_if(|(instruction, _)| *instruction <= self.instructions_executed) {
+		instruction_decoders::replay_key_event(&mut self.system, event);
+	    }
+	    result = match self.replay_step() {
+		Ok(()) | Err(Chip8Error::WatchpointHit { .. }) => Ok(()),
+		Err(error) => Err(error)
+	    };
+	}
+	instruction_decoders::end_replay(&mut self.system);
+	result?;
+	return Ok(true);
+    }
+
+    ///returns how many instructions have been decoded since the program started.
+    pub fn instructions_executed(&self) -> u64 {
+	return self.instructions_executed;
     }
 
     ///initializes the Chip8 with a program from a file. Just provide it with a file, and it will split it into bytes and load it into the memory.
-    pub fn init(&mut self, file: File) {
+    ///An Err() is returned if the file cannot be read, or if the program is too big for the memory.
+    pub fn init(&mut self, file: File) -> Result<(), Chip8Error> {
 	let file_buffer = BufReader::new(file)
 	    .bytes()
-	    .map(|x| match x {
-		Ok(x) => x,
-		Err(error) => panic!("error with reading program file: {}", error) })
-	    .collect::<Vec<u8>>();
+	    .collect::<Result<Vec<u8>, std::io::Error>>()?;
 
-	instruction_decoders::load_program_from_vector(&mut self.system, file_buffer);
+	instruction_decoders::load_program_from_vector(&mut self.system, file_buffer)?;
+	self.restart_rewind();
+	return Ok(());
     }
 
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    ///counts the frames it is asked to show.
+    struct CountingDisplay(Rc<Cell<u32>>);
+
+    impl VideoDriver for CountingDisplay {
+	fn draw(&mut self, _frame: &Frame) {}
+
+	fn present(&mut self) {
+	    self.0.set(self.0.get() + 1);
+	}
+    }
+
+    ///counts the ticks of sound it is given.
+    struct CountingSound(Rc<Cell<u32>>);
+
+    impl SoundDriver for CountingSound {
+	fn update(&mut self, _stream: &mut SampleStream) {
+	    self.0.set(self.0.get() + 1);
+	}
+    }
+
+    ///holds down the keys in the shared mask.
+    struct HeldKeys {
+	held: Rc<Cell<u16>>,
+	reported: u16
+    }
+
+    impl KeyboardDriver for HeldKeys {
+	fn next_event(&mut self) -> Option<KeyEvent> {
+	    return next_mask_event(&mut self.reported, self.held.get());
+	}
+    }
+
+    ///what step_back() has to get right: the save state, the instruction count and the frame count.
+    type Moment = (Vec<u8>, u64, u64);
+
+    struct Setup {
+	runner: TimedRunner<'static>,
+	presents: Rc<Cell<u32>>,
+	sound_updates: Rc<Cell<u32>>,
+	keys: Rc<Cell<u16>>
+    }
+
+    ///assembles the program and loads it into a runner that keeps a minute of snapshots, with 7 instructions a frame
+    /// so that the frames end in between the key changes.
+    fn setup(source: &str) -> Setup {
+	let (presents, sound_updates, keys) = (Rc::new(Cell::new(0)), Rc::new(Cell::new(0)), Rc::new(Cell::new(0)));
+	let mut runner = TimedRunner::new(CountingDisplay(presents.clone()), CountingSound(sound_updates.clone()),
+	    HeldKeys { held: keys.clone(), reported: 0 }, Quirks::cosmac_vip());
+	runner.set_instructions_per_frame(7);
+	runner.set_seed(1234);
+	runner.enable_rewind(3600);
+	let program = assembler::assemble(source).expect("the test program assembles");
+	instruction_decoders::load_program_from_vector(&mut runner.system, program).expect("the test program fits");
+	runner.restart_rewind();
+	return Setup { runner, presents, sound_updates, keys };
+    }
+
+    fn moment(runner: &TimedRunner) -> Moment {
+	return (runner.save_state(), runner.instructions_executed(), runner.frames_elapsed());
+    }
+
+    ///runs the instructions one at a time, changing the held keys at the instructions in the script, and returns the moment before
+    /// the first instruction and after every one.
+    fn run(setup: &mut Setup, instructions: u64, script: &[(u64, u16)]) -> Vec<Moment> {
+	let mut history = vec![moment(&setup.runner)];
+	for instruction in 0..instructions {
+	    if let Some((_, keys)) = script.iter().find(|(at, _)| *at == instruction) {
+		setup.keys.set(*keys);
+	    }
+	    setup.runner.decode_next_immediately().expect("the test program runs");
+	    history.push(moment(&setup.runner));
+	}
+	return history;
+    }
+
+    ///the program counts the instructions that see key 5 held down into V2, and draws, beeps and uses random numbers so that all of it
+    /// has to come back right.
+    const KEY_COUNTER: &str = "
+	    LD V5, 5
+	    LD I, dot
+	loop:
+	    ADD V0, 1
+	    RND V1, 0x0F
+	    LD DT, V1
+	    LD ST, V1
+	    SKNP V5
+	    ADD V2, 1
+	    DRW V0, V2, 1
+	    JP loop
+	dot:
+	    DB 0x80";
+
+    #[test]
+    fn stepping_back_goes_through_the_same_states_in_reverse() {
+	let mut setup = setup(KEY_COUNTER);
+	let history = run(&mut setup, 300, &[(50, 1 << 5), (125, 0), (200, 1 << 5)]);
+	let (presents, sound_updates) = (setup.presents.get(), setup.sound_updates.get());
+	for expected in history.iter().rev().skip(1) {
+	    assert!(setup.runner.step_back().expect("the replay runs"));
+	    assert!(moment(&setup.runner) == *expected, "stepping back to instruction {} went somewhere else", expected.1);
+	}
+	assert!(!setup.runner.step_back().expect("the step back runs"), "there was nothing before the first instruction to go back to");
+	assert_eq!(setup.presents.get(), presents, "the replay showed frames again");
+	assert_eq!(setup.sound_updates.get(), sound_updates, "the replay made sound again");
+    }
+
+    #[test]
+    fn stepping_back_then_forward_gets_back_to_the_same_state() {
+	let mut setup = setup(KEY_COUNTER);
+	let history = run(&mut setup, 150, &[(50, 1 << 5), (100, 0)]);
+	for _ in 0..30 {
+	    setup.runner.step_back().expect("the replay runs");
+	}
+	for expected in &history[(history.len() - 30)..] {
+	    setup.runner.decode_next_immediately().expect("the test program runs");
+	    assert!(moment(&setup.runner) == *expected, "stepping forward to instruction {} went somewhere else", expected.1);
+	}
+    }
+
+    #[test]
+    fn stepping_back_replays_a_key_wait() {
+	let mut setup = setup("
+		LD V3, K
+		ADD V4, 1
+		LD V3, K
+		ADD V4, 1
+	    stop:
+		JP stop");
+	let history = run(&mut setup, 40, &[(5, 1 << 7), (12, 0), (20, 1 << 9), (21, 0)]);
+	assert_eq!(setup.runner.system().variable_registers()[4], 2, "both key waits finished");
+	for expected in history.iter().rev().skip(1) {
+	    setup.runner.step_back().expect("the replay runs");
+	    assert!(moment(&setup.runner) == *expected, "stepping back to instruction {} went somewhere else", expected.1);
+	}
+    }
+
+    ///runs a program with the quirks until it stops or fails, and returns the opcode it failed on.
+    fn failing_opcode(source: &str, quirks: Quirks) -> Option<u16> {
+	let mut runner = TimedRunner::new(drivers::NullDisplay::new(), drivers::NullSound::new(), drivers::ScriptedKeyboard::new(Vec::new()), quirks);
+	let program = assembler::assemble(source).expect("the test program assembles");
+	instruction_decoders::load_program_from_vector(&mut runner.system, program).expect("the test program fits");
+	for _ in 0..10 {
+	    match runner.run_frame() {
+		Ok(()) => {},
+		Err(Chip8Error::Execution { opcode, .. }) => return Some(opcode),
+		Err(error) => panic!("{}", error)
+	    }
+	}
+	return None;
+    }
+
+    #[test]
+    fn super_chip_instructions_need_the_super_chip_quirk() {
+	for source in ["HIGH", "LOW", "SCD 1", "SCR", "SCL", "EXIT", "LD HF, V0", "LD R, V7", "LD V7, R"] {
+	    let program = format!("{}\nstop:\nJP stop", source);
+	    assert!(failing_opcode(&program, Quirks::cosmac_vip()).is_some(), "{} ran without SUPER-CHIP", source);
+	    assert_eq!(failing_opcode(&program, Quirks::schip()), None, "{} failed with SUPER-CHIP", source);
+	}
+	//SUPER-CHIP only has 8 flag registers.
+	assert_eq!(failing_opcode("LD R, V8\nstop:\nJP stop", Quirks::schip()), Some(0xF875));
+	assert_eq!(failing_opcode("LD V8, R\nstop:\nJP stop", Quirks::schip()), Some(0xF885));
+	assert_eq!(failing_opcode("LD R, VF\nLD VF, R\nstop:\nJP stop", Quirks::xo_chip()), None);
+    }
+
+    #[test]
+    fn rewinding_goes_back_whole_frames() {
+	let mut setup = setup(KEY_COUNTER);
+	let history = run(&mut setup, 70, &[]);
+	assert_eq!(setup.runner.rewind(3).expect("the snapshot loads"), 3);
+	assert!(moment(&setup.runner) == history[70 - 21]);
+	assert_eq!(setup.runner.rewind(100).expect("the snapshot loads"), 7);
+	assert!(moment(&setup.runner) == history[0]);
+    }
+}
@@ -0,0 +1,123 @@
+//! This module holds the palettes, the colors the screen is drawn in.
+//! XO-CHIP programs can draw to each bitplane separately, and every combination of planes a pixel is lit in gets its own color:
+//!  color 0 is the background, 1 is a pixel lit only in the first plane, 2 only in the second, 3 in both, and so on up to 15 for all four.
+//! Programs that only use the first plane just need the background and color 1.
+//!
+//! There are built-in themes, and a theme can also be loaded from a small TOML file like this one:
+//!  # start from a built-in theme (classic if this is left out)
+//!  theme = "amber"
+//!  # the background, then color 1, 2, 3 and so on, up to 16 colors. any that are left out keep the theme's color.
+//!  colors = ["#000000", "#ffffff", "#ff0000", "#00ff00"]
+
+use std::path::Path;
+use crate::chip_8::config;
+
+///how many colors there are, one for every combination of the 4 planes.
+pub const COLOR_COUNT: usize = 16;
+
+///The color of every combination of planes, each is red, green and blue from 0 to 255.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Palette {
+    colors: [[u8; 3]; COLOR_COUNT]
+}
+
+impl Palette {
+    ///White on black, with grays for the other XO-CHIP planes.
+    pub fn monochrome() -> Self {
+	return Palette::from_colors(&[[0x00, 0x00, 0x00], [0xFF, 0xFF, 0xFF], [0xAA, 0xAA, 0xAA], [0x55, 0x55, 0x55]]);
+    }
+
+    ///Green phosphor, like an old monochrome monitor.
+    pub fn classic() -> Self {
+	return Palette::from_colors(&[[0x0B, 0x16, 0x0B], [0x33, 0xFF, 0x66], [0x1A, 0x80, 0x33], [0x99, 0xFF, 0xB3]]);
+    }
+
+    ///Amber phosphor, the other old monochrome monitor.
+    pub fn amber() -> Self {
+	return Palette::from_colors(&[[0x14, 0x0C, 0x00], [0xFF, 0xB0, 0x00], [0x80, 0x58, 0x00], [0xFF, 0xD7, 0x80]]);
+    }
+
+    ///The colors Octo starts out with.
+    pub fn octo() -> Self {
+	return Palette::from_colors(&[[0x99, 0x66, 0x00], [0xFF, 0xCC, 0x00], [0xFF, 0x66, 0x00], [0x66, 0x22, 0x00]]);
+    }
+
+    ///A greenish LCD screen, like Octo's LCD theme.
+    pub fn lcd() -> Self {
+	return Palette::from_colors(&[[0xF9, 0xFF, 0xB3], [0x3D, 0x80, 0x26], [0xAB, 0xCC, 0x47], [0x00, 0x13, 0x1A]]);
+    }
+
+    ///Returns the built-in theme with the given name ("mono", "classic", "amber", "octo" or "lcd"), or None if there is none by that name.
+    pub fn from_name(name: &str) -> Option<Self> {
+	return match name.to_ascii_lowercase().as_str() {
+	    "mono" | "monochrome" => Some(Palette::monochrome()),
+	    "classic" | "green" => Some(Palette::classic()),
+	    "amber" => Some(Palette::amber()),
+	    "octo" => Some(Palette::octo()),
+	    "lcd" => Some(Palette::lcd()),
+	    _ => None
+	};
+    }
+
+    ///returns a palette with the given colors, starting with the background. The colors after the last one given go round the
+    /// ones given again, skipping the background, so that every combination of planes can still be seen.
+    fn from_colors(colors: &[[u8; 3]]) -> Self {
+	let mut palette = [colors[0]; COLOR_COUNT];
+	for (index, color) in palette.iter_mut().enumerate().skip(1) {
+	    *color = if index < colors.len() {colors[index]} else {colors[1 + ((index - 1) % (colors.len() - 1))]};
+	}
+	return Palette { colors: palette };
+    }
+
+    ///returns the color of a combination of planes, see Frame::color().
+    pub fn color(&self, index: u8) -> [u8; 3] {
+	return self.colors[index as usize % COLOR_COUNT];
+    }
+
+    ///reads a theme file, see the module documentation for what it looks like.
+    ///Any problem with the file is returned as an Err() with a message for the user, that says which line it is on.
+    pub fn from_config(config: &str) -> Result<Self, String> {
+	let mut palette = Palette::classic();
+	for setting in config::parse(config)? {
+	    match (setting.table.as_deref(), setting.name.as_str()) {
+		(None, "theme") => {
+		    let theme = setting.string()?;
+		    palette = Palette::from_name(theme).ok_or_else(|| setting.error(&format!("unknown theme \"{}\", expected mono, classic, amber, octo or lcd", theme)))?;
+		},
+		(None, "colors") => {
+		    let colors = setting.list();
+		    if colors.len() > COLOR_COUNT {
+			return Err(setting.error(&format!("there are {} colors, but only {} can be used", colors.len(), COLOR_COUNT)));
+		    }
+		    for (index, color) in colors.iter().enumerate() {
+			palette.colors[index] = parse_color(color).ok_or_else(|| setting.error(&format!("\"{}\" is not a color, it should be like \"#ffcc00\"", color)))?;
+		    }
+		},
+		(None, name) => return Err(setting.error(&format!("unknown setting \"{}\"", name))),
+		(Some(table), _) => return Err(setting.error(&format!("unknown table [{}], a theme does not have any", table)))
+	    }
+	}
+	return Ok(palette);
+    }
+
+    ///reads a theme file from disk, the errors start with the file's path.
+    pub fn load(path: &Path) -> Result<Self, String> {
+	return config::load_file(path, Palette::from_config);
+    }
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+	return Palette::classic();
+    }
+}
+
+///reads a color written as #rrggbb in hex, the # can be left out.
+fn parse_color(color: &str) -> Option<[u8; 3]> {
+    let hex = color.strip_prefix('#').unwrap_or(color);
+    if hex.len() != 6 || !hex.is_ascii() {
+	return None;
+    }
+    let channel = |start: usize| u8::from_str_radix(&hex[start..start + 2], 16).ok();
+    return Some([channel(0)?, channel(2)?, channel(4)?]);
+}
@@ -0,0 +1,121 @@
+//! This module holds the quirks of the chip 8.
+//! Over the years the different chip 8 interpreters (the original COSMAC VIP one, CHIP-48 on the HP48 calculators, SUPER-CHIP, etc.)
+//!  ended up interpreting a few of the opcodes differently, and programs were written to rely on whichever interpreter they were made for.
+//! The Quirks struct lets you pick how each of these ambiguous opcodes should behave.
+
+///Contains a switch for every ambiguous behavior of the chip 8 instructions.
+///Every switch is independent, so you can start from one of the presets and then change only the behaviors that a program needs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Quirks {
+    ///8XY6 and 8XYE shift VY and store the result in VX. If false, VX is shifted in place and VY is ignored.
+    pub shift_uses_vy: bool,
+    ///how FX55 and FX65 change I after storing or loading V0 to VX.
+    pub load_store_index: IndexIncrement,
+    ///BNNN is read as BXNN, jumping to XNN plus the value of VX. If false, it jumps to NNN plus V0.
+    pub jump_uses_vx: bool,
+    ///8XY1, 8XY2 and 8XY3 reset VF to 0 after the logic operation.
+    pub logic_resets_vf: bool,
+    ///DXYN cuts off the parts of a sprite that go past the right or bottom edge of the screen. If false, they wrap around to the other side.
+    pub clip_sprites: bool,
+    ///turns on the SUPER-CHIP instructions: 00CN, 00FB to 00FF, FX30, FX75 and FX85, and DXY0 drawing a 16x16 sprite.
+    ///Without it those are unknown opcodes, and DXY0 draws nothing, like on the COSMAC VIP. XO-CHIP builds on SUPER-CHIP, so it needs this on too.
+    pub super_chip: bool,
+    ///turns on the XO-CHIP extensions: 64 kilobytes of memory, the four byte F000 NNNN instruction, and skips that jump over it whole.
+    ///The other XO-CHIP instructions (00DN, 5XY2, 5XY3, FN01, F002 and FX3A) are unknown opcodes without it.
+    pub xo_chip: bool
+}
+
+///How FX55 and FX65 change I. The interpreters dont agree on this one, so it has three choices instead of being a switch.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IndexIncrement {
+    ///I is left untouched, like SUPER-CHIP.
+    Unchanged,
+    ///I += X, so it is left pointing at the last register that was stored or loaded, like CHIP-48.
+    ByX,
+    ///I += X + 1, so it is left pointing at the address after the last register, like the COSMAC VIP.
+    ByXPlusOne
+}
+
+impl IndexIncrement {
+    ///returns how much I goes up by after storing or loading V0 to VX.
+    pub fn amount(&self, x: usize) -> u16 {
+	return match self {
+	    IndexIncrement::Unchanged => 0,
+	    IndexIncrement::ByX => x as u16,
+	    IndexIncrement::ByXPlusOne => x as u16 + 1
+	};
+    }
+}
+
+impl Quirks {
+    ///The behavior of the original chip 8 interpreter on the COSMAC VIP.
+    pub const fn cosmac_vip() -> Self {
+	return Quirks {
+	    shift_uses_vy: true,
+	    load_store_index: IndexIncrement::ByXPlusOne,
+	    jump_uses_vx: false,
+	    logic_resets_vf: true,
+	    clip_sprites: true,
+	    super_chip: false,
+	    xo_chip: false
+	};
+    }
+
+    ///The behavior of CHIP-48 on the HP48 calculators. FX55 and FX65 add X to I, one less than the COSMAC VIP did.
+    pub const fn chip48() -> Self {
+	return Quirks {
+	    shift_uses_vy: false,
+	    load_store_index: IndexIncrement::ByX,
+	    jump_uses_vx: true,
+	    logic_resets_vf: false,
+	    clip_sprites: true,
+	    super_chip: false,
+	    xo_chip: false
+	};
+    }
+
+    ///The behavior of SUPER-CHIP 1.1, which kept most of the CHIP-48 interpretations, but leaves I untouched in FX55 and FX65.
+    pub const fn schip() -> Self {
+	return Quirks {
+	    shift_uses_vy: false,
+	    load_store_index: IndexIncrement::Unchanged,
+	    jump_uses_vx: true,
+	    logic_resets_vf: false,
+	    clip_sprites: true,
+	    super_chip: true,
+	    xo_chip: false
+	};
+    }
+
+    ///The behavior of XO-CHIP as implemented by Octo, with the XO-CHIP extensions turned on.
+    pub const fn xo_chip() -> Self {
+	return Quirks {
+	    shift_uses_vy: true,
+	    load_store_index: IndexIncrement::ByXPlusOne,
+	    jump_uses_vx: false,
+	    logic_resets_vf: false,
+	    clip_sprites: false,
+	    super_chip: true,
+	    xo_chip: true
+	};
+    }
+
+    ///Returns the preset with the given name ("vip", "chip48", "schip" or "xochip"), or None if there is no preset by that name.
+    pub fn from_name(name: &str) -> Option<Self> {
+	return match name.to_ascii_lowercase().as_str() {
+	    "vip" | "cosmac_vip" | "chip8" => Some(Quirks::cosmac_vip()),
+	    "chip48" => Some(Quirks::chip48()),
+	    "schip" | "superchip" => Some(Quirks::schip()),
+	    "xochip" | "xo-chip" => Some(Quirks::xo_chip()),
+	    _ => None
+	};
+    }
+}
+
+impl Default for Quirks {
+    ///By default the chip 8 behaves like the original COSMAC VIP interpreter. This is not how the emulator behaved before it had quirks,
+    /// the README lists what changed.
+    fn default() -> Self {
+	return Quirks::cosmac_vip();
+    }
+}
@@ -0,0 +1,70 @@
+//! This module has the random number generator used by the CXNN instruction.
+//! The chip 8 gets its random numbers from a RandomSource, so that a run can be repeated exactly by giving it the same seed,
+//!  which tests, recorded inputs and rewinding all depend on.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+///Defines how a random number generator should work so that the chip 8 can use it.
+///Different generators can be plugged in with ChipSystem::set_random_source(), for example one that replays recorded numbers.
+pub trait RandomSource {
+    ///returns the next random byte.
+    fn next_byte(&mut self) -> u8;
+
+    ///returns the state of the generator, so it can be put in a save state. A generator that cant be saved returns nothing.
+    fn save(&self) -> Vec<u8> {
+	return Vec::new();
+    }
+
+    ///puts the generator back into a state that came from save(). A generator that cant be saved ignores this.
+    fn restore(&mut self, _state: &[u8]) {}
+}
+
+///The default random number generator, an xorshift64* generator. It is quick, has a period of 2^64 - 1 and its numbers are evenly spread,
+/// which is far more than the chip 8 needs.
+pub struct XorShiftRandom {
+    state: u64
+}
+
+impl XorShiftRandom {
+    ///returns a generator that always gives the same numbers for the same seed. Any seed works, including 0.
+    pub fn new(seed: u64) -> Self {
+	//the seed is mixed with splitmix64 so that seeds that are close together dont start out with similar numbers.
+	let mut mixed = seed.wrapping_add(0x9E3779B97F4A7C15);
+	mixed = (mixed ^ (mixed >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+	mixed = (mixed ^ (mixed >> 27)).wrapping_mul(0x94D049BB133111EB);
+	mixed ^= mixed >> 31;
+	return XorShiftRandom {
+	    state: if mixed == 0 {0x9E3779B97F4A7C15} else {mixed} //xorshift gets stuck on 0 forever.
+	};
+    }
+
+    ///returns a seed taken from the clock, for when the numbers should be different every time the emulator runs.
+    pub fn seed_from_clock() -> u64 {
+	return match SystemTime::now().duration_since(UNIX_EPOCH) {
+	    Ok(duration) => duration.as_nanos() as u64,
+	    Err(error) => error.duration().as_nanos() as u64
+	};
+    }
+}
+
+impl RandomSource for XorShiftRandom {
+    fn next_byte(&mut self) -> u8 {
+	self.state ^= self.state >> 12;
+	self.state ^= self.state << 25;
+	self.state ^= self.state >> 27;
+	return (self.state.wrapping_mul(0x2545F4914F6CDD1D) >> 56) as u8; //the highest bits are the most random ones.
+    }
+
+    fn save(&self) -> Vec<u8> {
+	return self.state.to_be_bytes().to_vec();
+    }
+
+    fn restore(&mut self, state: &[u8]) {
+	if let Ok(bytes) = <[u8; 8]>::try_from(state) {
+	    let state = u64::from_be_bytes(bytes);
+	    if state != 0 {
+		self.state = state;
+	    }
+	}
+    }
+}
@@ -0,0 +1,474 @@
+//! This module records what a program draws into a video file, as it runs. It can write two formats, without needing any other crates:
+//!  An animated GIF, which plays almost anywhere. Its pixels are compressed with the GIF's LZW, which is written out below.
+//!  A YUV4MPEG2 (.y4m) stream, raw video that ffmpeg and most other video tools read, to turn it into any other format later.
+//!
+//! The recording follows the chip 8's own clock, not the computer's. Every frame the chip 8 runs (60 a second of emulated time)
+//! is a sixtieth of a second of video, so a recording of a headless run, or of a run that was slowed down, still plays at the right speed.
+//! The video is always big enough for the 128 x 64 high resolution screen, and a low resolution screen is drawn at twice the size to fill it,
+//! since neither format can change its size partway through.
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::collections::HashMap;
+use crate::chip_8::image::Image;
+use crate::chip_8::palette::{Palette, COLOR_COUNT};
+use crate::chip_8::video::{VideoDriver, Frame, PLANE_COUNT};
+
+///the size of the video in chip 8 pixels, the size of the high resolution screen.
+const VIDEO_SIZE: (usize, usize) = (128, 64);
+///the frames per second of emulated time, which is the frame rate of the video.
+const FRAMES_PER_SECOND: u64 = 60;
+///GIF frame delays are in hundredths of a second. Most browsers play a delay under 2 as 10, so no frame is made shorter than this.
+const MIN_GIF_DELAY: u64 = 2;
+///a GIF image is 16 colors, so every pixel is a 4 bit color index, which is also the smallest code size the LZW starts with.
+const GIF_COLOR_BITS: u8 = 4;
+///the LZW codes never go over 12 bits, so there can be at most 4096 of them before the table has to be cleared.
+const MAX_LZW_CODES: u16 = 4096;
+
+///a copy of a frame: its width, height and planes.
+type FrameCopy = (usize, usize, [[u128; 64]; PLANE_COUNT]);
+
+///The formats a recording can be written in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RecordingFormat {
+    Gif,
+    Y4m
+}
+
+impl RecordingFormat {
+    ///picks the format from a file's extension, .y4m is a YUV4MPEG2 stream and anything else is a GIF.
+    pub fn from_path(path: &Path) -> Self {
+	return match path.extension().and_then(|extension| extension.to_str()) {
+	    Some(extension) if extension.eq_ignore_ascii_case("y4m") => RecordingFormat::Y4m,
+	    _ => RecordingFormat::Gif
+	};
+    }
+}
+
+///Implements VideoDriver by recording every frame into a video, and handing everything on to another driver so the program can still be seen.
+///A frame is added to the video every time present() is called, which the chip 8 does once at the end of every frame.
+///The video is finished when the recorder is dropped (along with the TimedRunner it was given to).
+///If writing the video fails, the problem is printed to stderr once, and the program carries on without being recorded.
+pub struct Recorder <D: VideoDriver, W: Write> {
+    driver: D,
+    output: Option<W>, //None once writing has failed.
+    format: RecordingFormat,
+    palette: Palette,
+    scale: usize,
+    frame: FrameCopy, //the last frame that was drawn.
+    frames_recorded: u64,
+    held: Option<(Vec<u8>, u64)>, //GIF only: the frame that has not been written yet, and the frame number it started on.
+    last_y4m: Option<(FrameCopy, Vec<u8>)> //Y4M only: the last frame and its YUV bytes, to reuse when nothing changed.
+}
+
+impl <D: VideoDriver> Recorder<D, BufWriter<File>> {
+    ///starts recording into a file, in the format that goes with the file's extension (see RecordingFormat::from_path()).
+    pub fn create(driver: D, path: &Path, palette: Palette, scale: usize) -> io::Result<Self> {
+	return Recorder::new(driver, BufWriter::new(File::create(path)?), RecordingFormat::from_path(path), palette, scale);
+    }
+}
+
+impl <D: VideoDriver, W: Write> Recorder<D, W> {
+    ///starts recording into the output, with every chip 8 pixel made scale pixels wide and tall (twice that for low resolution).
+    ///A scale of 0 is taken as 1. The header of the video is written straight away.
+    pub fn new(driver: D, mut output: W, format: RecordingFormat, palette: Palette, scale: usize) -> io::Result<Self> {
+	let scale = scale.max(1);
+	let (width, height) = (VIDEO_SIZE.0 * scale, VIDEO_SIZE.1 * scale);
+	match format {
+	    RecordingFormat::Gif => write_gif_header(&mut output, width, height, &palette)?,
+	    RecordingFormat::Y4m => writeln!(output, "YUV4MPEG2 W{} H{} F{}:1 Ip A1:1 C444", width, height, FRAMES_PER_SECOND)?
+	}
+	return Ok(Recorder {
+	    driver,
+	    output: Some(output),
+	    format,
+	    palette,
+	    scale,
+	    frame: (64, 32, [[0u128; 64]; PLANE_COUNT]),
+	    frames_recorded: 0,
+	    held: None,
+	    last_y4m: None
+	});
+    }
+
+    ///returns how many frames have been recorded, a sixtieth of a second each.
+    pub fn frames_recorded(&self) -> u64 {
+	return self.frames_recorded;
+    }
+
+    ///returns how much bigger than a chip 8 pixel each pixel of the video is, in the current resolution.
+    fn pixel_size(&self) -> usize {
+	return self.scale * (VIDEO_SIZE.0 / self.frame.0.max(1));
+    }
+
+    ///adds the last frame that was drawn to the video.
+    fn record_frame(&mut self) -> io::Result<()> {
+	let (width, height, planes) = &self.frame;
+	let frame = Frame { width: *width, height: *height, planes };
+	let pixel_size = self.pixel_size();
+	match self.format {
+	    RecordingFormat::Gif => {
+		let mut pixels = Vec::with_capacity(VIDEO_SIZE.0 * VIDEO_SIZE.1 * self.scale * self.scale);
+		for y in 0..(frame.height * pixel_size) {
+		    pixels.extend((0..(frame.width * pixel_size)).map(|x| frame.color(x / pixel_size, y / pixel_size)));
+		}
+		//a frame that looks the same as the one before only makes that one last longer, and a frame that would be too short
+		// to play right takes the place of the one before it, so the frames after it still start on time.
+		let start = match self.held.take() {
+		    Some((held, start)) if held == pixels => start,
+		    Some((_, start)) if gif_delay(start, self.frames_recorded) < MIN_GIF_DELAY => start,
+		    Some((held, start)) => {
+			self.write_gif_frame(&held, start, self.frames_recorded)?;
+			self.frames_recorded
+		    },
+		    None => self.frames_recorded
+		};
+		self.held = Some((pixels, start));
+	    },
+	    RecordingFormat::Y4m => {
+		if self.last_y4m.as_ref().map(|(last, _)| last) != Some(&self.frame) {
+		    let image = Image::from_frame(&frame, &self.palette, pixel_size);
+		    self.last_y4m = Some((self.frame, to_yuv444(&image)));
+		}
+		let (_, yuv) = self.last_y4m.as_ref().expect("the frame was just converted");
+		let output = self.output.as_mut().expect("only recording while there is an output");
+		output.write_all(b"FRAME\n")?;
+		output.write_all(yuv)?;
+	    }
+	}
+	self.frames_recorded += 1;
+	return Ok(());
+    }
+
+    ///writes a GIF frame that is shown from the start frame until the end frame. A GIF delay cant be longer than about 11 minutes,
+    /// so a frame that stays up longer than that is written more than once.
+    fn write_gif_frame(&mut self, pixels: &[u8], start: u64, end: u64) -> io::Result<()> {
+	let output = self.output.as_mut().expect("only recording while there is an output");
+	let (width, height) = (VIDEO_SIZE.0 * self.scale, VIDEO_SIZE.1 * self.scale);
+	let mut delay = gif_delay(start, end).max(MIN_GIF_DELAY); //only the last frame can be shorter, if the recording stops right after it.
+	let data = lzw_encode(pixels, GIF_COLOR_BITS);
+	loop {
+	    let part = delay.min(u16::MAX as u64);
+	    output.write_all(&[0x21, 0xF9, 0x04, 0x00])?; //a graphic control extension, with nothing transparent.
+	    output.write_all(&(part as u16).to_le_bytes())?;
+	    output.write_all(&[0x00, 0x00])?;
+	    output.write_all(&[0x2C, 0x00, 0x00, 0x00, 0x00])?; //the image descriptor, the image covers the whole video from the top left.
+	    output.write_all(&(width as u16).to_le_bytes())?;
+	    output.write_all(&(height as u16).to_le_bytes())?;
+	    output.write_all(&[0x00, GIF_COLOR_BITS])?; //it uses the global colors, then the LZW code size.
+	    for block in data.chunks(255) {
+		output.write_all(&[block.len() as u8])?;
+		output.write_all(block)?;
+	    }
+	    output.write_all(&[0x00])?;
+	    delay -= part;
+	    if delay == 0 {
+		return Ok(());
+	    }
+	}
+    }
+
+    ///writes out whatever has not been written yet and the end of the video.
+    fn finish(&mut self) -> io::Result<()> {
+	if let Some((held, start)) = self.held.take() {
+	    self.write_gif_frame(&held, start, self.frames_recorded)?;
+	}
+	let output = self.output.as_mut().expect("only recording while there is an output");
+	if self.format == RecordingFormat::Gif {
+	    output.write_all(&[0x3B])?; //the GIF trailer.
+	}
+	return output.flush();
+    }
+}
+
+impl <D: VideoDriver, W: Write> VideoDriver for Recorder<D, W> {
+    fn draw(&mut self, frame: &Frame) {
+	self.frame = (frame.width, frame.height, *frame.planes);
+	self.driver.draw(frame);
+    }
+
+    fn present(&mut self) {
+	if self.output.is_some() {
+	    if let Err(error) = self.record_frame() {
+		eprintln!("the recording stopped: {}", error);
+		self.output = None;
+	    }
+	}
+	self.driver.present();
+    }
+}
+
+impl <D: VideoDriver, W: Write> Drop for Recorder<D, W> {
+    fn drop(&mut self) {
+	if self.output.is_some() {
+	    if let Err(error) = self.finish() {
+		eprintln!("unable to finish the recording: {}", error);
+	    }
+	}
+    }
+}
+
+///returns how long a GIF frame that is shown from the start frame until the end frame should last, in hundredths of a second.
+///the frames are rounded to the nearest hundredth from the beginning of the video, so the rounding does not add up over a long recording.
+fn gif_delay(start: u64, end: u64) -> u64 {
+    let hundredths = |frame: u64| ((frame * 100) + (FRAMES_PER_SECOND / 2)) / FRAMES_PER_SECOND;
+    return hundredths(end) - hundredths(start);
+}
+
+///writes the start of a GIF: the header, the size of the video, the palette's colors and the extension that makes it loop forever.
+fn write_gif_header<W: Write>(output: &mut W, width: usize, height: usize, palette: &Palette) -> io::Result<()> {
+    output.write_all(b"GIF89a")?;
+    output.write_all(&(width as u16).to_le_bytes())?;
+    output.write_all(&(height as u16).to_le_bytes())?;
+    output.write_all(&[0xF0 | (GIF_COLOR_BITS - 1), 0x00, 0x00])?; //a global color table of 2^4 colors, the background is color 0.
+    for color in 0..COLOR_COUNT {
+	output.write_all(&palette.color(color as u8))?;
+    }
+    output.write_all(&[0x21, 0xFF, 0x0B])?;
+    output.write_all(b"NETSCAPE2.0")?;
+    output.write_all(&[0x03, 0x01, 0x00, 0x00, 0x00])?; //loop 0 times, which means forever.
+    return Ok(());
+}
+
+///compresses the color indices with the variable length LZW that GIF uses. The codes start at one bit more than the code size
+/// and grow as the table fills up, to at most 12 bits. When the table is full a clear code is sent and it starts over.
+fn lzw_encode(indices: &[u8], code_size: u8) -> Vec<u8> {
+    let clear_code = 1u16 << code_size;
+    let end_code = clear_code + 1;
+    let mut output = BitWriter::new();
+    let mut table: HashMap<(u16, u8), u16> = HashMap::new(); //the code for a string that is already in the table, followed by one more index.
+    let mut next_code = end_code + 1;
+    let mut code_bits = code_size + 1;
+    output.write(clear_code, code_bits);
+    let mut indices = indices.iter();
+    let Some(first) = indices.next() else {
+	output.write(end_code, code_bits);
+	return output.finish();
+    };
+    let mut current = *first as u16;
+    for index in indices {
+	if let Some(code) = table.get(&(current, *index)) {
+	    current = *code;
+	    continue;
+	}
+	output.write(current, code_bits);
+	if next_code == MAX_LZW_CODES {
+	    output.write(clear_code, code_bits);
+	    table.clear();
+	    next_code = end_code + 1;
+	    code_bits = code_size + 1;
+	} else {
+	    table.insert((current, *index), next_code);
+	    next_code += 1;
+	    if next_code > (1 << code_bits) && code_bits < 12 { //the decoder is a code behind, so it only needs the next bit after this.
+		code_bits += 1;
+	    }
+	}
+	current = *index as u16;
+    }
+    output.write(current, code_bits);
+    if next_code == (1 << code_bits) && code_bits < 12 { //the decoder still adds to its table after the last code, and can need the next bit for the end code.
+	code_bits += 1;
+    }
+    output.write(end_code, code_bits);
+    return output.finish();
+}
+
+///Packs codes of any number of bits into bytes, starting from the lowest bit, like GIF wants them.
+struct BitWriter {
+    bytes: Vec<u8>,
+    buffer: u32, //the bits that have not made a whole byte yet, in the lowest bits.
+    bits: u8
+}
+
+impl BitWriter {
+    fn new() -> Self {
+	return BitWriter { bytes: Vec::new(), buffer: 0, bits: 0 };
+    }
+
+    fn write(&mut self, code: u16, bits: u8) {
+	self.buffer |= (code as u32) << self.bits;
+	self.bits += bits;
+	while self.bits >= 8 {
+	    self.bytes.push(self.buffer as u8);
+	    self.buffer >>= 8;
+	    self.bits -= 8;
+	}
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+	if self.bits > 0 {
+	    self.bytes.push(self.buffer as u8);
+	}
+	return self.bytes;
+    }
+}
+
+///turns an image into the three planes of a Y4M frame with no chroma subsampling: every pixel's Y, then every U, then every V.
+///the colors are converted with the BT.601 studio swing formulas, which is what video tools expect from a Y4M file.
+fn to_yuv444(image: &Image) -> Vec<u8> {
+    let mut planes = vec![0u8; image.pixels.len() * 3];
+    let (y_plane, chroma) = planes.split_at_mut(image.pixels.len());
+    let (u_plane, v_plane) = chroma.split_at_mut(image.pixels.len());
+    for (index, [red, green, blue]) in image.pixels.iter().enumerate() {
+	let (red, green, blue) = (*red as i32, *green as i32, *blue as i32);
+	y_plane[index] = ((((66 * red) + (129 * green) + (25 * blue) + 128) >> 8) + 16) as u8;
+	u_plane[index] = ((((-38 * red) - (74 * green) + (112 * blue) + 128) >> 8) + 128) as u8;
+	v_plane[index] = ((((112 * red) - (94 * green) - (18 * blue) + 128) >> 8) + 128) as u8;
+    }
+    return planes;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chip_8::drivers::NullDisplay;
+
+    ///a GIF LZW decoder, written the usual way from the GIF spec, to check the encoder against.
+    ///returns the indices, along with the widest code it read and how many clear codes there were.
+    fn lzw_decode(data: &[u8], code_size: u8) -> (Vec<u8>, u8, usize) {
+	let clear_code = 1usize << code_size;
+	let end_code = clear_code + 1;
+	let reset = || (0..(end_code + 1)).map(|code| vec![code as u8]).collect::<Vec<Vec<u8>>>();
+	let mut table = reset();
+	let mut code_bits = code_size + 1;
+	let (mut widest, mut clears) = (code_bits, 0);
+	let mut previous: Option<Vec<u8>> = None;
+	let mut indices = Vec::new();
+	let mut position = 0; //in bits
+	loop {
+	    assert!(position + code_bits as usize <= data.len() * 8, "the data ended without an end code");
+	    let code = (0..code_bits as usize).fold(0, |code, bit| code | ((((data[(position + bit) / 8] >> ((position + bit) % 8)) & 1) as usize) << bit));
+	    position += code_bits as usize;
+	    if code == clear_code {
+		table = reset();
+		code_bits = code_size + 1;
+		previous = None;
+		clears += 1;
+		continue;
+	    }
+	    if code == end_code {
+		break;
+	    }
+	    let entry = match &previous {
+		_ if code < table.len() => table[code].clone(),
+		Some(previous) if code == table.len() => [previous.as_slice(), &previous[..1]].concat(),
+		_ => panic!("code {} is not in the table yet, it only has {}", code, table.len())
+	    };
+	    indices.extend_from_slice(&entry);
+	    if let Some(previous) = previous {
+		if table.len() < MAX_LZW_CODES as usize {
+		    table.push([previous.as_slice(), &entry[..1]].concat());
+		}
+	    }
+	    if table.len() == (1 << code_bits) && code_bits < 12 {
+		code_bits += 1;
+		widest = widest.max(code_bits);
+	    }
+	    previous = Some(entry);
+	}
+	assert_eq!(position.div_ceil(8), data.len(), "there is data after the end code");
+	return (indices, widest, clears);
+    }
+
+    ///the same made up pixels every time, from a little xorshift.
+    fn noise(length: usize, colors: u8) -> Vec<u8> {
+	let mut state = 0x2545_F491u32;
+	return (0..length).map(|_| {
+	    state ^= state << 13;
+	    state ^= state >> 17;
+	    state ^= state << 5;
+	    return (state % colors as u32) as u8;
+	}).collect();
+    }
+
+    #[test]
+    fn lzw_round_trips_through_every_code_size() {
+	let mut inputs = vec![Vec::new(), vec![7], vec![0; 100_000], noise(100_000, 16), noise(100_000, 2)];
+	inputs.extend((0..700).map(|length| noise(length, 16))); //ends right around each of the code size changes.
+	let (mut widest, mut clears) = (0, 0);
+	for input in inputs.iter() {
+	    let (decoded, input_widest, input_clears) = lzw_decode(&lzw_encode(input, GIF_COLOR_BITS), GIF_COLOR_BITS);
+	    assert!(decoded == *input, "{} indices did not come back the same", input.len());
+	    widest = widest.max(input_widest);
+	    clears += input_clears;
+	}
+	assert_eq!(widest, 12, "the codes never grew to 12 bits");
+	assert!(clears > inputs.len(), "the table never filled up and started over");
+    }
+
+    fn sample_frame() -> FrameCopy {
+	let mut planes = [[0u128; 64]; PLANE_COUNT];
+	planes[0][0] = 1 << 127;
+	planes[0][31] = 0xF0F0 << 64;
+	planes[1][10] = !0;
+	return (64, 32, planes);
+    }
+
+    fn draw(recorder: &mut impl VideoDriver, frame: &FrameCopy) {
+	recorder.draw(&Frame { width: frame.0, height: frame.1, planes: &frame.2 });
+    }
+
+    #[test]
+    fn a_gif_has_a_header_frames_and_a_trailer() {
+	let mut file = Vec::new();
+	let mut recorder = Recorder::new(NullDisplay::new(), &mut file, RecordingFormat::Gif, Palette::monochrome(), 1).expect("writing to memory works");
+	let frame = sample_frame();
+	draw(&mut recorder, &frame);
+	for _ in 0..3 {
+	    recorder.present();
+	}
+	drop(recorder);
+
+	assert_eq!(&file[0..6], b"GIF89a");
+	assert_eq!(&file[6..13], &[128, 0, 64, 0, 0xF3, 0x00, 0x00]);
+	let colors = &file[13..(13 + (3 * COLOR_COUNT))];
+	assert_eq!(&colors[0..6], &[0x00, 0x00, 0x00, 0xFF, 0xFF, 0xFF]);
+	let rest = &file[(13 + (3 * COLOR_COUNT))..];
+	assert_eq!(&rest[0..3], &[0x21, 0xFF, 0x0B]);
+	assert_eq!(&rest[3..14], b"NETSCAPE2.0");
+	assert_eq!(&rest[14..19], &[0x03, 0x01, 0x00, 0x00, 0x00]);
+
+	let image = &rest[19..];
+	assert_eq!(&image[0..8], &[0x21, 0xF9, 0x04, 0x00, 5, 0, 0x00, 0x00]); //3 frames is 5 hundredths of a second.
+	assert_eq!(&image[8..18], &[0x2C, 0, 0, 0, 0, 128, 0, 64, 0, 0x00]);
+	assert_eq!(image[18], GIF_COLOR_BITS);
+	let mut data = Vec::new();
+	let mut position = 19;
+	while image[position] != 0 {
+	    let length = image[position] as usize;
+	    data.extend_from_slice(&image[(position + 1)..(position + 1 + length)]);
+	    position += 1 + length;
+	}
+	assert_eq!(&image[(position + 1)..], &[0x3B], "the frame should be followed by the trailer and nothing else");
+
+	let (pixels, _, _) = lzw_decode(&data, GIF_COLOR_BITS);
+	let (width, height, planes) = &frame;
+	let frame = Frame { width: *width, height: *height, planes };
+	let expected = (0..(64 * 128)).map(|pixel| frame.color((pixel % 128) / 2, (pixel / 128) / 2)).collect::<Vec<u8>>();
+	assert!(pixels == expected, "the low resolution frame should be drawn at twice the size");
+    }
+
+    #[test]
+    fn a_y4m_has_a_header_and_a_frame_every_sixtieth_of_a_second() {
+	let mut file = Vec::new();
+	let mut recorder = Recorder::new(NullDisplay::new(), &mut file, RecordingFormat::Y4m, Palette::monochrome(), 2).expect("writing to memory works");
+	draw(&mut recorder, &sample_frame());
+	recorder.present();
+	recorder.present();
+	assert_eq!(recorder.frames_recorded(), 2);
+	drop(recorder);
+
+	let header = b"YUV4MPEG2 W256 H128 F60:1 Ip A1:1 C444\n";
+	assert_eq!(&file[..header.len()], header);
+	let frame_size = b"FRAME\n".len() + (256 * 128 * 3);
+	assert_eq!(file.len(), header.len() + (2 * frame_size));
+	for frame in file[header.len()..].chunks(frame_size) {
+	    assert_eq!(&frame[..6], b"FRAME\n");
+	    let y_plane = &frame[6..(6 + (256 * 128))];
+	    assert_eq!(y_plane[0], 235, "the top left pixel is lit, which is white");
+	    assert_eq!(y_plane[4], 16, "the pixel after it is not, which is black");
+	}
+    }
+}
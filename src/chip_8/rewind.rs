@@ -0,0 +1,218 @@
+//! This module has the rewind buffer, which keeps the save states of the last few seconds so the chip 8 can go back in time.
+//! A save state is about 12 kilobytes but very little of it changes from one frame to the next, so only the newest state is kept whole,
+//!  and every older state is kept as the differences between it and the state after it.
+
+use std::collections::VecDeque;
+use crate::chip_8::keyboard::{KeyState, KeyEvent};
+
+///the equal bytes between two changes that are still put in the same run, since every run has some overhead of its own.
+const RUN_MERGE_GAP: usize = 8;
+
+///How to turn one state into another: the length of the other state, and the runs of bytes that are different, with where they start.
+struct Delta {
+    length: usize,
+    runs: Vec<(usize, Vec<u8>)>
+}
+
+impl Delta {
+    ///works out the delta that turns from into to.
+    fn between(from: &[u8], to: &[u8]) -> Self {
+	let mut runs: Vec<(usize, Vec<u8>)> = Vec::new();
+	for (position, byte) in to.iter().enumerate() {
+	    if from.get(position) == Some(byte) {
+		continue;
+	    }
+	    match runs.last_mut() {
+		Some((start, bytes)) if position - (*start + bytes.len()) <= RUN_MERGE_GAP => {
+		    let run_end = *start + bytes.len();
+		    bytes.extend_from_slice(&to[run_end..=position]);
+		},
+		_ => runs.push((position, vec![*byte]))
+	    }
+	}
+	return Delta { length: to.len(), runs };
+    }
+
+    ///turns the state the delta was made from into the state it leads to.
+    fn apply(&self, from: &[u8]) -> Vec<u8> {
+	let mut state = from.to_vec();
+	state.resize(self.length, 0);
+	for (start, bytes) in self.runs.iter() {
+	    state[*start..(*start + bytes.len())].copy_from_slice(bytes);
+	}
+	return state;
+    }
+}
+
+///Where the runner was when a state was taken. None of this is in the save state, so it is kept next to it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SnapshotInfo {
+    pub instructions_executed: u64,
+    pub frames_elapsed: u64,
+    pub keys: KeyState
+}
+
+///A ring buffer of save states, each one tagged with a SnapshotInfo of when it was taken.
+///Once it holds capacity states, the oldest one is dropped every time a new one is pushed, so the memory it uses stays bounded.
+pub struct RewindBuffer {
+    capacity: usize,
+    newest: Option<(SnapshotInfo, Vec<u8>)>,
+    older: VecDeque<(SnapshotInfo, Delta)>, //the back is the state right before the newest one, and each delta turns the state after it into this one.
+    key_events: VecDeque<(u64, KeyEvent)> //every key event since the oldest state, with the number of the instruction that got it.
+}
+
+impl RewindBuffer {
+    ///returns an empty RewindBuffer that keeps at most capacity states, it needs to hold at least one.
+    pub fn new(capacity: usize) -> Self {
+	return RewindBuffer {
+	    capacity: capacity.max(1),
+	    newest: None,
+	    older: VecDeque::new(),
+	    key_events: VecDeque::new()
+	};
+    }
+
+    ///adds a state as the newest one, dropping the oldest one if the buffer is full.
+    pub fn push(&mut self, info: SnapshotInfo, state: Vec<u8>) {
+	if let Some((previous_info, previous)) = self.newest.take() {
+	    self.older.push_back((previous_info, Delta::between(&state, &previous)));
+	}
+	self.newest = Some((info, state));
+	while self.len() > self.capacity {
+	    self.older.pop_front();
+	}
+	let oldest = self.older.front().map_or(info.instructions_executed, |(oldest, _)| oldest.instructions_executed);
+	while self.key_events.front().is_some_and(|(instruction, _)| *instruction < oldest) {
+	    self.key_events.pop_front();
+	}
+    }
+
+    ///keeps the key events that an instruction got, instruction is its number (how many instructions had run before it).
+    pub fn push_key_events(&mut self, instruction: u64, events: Vec<KeyEvent>) {
+	self.key_events.extend(events.into_iter().map(|event| (instruction, event)));
+    }
+
+    ///returns the key events that the instructions from start up to end (not including end) got, in order.
+    pub fn key_events_between(&self, start: u64, end: u64) -> Vec<(u64, KeyEvent)> {
+	return self.key_events.iter().filter(|(instruction, _)| *instruction >= start && *instruction < end).copied().collect();
+    }
+
+    ///drops the key events from the instruction on, for when the chip 8 goes back to before them and they will not happen again.
+    pub fn forget_key_events_from(&mut self, instruction: u64) {
+	while self.key_events.back().is_some_and(|(tagged, _)| *tagged >= instruction) {
+	    self.key_events.pop_back();
+	}
+    }
+
+    ///removes the newest state and hands it back, the one before it becomes the newest.
+    pub fn pop(&mut self) -> Option<(SnapshotInfo, Vec<u8>)> {
+	let newest = self.newest.take()?;
+	if let Some((info, delta)) = self.older.pop_back() {
+	    self.newest = Some((info, delta.apply(&newest.1)));
+	}
+	return Some(newest);
+    }
+
+    ///returns the newest state without removing it.
+    pub fn newest(&self) -> Option<(SnapshotInfo, &[u8])> {
+	return self.newest.as_ref().map(|(info, state)| (*info, state.as_slice()));
+    }
+
+    ///returns how many states are in the buffer.
+    pub fn len(&self) -> usize {
+	return self.older.len() + self.newest.is_some() as usize;
+    }
+
+    ///drops every state.
+    pub fn clear(&mut self) {
+	self.newest = None;
+	self.older.clear();
+	self.key_events.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn info(instructions_executed: u64) -> SnapshotInfo {
+	return SnapshotInfo { instructions_executed, frames_elapsed: instructions_executed / 10, keys: KeyState::default() };
+    }
+
+    #[test]
+    fn a_delta_turns_one_state_into_the_other() {
+	let state = (0..100u8).collect::<Vec<u8>>();
+	let mut changed_ends = state.clone();
+	changed_ends[0] = 0xFF;
+	changed_ends[99] = 0xFF;
+	let cases = [
+	    state.clone(),
+	    changed_ends,
+	    state[..60].to_vec(),
+	    [state.clone(), vec![1, 2, 3]].concat(),
+	    Vec::new(),
+	    vec![0xAA; 100]
+	];
+	for to in cases.iter() {
+	    assert_eq!(Delta::between(&state, to).apply(&state), *to);
+	    assert_eq!(Delta::between(to, &state).apply(to), state);
+	}
+    }
+
+    #[test]
+    fn a_delta_only_keeps_the_changed_bytes() {
+	let from = vec![0u8; 100];
+	assert!(Delta::between(&from, &from).runs.is_empty());
+
+	let mut to = from.clone();
+	to[10] = 1;
+	to[10 + RUN_MERGE_GAP + 1] = 1; //close enough to share a run, with the equal bytes between them.
+	to[50] = 1; //far enough away for a run of its own.
+	let delta = Delta::between(&from, &to);
+	assert_eq!(delta.runs.len(), 2);
+	assert_eq!(delta.runs[0].0, 10);
+	assert_eq!(delta.runs[0].1.len(), RUN_MERGE_GAP + 2);
+	assert_eq!(delta.runs[1], (50, vec![1]));
+    }
+
+    #[test]
+    fn popping_gives_the_states_back_newest_first() {
+	let mut buffer = RewindBuffer::new(3);
+	for count in 0..5u8 {
+	    let mut state = vec![0u8; 64];
+	    state[count as usize * 3] = count + 1;
+	    buffer.push(info(count as u64 * 10), state);
+	}
+	assert_eq!(buffer.len(), 3);
+	for count in (2..5u8).rev() {
+	    assert_eq!(buffer.newest().map(|(info, _)| info), Some(info(count as u64 * 10)));
+	    let (popped_info, state) = buffer.pop().expect("there are states left");
+	    assert_eq!(popped_info, info(count as u64 * 10));
+	    assert_eq!(state[count as usize * 3], count + 1);
+	    assert_eq!(state.iter().filter(|byte| **byte != 0).count(), 1);
+	}
+	assert!(buffer.pop().is_none());
+	assert_eq!(buffer.len(), 0);
+    }
+
+    #[test]
+    fn key_events_are_kept_as_long_as_their_states() {
+	let mut buffer = RewindBuffer::new(2);
+	buffer.push(info(0), vec![0]);
+	buffer.push_key_events(3, vec![KeyEvent::Pressed(1)]);
+	buffer.push(info(10), vec![1]);
+	buffer.push_key_events(12, vec![KeyEvent::Released(1), KeyEvent::Pressed(2)]);
+	buffer.push_key_events(15, vec![KeyEvent::Released(2)]);
+	assert_eq!(buffer.key_events_between(0, 20).len(), 4);
+	assert_eq!(buffer.key_events_between(10, 15), vec![(12, KeyEvent::Released(1)), (12, KeyEvent::Pressed(2))]);
+
+	buffer.forget_key_events_from(15);
+	assert_eq!(buffer.key_events_between(10, 20).len(), 2);
+
+	buffer.push(info(20), vec![2]); //the state at 0 is dropped, and so is the event before the state at 10.
+	assert_eq!(buffer.key_events_between(0, 20), vec![(12, KeyEvent::Released(1)), (12, KeyEvent::Pressed(2))]);
+
+	buffer.clear();
+	assert!(buffer.key_events_between(0, 20).is_empty());
+    }
+}
@@ -0,0 +1,383 @@
+//! This module has the save state format, a snapshot of everything the chip 8 needs to carry on running a program from where it was.
+//!
+//! A save state file is laid out like this, every number is big endian:
+//!  offset  size  contents
+//!  0       8     the magic bytes "CHIP8SAV"
+//!  8       2     the format version, currently 4
+//!  10      4     the length of the body in bytes
+//!  14      n     the body
+//!  14 + n  4     the CRC-32 of everything before it (the header and the body)
+//!
+//! The body of version 4 is, in order:
+//!  1 byte          the quirks, one bit each: shift_uses_vy (bit 0), FX55/FX65 do I += X + 1, jump_uses_vx, logic_resets_vf,
+//!                   clip_sprites, xo_chip (bit 5), FX55/FX65 do I += X (bit 6) and super_chip (bit 7).
+//!                   If neither bit 1 nor bit 6 is set, I is left untouched.
+//!  1 byte          1 if the program has exited with 00FD, otherwise 0
+//!  2 bytes         the program counter
+//!  2 bytes         the index register
+//!  16 bytes        V0 to VF
+//!  16 bytes        the SUPER-CHIP flag registers
+//!  1 byte          how many addresses are on the stack (up to 32), followed by that many 2 byte addresses, the oldest first
+//!  1 byte          the delay timer
+//!  1 byte          the sound timer
+//!  1 byte          1 if an XO-CHIP audio pattern has been loaded, otherwise 0
+//!  16 bytes        the audio pattern (all zero if there is none)
+//!  1 byte          the audio pitch
+//!  1 byte          1 if the screen is in high resolution, otherwise 0
+//!  1 byte          the mask of the planes that are selected for drawing
+//!  4 * 64 * 16     the 4 planes of the screen, each is 64 rows of 16 bytes, the leftmost pixel is the highest bit of the row
+//!  4 bytes         the size of the memory (4096, or 65536 with XO-CHIP), followed by the whole memory
+//!  4 bytes         how many instructions of the current frame have already been run
+//!  1 byte          the length of the random number generator's state, followed by the state (8 bytes for the default XorShiftRandom)
+//!
+//! Version 3 was the same, but the SUPER-CHIP instructions could not be turned off yet, so they are turned on when one is loaded.
+//! Version 2 was the same as version 3, but without the random number generator's state, so the generator is left as it is when one is loaded.
+//! Version 1 was the same as version 2, except that it ended with two 8 byte times in microseconds from before the chip 8 ran in frames.
+//!  Those are skipped when a version 1 state is loaded, and it starts at the beginning of a frame.
+
+use crate::chip_8::checksum;
+use crate::chip_8::errors::Chip8Error;
+use crate::chip_8::memory::{STANDARD_MEMORY_SIZE, EXTENDED_MEMORY_SIZE};
+use crate::chip_8::quirks::{Quirks, IndexIncrement};
+use crate::chip_8::video::PLANE_COUNT;
+
+const MAGIC: &[u8; 8] = b"CHIP8SAV";
+///the version of the format written by to_bytes(). Older versions can be read as long as from_bytes() still knows about them.
+pub const SAVE_STATE_VERSION: u16 = 4;
+const HEADER_SIZE: usize = 14;
+const CHECKSUM_SIZE: usize = 4;
+const STACK_SIZE: usize = 32;
+
+///Everything in a save state, see the module documentation for how it is stored.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SaveState {
+    pub quirks: Quirks,
+    pub halted: bool,
+    pub program_counter: u16,
+    pub index_register: u16,
+    pub variable_registers: [u8; 16],
+    pub flag_registers: [u8; 16],
+    pub stack: Vec<u16>,
+    pub delay_timer: u8,
+    pub sound_timer: u8,
+    pub audio_pattern: Option<[u8; 16]>,
+    pub pitch: u8,
+    pub high_resolution: bool,
+    pub selected_planes: u8,
+    pub planes: [[u128; 64]; PLANE_COUNT],
+    pub memory: Vec<u8>,
+    pub instructions_in_frame: u32,
+    pub random_state: Vec<u8> //empty if the generator cant be saved, or the state is from before version 3.
+}
+
+impl SaveState {
+    ///turns the save state into the bytes of a save state file.
+    pub fn to_bytes(&self) -> Vec<u8> {
+	let mut body = Vec::new();
+	body.push(quirk_bits(&self.quirks));
+	body.push(self.halted as u8);
+	body.extend(self.program_counter.to_be_bytes());
+	body.extend(self.index_register.to_be_bytes());
+	body.extend(self.variable_registers);
+	body.extend(self.flag_registers);
+	body.push(self.stack.len() as u8);
+	self.stack.iter().for_each(|address| body.extend(address.to_be_bytes()));
+	body.push(self.delay_timer);
+	body.push(self.sound_timer);
+	body.push(self.audio_pattern.is_some() as u8);
+	body.extend(self.audio_pattern.unwrap_or([0u8; 16]));
+	body.push(self.pitch);
+	body.push(self.high_resolution as u8);
+	body.push(self.selected_planes);
+	self.planes.iter().flatten().for_each(|row| body.extend(row.to_be_bytes()));
+	body.extend((self.memory.len() as u32).to_be_bytes());
+	body.extend(&self.memory);
+	body.extend(self.instructions_in_frame.to_be_bytes());
+	body.push(self.random_state.len() as u8);
+	body.extend(&self.random_state);
+
+	let mut bytes = Vec::with_capacity(HEADER_SIZE + body.len() + CHECKSUM_SIZE);
+	bytes.extend(MAGIC);
+	bytes.extend(SAVE_STATE_VERSION.to_be_bytes());
+	bytes.extend((body.len() as u32).to_be_bytes());
+	bytes.extend(body);
+	bytes.extend(checksum::crc32(&bytes).to_be_bytes());
+	return bytes;
+    }
+
+    ///reads the bytes of a save state file. An InvalidSaveState error is returned if the bytes are not a save state,
+    /// are from a newer version of the format, are damaged (the checksum does not match), or describe a chip 8 that cant exist.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Chip8Error> {
+	if bytes.len() < HEADER_SIZE + CHECKSUM_SIZE || &bytes[0..8] != MAGIC {
+	    return Err(invalid("this is not a save state"));
+	}
+	let version = u16::from_be_bytes([bytes[8], bytes[9]]);
+	if version == 0 || version > SAVE_STATE_VERSION {
+	    return Err(invalid(&format!("save state version {} is not supported, only versions up to {} are", version, SAVE_STATE_VERSION)));
+	}
+	let body_length = u32::from_be_bytes([bytes[10], bytes[11], bytes[12], bytes[13]]) as usize;
+	if bytes.len() != HEADER_SIZE + body_length + CHECKSUM_SIZE {
+	    return Err(invalid("the save state is cut off"));
+	}
+	let (contents, stored_checksum) = bytes.split_at(HEADER_SIZE + body_length);
+	if checksum::crc32(contents).to_be_bytes() != stored_checksum {
+	    return Err(invalid("the save state is damaged, its checksum does not match"));
+	}
+
+	let mut reader = Reader { bytes: &contents[HEADER_SIZE..], position: 0 };
+	let mut quirks = quirks_from_bits(reader.u8()?);
+	if version <= 3 {
+	    quirks.super_chip = true;
+	}
+	let halted = reader.u8()? != 0;
+	let program_counter = reader.u16()?;
+	let index_register = reader.u16()?;
+	let variable_registers = reader.array()?;
+	let flag_registers = reader.array()?;
+	let stack_depth = reader.u8()? as usize;
+	if stack_depth > STACK_SIZE {
+	    return Err(invalid("the stack in the save state is too deep"));
+	}
+	let stack = (0..stack_depth).map(|_| reader.u16()).collect::<Result<Vec<u16>, Chip8Error>>()?;
+	let delay_timer = reader.u8()?;
+	let sound_timer = reader.u8()?;
+	let has_pattern = reader.u8()? != 0;
+	let pattern = reader.array()?;
+	let pitch = reader.u8()?;
+	let high_resolution = reader.u8()? != 0;
+	let selected_planes = reader.u8()?;
+	let mut planes = [[0u128; 64]; PLANE_COUNT];
+	for row in planes.iter_mut().flatten() {
+	    *row = u128::from_be_bytes(reader.array()?);
+	}
+	let memory_size = u32::from_be_bytes(reader.array()?) as usize;
+	let expected_size = if quirks.xo_chip {EXTENDED_MEMORY_SIZE} else {STANDARD_MEMORY_SIZE};
+	if memory_size != expected_size {
+	    return Err(invalid(&format!("the memory in the save state is {} bytes, but it should be {}", memory_size, expected_size)));
+	}
+	let memory = reader.bytes(memory_size)?.to_vec();
+	let instructions_in_frame = match version {
+	    1 => {
+		reader.bytes(16)?; //the two old timing fields.
+		0
+	    },
+	    _ => u32::from_be_bytes(reader.array()?)
+	};
+	let random_state = match version {
+	    1 | 2 => Vec::new(),
+	    _ => {
+		let length = reader.u8()? as usize;
+		reader.bytes(length)?.to_vec()
+	    }
+	};
+	if reader.position != reader.bytes.len() {
+	    return Err(invalid("the save state has extra bytes at the end"));
+	}
+
+	return Ok(SaveState {
+	    quirks,
+	    halted,
+	    program_counter,
+	    index_register,
+	    variable_registers,
+	    flag_registers,
+	    stack,
+	    delay_timer,
+	    sound_timer,
+	    audio_pattern: if has_pattern {Some(pattern)} else {None},
+	    pitch,
+	    high_resolution,
+	    selected_planes,
+	    planes,
+	    memory,
+	    instructions_in_frame,
+	    random_state
+	});
+    }
+}
+
+fn invalid(reason: &str) -> Chip8Error {
+    return Chip8Error::InvalidSaveState { reason: reason.to_string() };
+}
+
+fn quirk_bits(quirks: &Quirks) -> u8 {
+    return (quirks.shift_uses_vy as u8)
+	| ((quirks.load_store_index == IndexIncrement::ByXPlusOne) as u8) << 1
+	| (quirks.jump_uses_vx as u8) << 2
+	| (quirks.logic_resets_vf as u8) << 3
+	| (quirks.clip_sprites as u8) << 4
+	| (quirks.xo_chip as u8) << 5
+	| ((quirks.load_store_index == IndexIncrement::ByX) as u8) << 6
+	| (quirks.super_chip as u8) << 7;
+}
+
+fn quirks_from_bits(bits: u8) -> Quirks {
+    return Quirks {
+	shift_uses_vy: bits & 0x01 != 0,
+	load_store_index: match (bits & 0x02 != 0, bits & 0x40 != 0) {
+	    (true, _) => IndexIncrement::ByXPlusOne,
+	    (false, true) => IndexIncrement::ByX,
+	    (false, false) => IndexIncrement::Unchanged
+	},
+	jump_uses_vx: bits & 0x04 != 0,
+	logic_resets_vf: bits & 0x08 != 0,
+	clip_sprites: bits & 0x10 != 0,
+	super_chip: bits & 0x80 != 0,
+	xo_chip: bits & 0x20 != 0
+    };
+}
+
+///reads the body of a save state one field at a time.
+struct Reader <'b> {
+    bytes: &'b [u8],
+    position: usize
+}
+
+impl <'b> Reader <'b> {
+    fn bytes(&mut self, length: usize) -> Result<&'b [u8], Chip8Error> {
+	let bytes = self.bytes.get(self.position..(self.position + length)).ok_or(invalid("the save state body is too short"))?;
+	self.position += length;
+	return Ok(bytes);
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], Chip8Error> {
+	let mut array = [0u8; N];
+	array.copy_from_slice(self.bytes(N)?);
+	return Ok(array);
+    }
+
+    fn u8(&mut self) -> Result<u8, Chip8Error> {
+	return Ok(self.bytes(1)?[0]);
+    }
+
+    fn u16(&mut self) -> Result<u16, Chip8Error> {
+	return Ok(u16::from_be_bytes(self.array()?));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    ///a state with something other than zero in every field, so a field that is written or read in the wrong place shows up.
+    fn sample_state() -> SaveState {
+	let mut planes = [[0u128; 64]; PLANE_COUNT];
+	planes[0][0] = 0xF000_0000_0000_0000_0000_0000_0000_000F;
+	planes[3][63] = 1;
+	return SaveState {
+	    quirks: Quirks::schip(),
+	    halted: false,
+	    program_counter: 0x2A4,
+	    index_register: 0x3FF,
+	    variable_registers: std::array::from_fn(|index| index as u8 * 3),
+	    flag_registers: std::array::from_fn(|index| 0xF0 | index as u8),
+	    stack: vec![0x202, 0x310],
+	    delay_timer: 12,
+	    sound_timer: 5,
+	    audio_pattern: Some([0xAA; 16]),
+	    pitch: 80,
+	    high_resolution: true,
+	    selected_planes: 0x3,
+	    planes,
+	    memory: (0..STANDARD_MEMORY_SIZE).map(|address| address as u8).collect(),
+	    instructions_in_frame: 7,
+	    random_state: vec![1, 2, 3, 4, 5, 6, 7, 8]
+	};
+    }
+
+    ///puts a header and a checksum around a body, the way to_bytes() does but with any version.
+    fn file(version: u16, body: &[u8]) -> Vec<u8> {
+	let mut bytes = MAGIC.to_vec();
+	bytes.extend(version.to_be_bytes());
+	bytes.extend((body.len() as u32).to_be_bytes());
+	bytes.extend(body);
+	bytes.extend(checksum::crc32(&bytes).to_be_bytes());
+	return bytes;
+    }
+
+    ///the body of a file, without its header and checksum.
+    fn body(bytes: &[u8]) -> Vec<u8> {
+	return bytes[HEADER_SIZE..(bytes.len() - CHECKSUM_SIZE)].to_vec();
+    }
+
+    fn assert_invalid(bytes: &[u8], reason: &str) {
+	match SaveState::from_bytes(bytes) {
+	    Err(Chip8Error::InvalidSaveState { reason: found }) => assert!(found.contains(reason), "expected \"{}\", got \"{}\"", reason, found),
+	    other => panic!("expected an InvalidSaveState error about \"{}\", got {:?}", reason, other)
+	}
+    }
+
+    #[test]
+    fn a_state_round_trips() {
+	let state = sample_state();
+	assert_eq!(SaveState::from_bytes(&state.to_bytes()).expect("the state should load"), state);
+    }
+
+    #[test]
+    fn every_quirks_preset_round_trips() {
+	for quirks in [Quirks::cosmac_vip(), Quirks::chip48(), Quirks::schip(), Quirks::xo_chip()] {
+	    assert_eq!(quirks_from_bits(quirk_bits(&quirks)), quirks);
+	}
+    }
+
+    #[test]
+    fn a_damaged_checksum_is_rejected() {
+	let mut bytes = sample_state().to_bytes();
+	let last = bytes.len() - 1;
+	bytes[last] ^= 0x01;
+	assert_invalid(&bytes, "checksum does not match");
+	let mut bytes = sample_state().to_bytes();
+	bytes[HEADER_SIZE + 4] ^= 0x80; //a byte of the body, so the stored checksum no longer matches it.
+	assert_invalid(&bytes, "checksum does not match");
+    }
+
+    #[test]
+    fn cut_off_and_padded_states_are_rejected() {
+	let bytes = sample_state().to_bytes();
+	assert_invalid(&bytes[..bytes.len() - 1], "cut off");
+	assert_invalid(&bytes[..HEADER_SIZE], "not a save state");
+	let mut padded = bytes.clone();
+	padded.push(0);
+	assert_invalid(&padded, "cut off");
+	let mut longer_body = body(&bytes);
+	longer_body.push(0);
+	assert_invalid(&file(SAVE_STATE_VERSION, &longer_body), "extra bytes");
+	let shorter_body = body(&bytes);
+	assert_invalid(&file(SAVE_STATE_VERSION, &shorter_body[..shorter_body.len() - 1]), "too short");
+    }
+
+    #[test]
+    fn unknown_versions_are_rejected() {
+	let body = body(&sample_state().to_bytes());
+	assert_invalid(&file(0, &body), "version 0 is not supported");
+	assert_invalid(&file(SAVE_STATE_VERSION + 1, &body), "is not supported");
+    }
+
+    #[test]
+    fn version_3_states_have_the_super_chip_instructions_on() {
+	let state = SaveState { quirks: Quirks::cosmac_vip(), ..sample_state() };
+	assert!(!SaveState::from_bytes(&state.to_bytes()).expect("the state should load").quirks.super_chip);
+	let loaded = SaveState::from_bytes(&file(3, &body(&state.to_bytes()))).expect("a version 3 state should load");
+	assert_eq!(loaded.quirks, Quirks { super_chip: true, ..Quirks::cosmac_vip() });
+    }
+
+    #[test]
+    fn version_2_states_load_without_the_random_state() {
+	let state = sample_state();
+	let mut body = body(&state.to_bytes());
+	body.truncate(body.len() - 1 - state.random_state.len()); //version 2 ends after instructions_in_frame.
+	let loaded = SaveState::from_bytes(&file(2, &body)).expect("a version 2 state should load");
+	assert_eq!(loaded, SaveState { random_state: Vec::new(), ..state });
+    }
+
+    #[test]
+    fn version_1_states_load_at_the_start_of_a_frame() {
+	let state = sample_state();
+	let mut body = body(&state.to_bytes());
+	body.truncate(body.len() - 1 - state.random_state.len() - 4); //version 1 ends after the memory, with the two old times.
+	body.extend(123_456u64.to_be_bytes());
+	body.extend(789_012u64.to_be_bytes());
+	let loaded = SaveState::from_bytes(&file(1, &body)).expect("a version 1 state should load");
+	assert_eq!(loaded, SaveState { instructions_in_frame: 0, random_state: Vec::new(), ..state });
+    }
+}
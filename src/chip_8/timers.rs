@@ -2,6 +2,8 @@
 //! This module is for the chip 8 timers.
 //! It contains implementations for the delay timer and sound timer.
 
+use crate::chip_8::audio::{SampleStream, DEFAULT_SAMPLE_RATE};
+
 ///This is a delay timer, which will tick down from whatever number it was set to until it reaches 0.
 pub struct DelayTimer {
     pub time_value: u8
@@ -25,13 +27,34 @@ impl DelayTimer {
 
 ///This defines how a sound driver should behave so that the chip 8 can use it.
 ///multiple sound drivers can be used so that the chip 8 can beep in different ways.
+///
+///Every time the sound timer ticks (60 times a second), the driver is given the SampleStream, and it can pull as many samples out of it
+/// as its audio sink needs, usually sample_rate / 60 of them. A driver that only cares about on and off can just check is_sounding().
 pub trait SoundDriver {
-    fn set_beep(&self, state: bool);
+    fn update(&mut self, stream: &mut SampleStream);
+
+    ///the sample rate the driver wants the samples to be generated at.
+    fn sample_rate(&self) -> u32 {
+	return DEFAULT_SAMPLE_RATE;
+    }
+}
+
+///passes the sound on to the boxed sound driver.
+impl <T: SoundDriver + ?Sized> SoundDriver for Box<T> {
+    fn update(&mut self, stream: &mut SampleStream) {
+	(**self).update(stream);
+    }
+
+    fn sample_rate(&self) -> u32 {
+	return (**self).sample_rate();
+    }
 }
 
 ///This is the sound timer, which will continuously beep as long as it is above 0. It ticks down until it reaches 0.
+///The sound itself is generated by the SampleStream it holds.
 pub struct SoundTimer <'a> {
     pub time_value: u8,
+    pub stream: SampleStream,
     driver: Box<dyn SoundDriver + 'a>
 }
 
@@ -41,6 +64,7 @@ impl <'a> SoundTimer <'a> {
     pub fn new<T: SoundDriver + 'a>(driver: T) -> Self {
 	return SoundTimer {
 	    time_value: 0,
+	    stream: SampleStream::new(driver.sample_rate()),
 	    driver: Box::new(driver)
 	};
     }
@@ -48,11 +72,17 @@ impl <'a> SoundTimer <'a> {
     ///decrements the timer once everytime it is called, unless the timer has already reached 0.
     ///This will also cause constant beeping to happen as long as the timer is above 0.
     pub fn tick_down(&mut self) {
+	self.tick_down_quietly();
+	self.driver.update(&mut self.stream);
+    }
+
+    ///ticks the timer like tick_down(), without giving the driver any sound. This is for replaying instructions that already made their sound.
+    pub fn tick_down_quietly(&mut self) {
 	if self.time_value > 0 {
 	    self.time_value -= 1;
-	    self.driver.set_beep(true);
+	    self.stream.set_sounding(true);
 	} else {
-	    self.driver.set_beep(false);
+	    self.stream.set_sounding(false);
 	}
     }
 }
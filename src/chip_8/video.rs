@@ -1,57 +1,266 @@
-
 //! This module deals with the display for the chip 8
 //! It contains the VideoDisplay structure and various other methods to draw onto the screen
 //! This module though does not contain the methods to actually show the display though, a VideoDriver must be implemented and
 //!  supplied to the VideoDisplay so that it can show you the display.
 //! Some drivers are already implemented in the chip8::drivers module for use by the display.
 
+use std::fmt;
+use crate::chip_8::checksum;
+
+///the width and height of the original chip 8 display.
+pub const LOW_RESOLUTION: (usize, usize) = (64, 32);
+///the width and height of the SUPER-CHIP high resolution display.
+pub const HIGH_RESOLUTION: (usize, usize) = (128, 64);
+///the number of bitplanes the display has. The original chip 8 and SUPER-CHIP only ever use the first one,
+/// XO-CHIP programs can draw to each of them separately.
+pub const PLANE_COUNT: usize = 4;
+
 ///Defines how a driver should work to draw the display of the chip 8.
 ///many drivers can be implemented, so the chip 8 can make use of many methods, gui or cli.
 ///
-///In this implementation of the chip 8 video, the display is stored as a an array of 32 u64 numbers, to represent
-/// the 64x32 screen. Therefore, each pixel just gets one bit.
-///a driver just needs to know how to interface with this pixel array in order to properly draw the screen.
+///The driver is handed a Frame every time the screen changes. The frame is either 64x32 (chip 8 and SUPER-CHIP low resolution)
+/// or 128x64 (SUPER-CHIP high resolution), and the driver should check the width and height of every frame it gets,
+/// since a program can switch between the two at any time.
 ///See the drivers.rs file to see a very simple implemtation of this trait.
 pub trait VideoDriver {
-    fn draw(&mut self, bitmap: &[u64; 32]);
+    fn draw(&mut self, frame: &Frame);
+
+    ///called at the end of every frame, 60 times a second. A program can draw many times in a frame, so a driver can just keep
+    /// what it was given in draw() and only put it on the screen here, once per frame.
+    fn present(&mut self) {}
+}
+
+///passes the frames on to the boxed video driver.
+impl <T: VideoDriver + ?Sized> VideoDriver for Box<T> {
+    fn draw(&mut self, frame: &Frame) {
+	(**self).draw(frame);
+    }
+
+    fn present(&mut self) {
+	(**self).present();
+    }
+}
+
+///A view into the display that is handed to the VideoDriver.
+///The display is made of bitplanes stacked on top of each other. Each row of a plane is a u128, the leftmost pixel of a row is the highest bit
+/// of the u128 and a 1 bit means the pixel is on in that plane. Only the first `width` bits of each row and the first `height` rows are part of the frame.
+///A monochrome driver can just use pixel(), a driver with a palette can use color() to get which combination of planes a pixel is lit in.
+pub struct Frame <'b> {
+    pub width: usize,
+    pub height: usize,
+    pub planes: &'b [[u128; 64]; PLANE_COUNT]
+}
+
+impl Frame <'_> {
+    ///tells you if the pixel at the given x and y position is on in any of the planes.
+    pub fn pixel(&self, x: usize, y: usize) -> bool {
+	return self.color(x, y) != 0;
+    }
+
+    ///returns the color index of the pixel at the given x and y position. Bit p of the index is set if the pixel is on in plane p,
+    /// so 0 is the background, 1 is a pixel only lit in the first plane, 2 is only in the second plane, 3 is in both, and so on.
+    pub fn color(&self, x: usize, y: usize) -> u8 {
+	return self.planes
+	    .iter()
+	    .enumerate()
+	    .fold(0u8, |color, (plane, rows)| color | ((((rows[y] >> (127 - x)) & 0x1) as u8) << plane));
+    }
+
+    ///returns a hash of what is on the screen: the resolution and the color of every pixel.
+    ///two frames with the same hash look the same, so this is an easy way for tests to check what a program drew.
+    pub fn hash(&self) -> u64 {
+	let mut contents = Vec::with_capacity(4 + (self.width * self.height));
+	contents.extend((self.width as u16).to_be_bytes());
+	contents.extend((self.height as u16).to_be_bytes());
+	for y in 0..self.height {
+	    contents.extend((0..self.width).map(|x| self.color(x, y)));
+	}
+	return checksum::fnv1a_64(&contents);
+    }
+}
+
+///Prints the frame as text, one line per row. A pixel that is off is a '.', and a pixel that is on is a '#',
+/// or with XO-CHIP colors, the hex digit of its color if it is lit in other planes.
+impl fmt::Display for Frame <'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+	for y in 0..self.height {
+	    let line = (0..self.width)
+		.map(|x| match self.color(x, y) {
+		    0 => '.',
+		    1 => '#',
+		    color => char::from_digit(color as u32, 16).unwrap_or('?')
+		})
+		.collect::<String>();
+	    writeln!(f, "{}", line)?;
+	}
+	return Ok(());
+    }
 }
 
-///This struct holds the video display. It uses 32 u64 integers to hold the 64 x 32 frame.
-///Like described in the VideoDriver description, the screen is stored as an array of integers, each integer is a u64 (it has 64 bits),
-/// and there are 32 of them in the array.
+///This struct holds the video display. Each of its bitplanes uses 64 u128 integers to hold the frame, big enough for the 128 x 64 high resolution mode.
+///In the 64 x 32 low resolution mode only the top left part of each plane is used: the first 32 rows, and the highest 64 bits of each row.
 ///You must provide a VideoDriver though in order to actually be able to see the display.
 pub struct VideoDisplay <'a> {
-    buffer: [u64; 32],
+    planes: [[u128; 64]; PLANE_COUNT],
+    selected_planes: u8,
+    high_resolution: bool,
     driver: Box<dyn VideoDriver + 'a>
 }
 
 impl <'a> VideoDisplay <'a> {
-    ///Returns a new VideoDisplay, which is set to completely blank. You need to supply the driver that the display will use. 
+    ///Returns a new VideoDisplay, which is set to completely blank and in low resolution, drawing only to the first plane.
+    ///You need to supply the driver that the display will use. 
     pub fn new<T: VideoDriver + 'a>(driver: T) -> Self {
 	return VideoDisplay {
-	    buffer: [0u64; 32],
+	    planes: [[0u128; 64]; PLANE_COUNT],
+	    selected_planes: 0x1,
+	    high_resolution: false,
 	    driver: Box::new(driver)
 	};
     }
 
-    ///clears the screen, making everything empty
+    ///returns the width of the display in the current resolution.
+    pub fn width(&self) -> usize {
+	return if self.high_resolution {HIGH_RESOLUTION.0} else {LOW_RESOLUTION.0};
+    }
+
+    ///returns the height of the display in the current resolution.
+    pub fn height(&self) -> usize {
+	return if self.high_resolution {HIGH_RESOLUTION.1} else {LOW_RESOLUTION.1};
+    }
+
+    ///switches between the 64 x 32 and 128 x 64 resolutions. Every plane of the screen is cleared when this happens.
+    pub fn set_high_resolution(&mut self, high_resolution: bool) {
+	self.high_resolution = high_resolution;
+	self.planes.iter_mut().for_each(|plane| plane.iter_mut().for_each(|row| *row = 0u128));
+    }
+
+    ///picks which planes drawing, clearing and scrolling work on. Bit p of the mask selects plane p.
+    pub fn select_planes(&mut self, mask: u8) {
+	self.selected_planes = mask & ((1 << PLANE_COUNT) - 1);
+    }
+
+    ///returns the mask of the planes that are currently selected, bit p is set if plane p is selected.
+    pub fn plane_mask(&self) -> u8 {
+	return self.selected_planes;
+    }
+
+    ///tells you if the screen is in the 128 x 64 resolution.
+    pub fn is_high_resolution(&self) -> bool {
+	return self.high_resolution;
+    }
+
+    ///returns what is currently on the screen, the same thing the driver is handed.
+    pub fn frame(&self) -> Frame<'_> {
+	return Frame {
+	    width: self.width(),
+	    height: self.height(),
+	    planes: &self.planes
+	};
+    }
+
+    ///returns the pixels of every plane, see Frame for how they are laid out.
+    pub fn planes(&self) -> &[[u128; 64]; PLANE_COUNT] {
+	return &self.planes;
+    }
+
+    ///puts back the screen from a save state, and shows it on the driver straight away.
+    pub fn restore(&mut self, planes: [[u128; 64]; PLANE_COUNT], selected_planes: u8, high_resolution: bool) {
+	self.planes = planes;
+	self.high_resolution = high_resolution;
+	self.select_planes(selected_planes);
+	self.update_screen();
+    }
+
+    ///returns the indexes of the planes that are currently selected, from lowest to highest.
+    pub fn selected_planes(&self) -> Vec<usize> {
+	return (0..PLANE_COUNT).filter(|plane| self.selected_planes & (1 << plane) != 0).collect();
+    }
+
+    ///clears the selected planes of the screen, making them empty
     ///this does not update the screen though, the update_screen() method also needs to be called to then show you your clear screen.
     pub fn clear_buffer(&mut self) {
-	self.buffer.iter_mut().for_each(|x| *x = 0u64);
+	for plane in self.selected_planes() {
+	    self.planes[plane].iter_mut().for_each(|x| *x = 0u128);
+	}
     }
 
-    ///Draws a sprite onto the screen at a given x and y coordinate.
+    ///Draws a sprite onto one plane of the screen at a given x and y coordinate.
     ///The sprite (get_line) is a closure which works similarly to rust's Iterator object. Every time the get_line closure is called, the next
-    ///byte of the sprite is returned (each byte is the binary representation of a row of pixels). Sprite bytes will be drawn until None are left.
-    pub fn draw_sprite<T: FnMut() -> Option<(u8, u8)>>(&mut self, inputx: u8, inputy: u8, mut get_line: T) {
+    ///row of the sprite is returned along with its y offset. A row is sprite_width bits wide (8 for normal sprites, 16 for the SUPER-CHIP 16x16 sprites),
+    ///and each bit is a pixel. Sprite rows will be drawn until None are left.
+    ///The x and y coordinates always wrap around the screen, but the parts of the sprite that go past the right or bottom edge are
+    /// either cut off (if clip is true) or wrapped around to the other side.
+    ///Returns true if any pixel that was on got turned off, this is how the chip 8 detects collisions.
+    pub fn draw_sprite<T: FnMut() -> Option<(u16, u8)>>(&mut self, plane: usize, inputx: u8, inputy: u8, sprite_width: u32, clip: bool, mut get_line: T) -> bool {
+	let width = self.width();
+	let height = self.height();
+	let row_mask = !0u128 << (128 - width);
+	let x = inputx as usize % width;
+	let y = inputy as usize % height;
+	let mut collision = false;
 	while let Some((sprite_line, y_offset)) = get_line() {
-	    self.buffer[(inputy.wrapping_add(y_offset) % 32) as usize] ^= ((sprite_line as u64) << 56) >> (inputx % 64);
+	    if clip && y + y_offset as usize >= height {
+		continue;
+	    }
+	    let line = (sprite_line as u128) << (128 - sprite_width);
+	    let wrapped_part = if clip {0} else {line << ((width - x) % 128)};
+	    let drawn_pixels = ((line >> x) | wrapped_part) & row_mask;
+	    let row = &mut self.planes[plane][(y + y_offset as usize) % height];
+	    collision |= (*row) & drawn_pixels != 0;
+	    (*row) ^= drawn_pixels;
+	}
+	return collision;
+    }
+
+    ///moves everything in the selected planes down by the given number of rows, the rows at the top become blank.
+    pub fn scroll_down(&mut self, rows: usize) {
+	let height = self.height();
+	for plane in self.selected_planes() {
+	    for y in (0..height).rev() {
+		self.planes[plane][y] = if y >= rows {self.planes[plane][y - rows]} else {0};
+	    }
+	}
+    }
+
+    ///moves everything in the selected planes up by the given number of rows, the rows at the bottom become blank.
+    pub fn scroll_up(&mut self, rows: usize) {
+	let height = self.height();
+	for plane in self.selected_planes() {
+	    for y in 0..height {
+		self.planes[plane][y] = if y + rows < height {self.planes[plane][y + rows]} else {0};
+	    }
+	}
+    }
+
+    ///moves everything in the selected planes right by the given number of pixels, the pixels at the left side become blank.
+    pub fn scroll_right(&mut self, pixels: usize) {
+	let row_mask = !0u128 << (128 - self.width());
+	for plane in self.selected_planes() {
+	    self.planes[plane].iter_mut().for_each(|row| *row = (*row >> pixels) & row_mask);
+	}
+    }
+
+    ///moves everything in the selected planes left by the given number of pixels, the pixels at the right side become blank.
+    pub fn scroll_left(&mut self, pixels: usize) {
+	for plane in self.selected_planes() {
+	    self.planes[plane].iter_mut().for_each(|row| *row <<= pixels);
 	}
     }
 
     ///updates the screen with the current latest buffer that is stored.
     ///uses the stored VideoDriver in order to accomplish the graphics.
     pub fn update_screen(&mut self) {
-	self.driver.draw(&self.buffer);
+	let frame = Frame {
+	    width: self.width(),
+	    height: self.height(),
+	    planes: &self.planes
+	};
+	self.driver.draw(&frame);
+    }
+
+    ///tells the VideoDriver that the frame is over, so it can show what was drawn.
+    pub fn present(&mut self) {
+	self.driver.present();
     }
 }
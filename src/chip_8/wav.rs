@@ -0,0 +1,229 @@
+//! This module writes the chip 8's sound into a WAV file, so it can be listened to or checked later without a sound card.
+//! The file is 16 bit mono PCM, the simplest kind of WAV there is. It is laid out like this, every number is little endian:
+//!  offset  size  contents
+//!  0       4     "RIFF"
+//!  4       4     the size of the rest of the file
+//!  8       4     "WAVE"
+//!  12      4     "fmt "
+//!  16      4     16, the size of the format chunk
+//!  20      2     1, PCM
+//!  22      2     1 channel
+//!  24      4     the sample rate
+//!  28      4     the bytes per second, the sample rate * 2
+//!  32      2     2, the bytes per sample
+//!  34      2     16 bits per sample
+//!  36      4     "data"
+//!  40      4     the size of the samples in bytes
+//!  44      n     the samples
+//! The two sizes are not known until the end, so they are filled in when the writer is dropped.
+
+use std::fs::File;
+use std::io::{self, BufWriter, Seek, SeekFrom, Write};
+use std::path::Path;
+use crate::chip_8::audio::{SampleStream, DEFAULT_SAMPLE_RATE};
+use crate::chip_8::timers::SoundDriver;
+
+///the sound timer ticks, and the driver is updated, 60 times a second.
+const TICKS_PER_SECOND: u64 = 60;
+///the size of everything before the samples.
+const HEADER_SIZE: u32 = 44;
+
+///Implements SoundDriver by writing every sample of the chip 8's sound into a WAV file, silence included, so the file lasts exactly
+/// as long as the program ran in emulated time, whatever speed it actually ran at.
+///Every tick is a sixtieth of a second of samples. Most sample rates dont divide into 60 evenly, so the ticks are a sample longer or shorter
+/// now and then to make up for it, and the samples never drift away from the ticks.
+///If writing the file fails, the problem is printed to stderr once, and the program carries on without it.
+pub struct WavWriter <W: Write + Seek> {
+    output: Option<W>, //None once writing has failed.
+    sample_rate: u32,
+    samples_written: u64,
+    ticks: u64,
+    buffer: Vec<i16>
+}
+
+impl WavWriter<BufWriter<File>> {
+    ///starts writing a WAV file at the default sample rate, 44100 samples a second.
+    pub fn create(path: &Path) -> io::Result<Self> {
+	return WavWriter::new(BufWriter::new(File::create(path)?), DEFAULT_SAMPLE_RATE);
+    }
+}
+
+impl <W: Write + Seek> WavWriter<W> {
+    ///starts writing a WAV file to the output, with the given sample rate. The header is written straight away, with the sizes left at 0.
+    pub fn new(mut output: W, sample_rate: u32) -> io::Result<Self> {
+	output.write_all(b"RIFF")?;
+	output.write_all(&(HEADER_SIZE - 8).to_le_bytes())?;
+	output.write_all(b"WAVEfmt ")?;
+	output.write_all(&16u32.to_le_bytes())?;
+	output.write_all(&1u16.to_le_bytes())?;
+	output.write_all(&1u16.to_le_bytes())?;
+	output.write_all(&sample_rate.to_le_bytes())?;
+	output.write_all(&(sample_rate * 2).to_le_bytes())?;
+	output.write_all(&2u16.to_le_bytes())?;
+	output.write_all(&16u16.to_le_bytes())?;
+	output.write_all(b"data")?;
+	output.write_all(&0u32.to_le_bytes())?;
+	return Ok(WavWriter {
+	    output: Some(output),
+	    sample_rate,
+	    samples_written: 0,
+	    ticks: 0,
+	    buffer: Vec::new()
+	});
+    }
+
+    ///returns how many samples have been written so far.
+    pub fn samples_written(&self) -> u64 {
+	return self.samples_written;
+    }
+
+    ///writes the samples of one tick. Going by the number of ticks so far, instead of adding up sample_rate / 60 every tick,
+    /// keeps the rounding from adding up.
+    fn write_tick(&mut self, stream: &mut SampleStream) -> io::Result<()> {
+	self.ticks += 1;
+	let samples = ((self.ticks * self.sample_rate as u64) / TICKS_PER_SECOND) - self.samples_written;
+	self.buffer.resize(samples as usize, 0);
+	stream.fill(&mut self.buffer);
+	let bytes = self.buffer.iter().flat_map(|sample| sample.to_le_bytes()).collect::<Vec<u8>>();
+	self.output.as_mut().expect("only writing while there is an output").write_all(&bytes)?;
+	self.samples_written += samples;
+	return Ok(());
+    }
+
+    ///fills in the sizes in the header, now that they are known.
+    fn finish(&mut self) -> io::Result<()> {
+	let data_size = (self.samples_written * 2).min((u32::MAX - HEADER_SIZE) as u64) as u32;
+	let output = self.output.as_mut().expect("only writing while there is an output");
+	output.seek(SeekFrom::Start(4))?;
+	output.write_all(&(data_size + HEADER_SIZE - 8).to_le_bytes())?;
+	output.seek(SeekFrom::Start(40))?;
+	output.write_all(&data_size.to_le_bytes())?;
+	return output.flush();
+    }
+}
+
+impl <W: Write + Seek> SoundDriver for WavWriter<W> {
+    fn update(&mut self, stream: &mut SampleStream) {
+	if self.output.is_some() {
+	    if let Err(error) = self.write_tick(stream) {
+		eprintln!("the sound recording stopped: {}", error);
+		self.output = None;
+	    }
+	}
+    }
+
+    fn sample_rate(&self) -> u32 {
+	return self.sample_rate;
+    }
+}
+
+impl <W: Write + Seek> Drop for WavWriter<W> {
+    fn drop(&mut self) {
+	if self.output.is_some() {
+	    if let Err(error) = self.finish() {
+		eprintln!("unable to finish the sound recording: {}", error);
+	    }
+	}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    ///writes the ticks of the stream into a WAV file in memory and returns the file, once the writer has finished it.
+    fn record(sample_rate: u32, stream: &mut SampleStream, ticks: usize) -> Vec<u8> {
+	let mut file = Vec::new();
+	let mut writer = WavWriter::new(Cursor::new(&mut file), sample_rate).expect("writing to memory works");
+	for _ in 0..ticks {
+	    writer.update(stream);
+	}
+	drop(writer);
+	return file;
+    }
+
+    fn u16_at(file: &[u8], offset: usize) -> u16 {
+	return u16::from_le_bytes([file[offset], file[offset + 1]]);
+    }
+
+    fn u32_at(file: &[u8], offset: usize) -> u32 {
+	return u32::from_le_bytes(file[offset..(offset + 4)].try_into().unwrap());
+    }
+
+    fn samples(file: &[u8]) -> Vec<i16> {
+	return file[HEADER_SIZE as usize..].chunks(2).map(|sample| i16::from_le_bytes([sample[0], sample[1]])).collect();
+    }
+
+    #[test]
+    fn the_header_describes_16_bit_mono_pcm() {
+	let file = record(22050, &mut SampleStream::new(22050), 0);
+	assert_eq!(file.len(), HEADER_SIZE as usize);
+	assert_eq!(&file[0..4], b"RIFF");
+	assert_eq!(u32_at(&file, 4), 36);
+	assert_eq!(&file[8..16], b"WAVEfmt ");
+	assert_eq!(u32_at(&file, 16), 16);
+	assert_eq!(u16_at(&file, 20), 1); //PCM
+	assert_eq!(u16_at(&file, 22), 1); //mono
+	assert_eq!(u32_at(&file, 24), 22050);
+	assert_eq!(u32_at(&file, 28), 44100);
+	assert_eq!(u16_at(&file, 32), 2);
+	assert_eq!(u16_at(&file, 34), 16);
+	assert_eq!(&file[36..40], b"data");
+	assert_eq!(u32_at(&file, 40), 0);
+    }
+
+    #[test]
+    fn the_sizes_are_filled_in_when_it_finishes() {
+	let file = record(DEFAULT_SAMPLE_RATE, &mut SampleStream::new(DEFAULT_SAMPLE_RATE), 60);
+	assert_eq!(file.len(), HEADER_SIZE as usize + 88200);
+	assert_eq!(u32_at(&file, 4), 88200 + 36);
+	assert_eq!(u32_at(&file, 40), 88200);
+	assert!(samples(&file).iter().all(|sample| *sample == 0), "the sound timer was never set, so it should all be silence");
+    }
+
+    #[test]
+    fn every_tick_is_a_sixtieth_of_a_second() {
+	for sample_rate in [8000, 22050, 44100, 48000] {
+	    let mut writer = WavWriter::new(Cursor::new(Vec::new()), sample_rate).expect("writing to memory works");
+	    let mut stream = SampleStream::new(sample_rate);
+	    let mut previous = 0;
+	    for tick in 1..=120 {
+		writer.update(&mut stream);
+		let samples = writer.samples_written() - previous;
+		assert!(samples == sample_rate as u64 / 60 || samples == (sample_rate as u64 / 60) + 1, "tick {} at {} Hz had {} samples", tick, sample_rate, samples);
+		assert_eq!(writer.samples_written(), (tick * sample_rate as u64) / 60, "the ticks drifted from the samples at {} Hz", sample_rate);
+		previous = writer.samples_written();
+	    }
+	}
+    }
+
+    #[test]
+    fn the_beep_is_a_square_wave() {
+	let mut stream = SampleStream::new(44000);
+	stream.set_sounding(true);
+	let samples = samples(&record(44000, &mut stream, 1));
+	assert_eq!(samples.len(), 733);
+	for (position, sample) in samples.iter().enumerate() {
+	    let expected = if position % 100 < 50 {8000} else {-8000}; //440 Hz at 44000 samples a second is 100 samples a period.
+	    assert_eq!(*sample, expected, "sample {}", position);
+	}
+    }
+
+    #[test]
+    fn an_xo_chip_pattern_is_played_bit_by_bit() {
+	let mut stream = SampleStream::new(8000);
+	let mut pattern = [0u8; 16];
+	pattern[0] = 0b1010_0000;
+	pattern[15] = 0b0000_0001;
+	stream.set_pattern(pattern);
+	stream.set_sounding(true);
+	let samples = samples(&record(8000, &mut stream, 2));
+	assert_eq!(samples.len(), 266);
+	for (position, sample) in samples.iter().enumerate() {
+	    let bit = (position / 2) % 128; //the default pitch plays 4000 bits a second, so every bit is 2 samples at 8000.
+	    let expected = if [0, 2, 127].contains(&bit) {8000} else {-8000};
+	    assert_eq!(*sample, expected, "sample {}", position);
+	}
+    }
+}
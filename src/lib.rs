@@ -1,3 +1,5 @@
+#![allow(clippy::needless_return, clippy::new_without_default)]
+
 
 pub mod chip_8;
 
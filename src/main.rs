@@ -1,39 +1,491 @@
-
 //! This is an emulator for the CHIP 8, written in Rust.
 //! I made this project mainly as a way to learn about Rust and it's features.
 //! Along the way, I definetly feel like I have gotten a hang of the basics of how rust works, suct as match statements,
 //!  ownership, unsafe blocks, etc. I didnt delve much into threads or macros though, maybe thats for another project.
 //!
 //! To start this program, you can just run "cargo run -- /path/to/.ch8/program" in order to run a specific program.
+//! Options can be given before or after the program path:
+//!  --quirks <vip|chip48|schip|xochip>    picks which interpreter's behavior the ambiguous instructions should follow (default vip).
+//!                                         SUPER-CHIP programs need schip (or xochip), the other two dont have the SUPER-CHIP instructions.
+//!  --ipf <count>                         how many instructions to run every frame (60 frames a second), 12 by default.
+//!                                         programs made for different interpreters want different speeds, usually between 7 and 30.
+//!  --debug                               starts the program paused in the debugger, type "help" at its prompt for the commands.
+//!  --headless                            runs the program as fast as it can without a screen or keyboard, then prints the screen, registers
+//!                                         and a hash of the screen. This is for testing programs in scripts.
+//!  --frames <count>                      how many frames (60 per second) to run the program for with --headless, 600 by default.
+//!  --keys <script>                       the keys to press with --headless, like "30:5,45:-" to hold key 5 from frame 30 to frame 45.
+//!                                         keys held together are joined with +, like "30:4+6".
+//!  --keymap <layout|file>                which keys press the chip 8 keys: qwerty (the default), azerty, dvorak, cosmac_vip, or a keymap file.
+//!                                         without it, a keymap file next to the program (program.ch8.keymap) is used if there is one.
+//!  --renderer <auto|blocks|halfblocks|braille>   how to draw the screen in the terminal. blocks needs 128x32 characters, halfblocks 64x16
+//!                                         and braille 32x8 (twice that for high resolution programs). auto, the default, picks the biggest that fits.
+//!  --theme <theme|file>                  draws the screen in color: classic (green), amber, octo, lcd, or a theme file. XO-CHIP planes get their own colors.
+//!                                         without it, a theme file next to the program (program.ch8.theme) is used if there is one.
+//!                                         true color is used if the COLORTERM environment variable says the terminal has it, otherwise 256 colors.
+//!  --seed <number>                       seeds the random numbers of CXNN, so the program gets the same ones every run.
+//!                                         without it the seed comes from the clock, except with --headless, which always uses 0
+//!                                         so that scripts get the same results every run. --headless prints the seed it used.
+//!  --screenshot-at-frame <frame>         with --headless, saves a picture of the screen once that many frames have run (0 is before the first).
+//!  --screenshot <file>                   where --screenshot-at-frame saves the picture, a .png or a .ppm file. program.ch8.frame<frame>.png by default.
+//!  --record <file>                       records the screen as the program runs into an animated .gif, or a .y4m video for ffmpeg.
+//!                                         it follows the chip 8's 60 frames a second, so it plays at the right speed even from --headless.
+//!  --wav <file>                          writes the sound into a 16 bit WAV file instead of beeping, silence and all, so it lasts as long as the
+//!                                         program ran for in emulated time. This works with --headless too, for checking a program's sound in tests.
+//!  --scale <number>                      how many pixels wide and tall each chip 8 pixel is in screenshots and recordings, 4 by default.
+//!                                         they are drawn in the --theme colors, or white on black without one.
+//! While running in the terminal, the = key saves a screenshot next to the program, like program.ch8.frame120.png.
+
+#![allow(clippy::needless_return)]
 
 use std::env;
-use std::fs::File;
-use chip_8_emulator::chip_8::{drivers, TimedRunner};
+use std::fs::{self, File};
+use std::io;
+use std::path::Path;
+use std::process;
+use chip_8_emulator::chip_8::{drivers, debugger, TimedRunner, Quirks, Keymap, Palette, Image, Recorder, VideoDriver, SoundDriver, WavWriter, XorShiftRandom, DEFAULT_INSTRUCTIONS_PER_FRAME};
+use chip_8_emulator::chip_8::drivers::TerminalCommand;
 
-///This is the main function for the emulator
-///the chip8's internal workings are all abstracted away into other modules, so this main function can be self expalnatory.
-fn main() {
-    println!("Starting emulator");
-    let args = env::args().collect::<Vec<String>>();
-    let input_file_name = {
-	if args.len() < 2 {
-	    panic!("program file location not provided, provide path to .ch8 program as first argument to this program to run it");
-	} else {
-	    &args[1]
+///The options that were given to the emulator on the command line.
+struct Options {
+    program_path: String,
+    quirks: Quirks,
+    instructions_per_frame: u32,
+    debug: bool,
+    headless: bool,
+    frames: u64,
+    keys: drivers::ScriptedKeyboard,
+    keymap: Option<String>,
+    renderer: drivers::Renderer,
+    theme: Option<String>,
+    seed: u64,
+    screenshot_at_frame: Option<u64>,
+    screenshot: Option<String>,
+    record: Option<String>,
+    wav: Option<String>,
+    scale: usize
+}
+
+///how many frames a headless run goes for if --frames is not given, 10 seconds.
+const DEFAULT_HEADLESS_FRAMES: u64 = 600;
+///the seed a headless run uses if --seed is not given. It is always the same, so two runs of a program print the same results.
+const DEFAULT_HEADLESS_SEED: u64 = 0;
+///how big each chip 8 pixel is in a screenshot or recording if --scale is not given, a low resolution screen is then 256x128.
+const DEFAULT_SCREENSHOT_SCALE: usize = 4;
+
+///printed after a problem with the arguments, the options are explained at the top of this file and in the README.
+const USAGE: &str = "usage: chip_8_emulator [--quirks <vip|chip48|schip|xochip>] [--ipf <count>] [--debug] [--headless] [--frames <count>]
+		       [--keys <script>] [--keymap <layout|file>] [--renderer <auto|blocks|halfblocks|braille>] [--theme <theme|file>]
+		       [--seed <number>] [--screenshot-at-frame <frame>] [--screenshot <file>] [--record <file>] [--wav <file>]
+		       [--scale <number>] <program.ch8>";
+
+///reads the command line arguments into an Options struct.
+///any problem with the arguments is returned as an Err() with a message for the user.
+fn parse_arguments(args: &[String]) -> Result<Options, String> {
+    let mut program_path = None;
+    let mut quirks = Quirks::default();
+    let mut instructions_per_frame = DEFAULT_INSTRUCTIONS_PER_FRAME;
+    let mut debug = false;
+    let mut headless = false;
+    let mut frames = DEFAULT_HEADLESS_FRAMES;
+    let mut keys = drivers::ScriptedKeyboard::new(Vec::new());
+    let mut keymap = None;
+    let mut renderer = drivers::Renderer::Auto;
+    let mut theme = None;
+    let mut seed = None;
+    let mut screenshot_at_frame = None;
+    let mut screenshot = None;
+    let mut record = None;
+    let mut wav = None;
+    let mut scale = DEFAULT_SCREENSHOT_SCALE;
+    let mut arguments = args.iter().skip(1);
+    while let Some(argument) = arguments.next() {
+	match argument.as_str() {
+	    "--quirks" => {
+		let name = arguments.next().ok_or("--quirks needs a preset name: vip, chip48, schip or xochip")?;
+		quirks = Quirks::from_name(name).ok_or(format!("unknown quirks preset \"{}\", expected vip, chip48, schip or xochip", name))?;
+	    },
+	    "--ipf" => {
+		let count = arguments.next().ok_or("--ipf needs the number of instructions to run every frame")?;
+		instructions_per_frame = count.parse::<u32>().ok().filter(|count| *count > 0).ok_or(format!("\"{}\" is not a number of instructions, it should be 1 or more", count))?;
+	    },
+	    "--debug" => debug = true,
+	    "--headless" => headless = true,
+	    "--frames" => {
+		let count = arguments.next().ok_or("--frames needs the number of frames to run for")?;
+		frames = count.parse::<u64>().map_err(|_| format!("\"{}\" is not a number of frames", count))?;
+	    },
+	    "--keys" => keys = drivers::ScriptedKeyboard::from_script(arguments.next().ok_or("--keys needs a script of keys, like 30:5,45:-")?)?,
+	    "--keymap" => keymap = Some(arguments.next().ok_or("--keymap needs a layout (qwerty, azerty, dvorak or cosmac_vip) or a keymap file")?.clone()),
+	    "--renderer" => {
+		let name = arguments.next().ok_or("--renderer needs a renderer: auto, blocks, halfblocks or braille")?;
+		renderer = drivers::Renderer::from_name(name).ok_or(format!("unknown renderer \"{}\", expected auto, blocks, halfblocks or braille", name))?;
+	    },
+	    "--theme" => theme = Some(arguments.next().ok_or("--theme needs a theme (mono, classic, amber, octo or lcd) or a theme file")?.clone()),
+	    "--seed" => {
+		let number = arguments.next().ok_or("--seed needs a number to seed the random numbers with")?;
+		seed = Some(number.parse::<u64>().map_err(|_| format!("\"{}\" is not a seed, it should be a number from 0 to {}", number, u64::MAX))?);
+	    },
+	    "--screenshot-at-frame" => {
+		let frame = arguments.next().ok_or("--screenshot-at-frame needs the frame to take the screenshot at")?;
+		screenshot_at_frame = Some(frame.parse::<u64>().map_err(|_| format!("\"{}\" is not a frame number", frame))?);
+	    },
+	    "--screenshot" => screenshot = Some(arguments.next().ok_or("--screenshot needs the file to save the screenshot to")?.clone()),
+	    "--record" => record = Some(arguments.next().ok_or("--record needs the file to record to, a .gif or a .y4m")?.clone()),
+	    "--wav" => wav = Some(arguments.next().ok_or("--wav needs the file to write the sound to")?.clone()),
+	    "--scale" => {
+		let number = arguments.next().ok_or("--scale needs how many pixels big each chip 8 pixel should be")?;
+		scale = number.parse::<usize>().ok().filter(|scale| (1..=64).contains(scale)).ok_or(format!("\"{}\" is not a scale, it should be a number from 1 to 64", number))?;
+	    },
+	    _ if argument.starts_with("--") => return Err(format!("unknown option {}", argument)),
+	    _ => program_path = Some(argument.clone())
+	}
+    }
+    return Ok(Options {
+	program_path: program_path.ok_or("program file location not provided, provide path to .ch8 program as an argument to this program to run it")?,
+	quirks,
+	instructions_per_frame,
+	debug,
+	headless,
+	frames,
+	keys,
+	keymap,
+	renderer,
+	theme,
+	seed: seed.unwrap_or_else(|| if headless {DEFAULT_HEADLESS_SEED} else {XorShiftRandom::seed_from_clock()}),
+	screenshot_at_frame,
+	screenshot,
+	record,
+	wav,
+	scale
+    });
+}
+
+///loads the program into the chip 8, or exits the emulator with a message if it cant be loaded.
+fn load_program(chip_8_system: &mut TimedRunner, program_file: File) {
+    println!("loading program...");
+    if let Err(error) = chip_8_system.init(program_file) {
+	eprintln!("unable to load the program: {}", error);
+	process::exit(1);
+    }
+}
+
+///how many frames back the chip 8 can be rewound, 10 seconds when playing and a minute in the debugger.
+const REWIND_FRAMES: usize = 60 * 10;
+const DEBUGGER_REWIND_FRAMES: usize = 60 * 60;
+///how many frames the rewind key goes back each time it is pressed.
+const FRAMES_PER_REWIND: usize = 60;
+
+///returns the file a save slot is kept in, it is next to the program, like "game.ch8.state1".
+fn save_slot_path(program_path: &str, slot: u8) -> String {
+    return format!("{}.state{}", program_path, slot);
+}
+
+///returns where the program's own keymap is kept, next to the program.
+fn keymap_path(program_path: &str) -> String {
+    return format!("{}.keymap", program_path);
+}
+
+///picks the keymap: the one given with --keymap (a layout name or a file), otherwise the program's own keymap file, otherwise QWERTY.
+fn choose_keymap(options: &Options) -> Result<Keymap, String> {
+    return choose_config(&options.keymap, &keymap_path(&options.program_path), Keymap::from_name, Keymap::load).map(Option::unwrap_or_default);
+}
+
+///returns where the program's own theme is kept, next to the program.
+fn theme_path(program_path: &str) -> String {
+    return format!("{}.theme", program_path);
+}
+
+///picks the colors: the theme given with --theme (a theme name or a file), otherwise the program's own theme file, otherwise none,
+/// in which case the screen is drawn in the terminal's own colors.
+fn choose_theme(options: &Options) -> Result<Option<Palette>, String> {
+    return choose_config(&options.theme, &theme_path(&options.program_path), Palette::from_name, Palette::load);
+}
+
+///picks a keymap or a theme: the one given with its option, which is a built-in name or else a file, otherwise the program's own file
+/// if there is one next to the program, otherwise None.
+fn choose_config<T>(given: &Option<String>, program_config: &str, from_name: fn(&str) -> Option<T>, load: fn(&Path) -> Result<T, String>) -> Result<Option<T>, String> {
+    if let Some(given) = given {
+	return match from_name(given) {
+	    Some(config) => Ok(Some(config)),
+	    None => load(Path::new(given)).map(Some)
+	};
+    }
+    if Path::new(program_config).exists() {
+	return load(Path::new(program_config)).map(Some);
+    }
+    return Ok(None);
+}
+
+///picks the colors for screenshots and recordings: the theme, like choose_theme(), or white on black if there is none.
+///if the theme cant be loaded, the emulator exits with a message.
+fn choose_picture_palette(options: &Options) -> Palette {
+    return match choose_theme(options) {
+	Ok(palette) => palette.unwrap_or_else(Palette::monochrome),
+	Err(message) => {
+	    eprintln!("unable to load the theme: {}", message);
+	    process::exit(1);
 	}
     };
-    
-    println!("taking input program location from provided 1st argument");
-    let program_file = File::open(input_file_name).expect("unable to find the program file specified");
+}
+
+///wraps the display in a Recorder if --record was given, so everything it shows is recorded too.
+///if the recording cant be started, the emulator exits with a message.
+fn with_recorder<'a, D: VideoDriver + 'a>(display: D, options: &Options, palette: Palette) -> Box<dyn VideoDriver + 'a> {
+    let Some(path) = &options.record else {
+	return Box::new(display);
+    };
+    return match Recorder::create(display, Path::new(path), palette, options.scale) {
+	Ok(recorder) => Box::new(recorder),
+	Err(error) => {
+	    eprintln!("unable to record to {}: {}", path, error);
+	    process::exit(1);
+	}
+    };
+}
+
+///writes the sound into a WAV file if --wav was given, otherwise it is played with the given driver.
+///if the file cant be made, the emulator exits with a message.
+fn choose_sound<'a, S: SoundDriver + 'a>(sound: S, options: &Options) -> Box<dyn SoundDriver + 'a> {
+    let Some(path) = &options.wav else {
+	return Box::new(sound);
+    };
+    return match WavWriter::create(Path::new(path)) {
+	Ok(writer) => Box::new(writer),
+	Err(error) => {
+	    eprintln!("unable to write the sound to {}: {}", path, error);
+	    process::exit(1);
+	}
+    };
+}
+
+///returns where a screenshot of the given frame is saved if no file is given for it, next to the program, like "game.ch8.frame120.png".
+fn screenshot_path(program_path: &str, frame: u64) -> String {
+    return format!("{}.frame{}.png", program_path, frame);
+}
+
+///saves a picture of the screen as it is now, in the format that goes with the file's extension.
+fn save_screenshot(chip_8_system: &TimedRunner, path: &str, palette: &Palette, scale: usize) -> io::Result<()> {
+    return Image::from_frame(&chip_8_system.system().screen(), palette, scale).save(Path::new(path));
+}
+
+///saves or loads a state in one of the numbered slots, rewinds a second or saves a screenshot. If that does not work, the chip 8 carries on
+/// and the problem is printed. Screenshots are drawn in the palette, scaled up by the --scale in the options.
+///returns false if the command was to quit.
+fn handle_command(chip_8_system: &mut TimedRunner, options: &Options, palette: &Palette, command: TerminalCommand) -> bool {
+    let program_path = options.program_path.as_str();
+    match command {
+	TerminalCommand::SaveState(slot) => {
+	    let path = save_slot_path(program_path, slot);
+	    match fs::write(&path, chip_8_system.save_state()) {
+		Ok(()) => eprintln!("saved the state to {}", path),
+		Err(error) => eprintln!("unable to save the state to {}: {}", path, error)
+	    }
+	},
+	TerminalCommand::Rewind => {
+	    if let Err(error) = chip_8_system.rewind(FRAMES_PER_REWIND) {
+		eprintln!("unable to rewind: {}", error);
+	    }
+	},
+	TerminalCommand::Screenshot => {
+	    let path = screenshot_path(program_path, chip_8_system.frames_elapsed());
+	    match save_screenshot(chip_8_system, &path, palette, options.scale) {
+		Ok(()) => eprintln!("saved a screenshot to {}", path),
+		Err(error) => eprintln!("unable to save a screenshot to {}: {}", path, error)
+	    }
+	},
+	TerminalCommand::LoadState(slot) => {
+	    let path = save_slot_path(program_path, slot);
+	    let loaded = fs::read(&path)
+		.map_err(|error| error.into())
+		.and_then(|state| chip_8_system.load_state(&state));
+	    match loaded {
+		Ok(()) => eprintln!("loaded the state from {}", path),
+		Err(error) => eprintln!("unable to load the state from {}: {}", path, error)
+	    }
+	},
+	TerminalCommand::Quit => return false
+    }
+    return true;
+}
 
+///runs the program in the terminal, with the screen drawn in the terminal and the keyboard read from stdin.
+///the number keys 5 to 9 save the state into slots 1 to 5, and with shift held they load it back. The - key rewinds a second,
+/// and the = key saves a screenshot.
+///Esc, ctrl-c or SIGTERM stop the emulator, and the terminal is put back the way it was however it stops.
+fn run_in_terminal(options: &Options, program_file: File) {
+    let keymap = match choose_keymap(options) {
+	Ok(keymap) => keymap,
+	Err(message) => {
+	    eprintln!("unable to load the keymap: {}", message);
+	    process::exit(1);
+	}
+    };
+    let palette = match choose_theme(options) {
+	Ok(palette) => palette,
+	Err(message) => {
+	    eprintln!("unable to load the theme: {}", message);
+	    process::exit(1);
+	}
+    };
+    let screenshot_palette = palette.unwrap_or_else(Palette::monochrome);
+    let terminal = drivers::TerminalGuard::new();
     println!("initializing chip8 decoder");
-    let mut chip_8_system = TimedRunner::new(drivers::StdoutDisplay::new(), drivers::TerminalBeep::new(), drivers::StdinKeysender::new()); 
-    
-    println!("loading program...");
-    chip_8_system.init(program_file);
+    let mut keysender = drivers::StdinKeysender::new(keymap);
+    let commands = keysender.take_commands().expect("the save slot commands were already taken");
+    let display = with_recorder(drivers::StdoutDisplay::new(options.renderer, palette), options, screenshot_palette);
+    let mut chip_8_system = TimedRunner::new(display, choose_sound(drivers::TerminalBeep::new(), options), keysender, options.quirks);
+    chip_8_system.set_instructions_per_frame(options.instructions_per_frame);
+    chip_8_system.set_seed(options.seed);
+    load_program(&mut chip_8_system, program_file);
+    chip_8_system.enable_rewind(REWIND_FRAMES);
 
     println!("starting decode loop");
+    let mut running = true;
+    let mut failure = None;
+    while running && !terminal.quit_requested() {
+	if chip_8_system.is_halted() {
+	    break;
+	}
+	if let Err(error) = chip_8_system.run_frame_timed(1f64) {
+	    failure = Some(error);
+	    break;
+	}
+	while let Ok(command) = commands.try_recv() {
+	    running = running && handle_command(&mut chip_8_system, options, &screenshot_palette, command);
+	}
+    }
+    let halted = chip_8_system.is_halted();
+    drop(chip_8_system); //the drivers go before the terminal is put back, so nothing is drawn after.
+    drop(terminal);
+    if let Some(error) = failure {
+	eprintln!("the chip 8 stopped: {}", error);
+	process::exit(1);
+    }
+    println!("{}", if halted {"program exited"} else {"emulator stopped"});
+}
+
+///runs the program paused in the debugger, which takes its commands from stdin.
+fn run_in_debugger(options: &Options, program_file: File) {
+    debugger::install_interrupt_handler();
+    let mut chip_debugger = debugger::Debugger::new();
+    let display = match options.record {
+	Some(_) => with_recorder(chip_debugger.display(), options, choose_picture_palette(options)),
+	None => Box::new(chip_debugger.display())
+    };
+    let mut chip_8_system = TimedRunner::new(display, choose_sound(drivers::TerminalBeep::new(), options), chip_debugger.keypad(), options.quirks);
+    chip_8_system.set_instructions_per_frame(options.instructions_per_frame);
+    chip_8_system.set_seed(options.seed);
+    load_program(&mut chip_8_system, program_file);
+    chip_8_system.enable_rewind(DEBUGGER_REWIND_FRAMES);
+
+    let result = chip_debugger.run(&mut chip_8_system, io::stdin().lock(), io::stdout());
+    drop(chip_8_system); //finishes the recordings, if there are any, which exiting would skip.
+    if let Err(error) = result {
+	eprintln!("the debugger stopped: {}", error);
+	process::exit(1);
+    }
+}
+
+///runs the program headless for the number of frames in the options, as fast as possible, pressing the keys from the --keys script.
+///Afterwards everything needed to check what the program did is printed: the screen, the registers and a hash of the screen.
+///If the program fails, the same is printed and then the emulator exits with code 1.
+///With --screenshot-at-frame, a picture of the screen is saved when that frame is reached, and where it went is printed to stderr.
+fn run_headless(options: Options, program_file: File) {
+    let frames = options.frames;
+    let seed = options.seed;
+    let palette = if options.screenshot_at_frame.is_some() || options.record.is_some() {choose_picture_palette(&options)} else {Palette::monochrome()};
+    let screenshot = options.screenshot_at_frame.map(|frame| {
+	(frame, options.screenshot.clone().unwrap_or_else(|| screenshot_path(&options.program_path, frame)))
+    });
+    let scale = options.scale;
+    let display = with_recorder(drivers::NullDisplay::new(), &options, palette);
+    let frame_counter = options.keys.frame_counter();
+    let sound = choose_sound(drivers::NullSound::new(), &options);
+    let mut chip_8_system = TimedRunner::new(display, sound, options.keys, options.quirks);
+    chip_8_system.set_instructions_per_frame(options.instructions_per_frame);
+    chip_8_system.set_seed(options.seed);
+    if let Err(error) = chip_8_system.init(program_file) { //not load_program(), so that only the results go to stdout.
+	eprintln!("unable to load the program: {}", error);
+	process::exit(1);
+    }
+
+    let mut failure = None;
+    let mut screenshot_taken = false;
     loop {
-	chip_8_system.decode_next_timed(1f64);
+	if let Some((frame, path)) = &screenshot {
+	    if *frame == chip_8_system.frames_elapsed() {
+		if let Err(error) = save_screenshot(&chip_8_system, path, &palette, scale) {
+		    eprintln!("unable to save a screenshot to {}: {}", path, error);
+		    process::exit(1);
+		}
+		eprintln!("saved a screenshot of frame {} to {}", frame, path);
+		screenshot_taken = true;
+	    }
+	}
+	if chip_8_system.frames_elapsed() >= frames || chip_8_system.is_halted() {
+	    break;
+	}
+	if let Err(error) = chip_8_system.run_frame() {
+	    failure = Some(error);
+	    break;
+	}
+	frame_counter.set(chip_8_system.frames_elapsed());
+    }
+    if let Some((frame, _)) = &screenshot {
+	if !screenshot_taken {
+	    eprintln!("no screenshot was taken, the run stopped at frame {} before it got to frame {}", chip_8_system.frames_elapsed(), frame);
+	}
+    }
+
+    let system = chip_8_system.system();
+    let screen = system.screen();
+    println!("frames: {}", chip_8_system.frames_elapsed());
+    println!("instructions: {}", chip_8_system.instructions_executed());
+    println!("halted: {}", chip_8_system.is_halted());
+    println!("{}", debugger::format_registers(system));
+    let stack = system.stack().iter().map(|address| format!("{:#06x}", address)).collect::<Vec<String>>();
+    println!("stack: {}", if stack.is_empty() {"empty".to_string()} else {stack.join(" ")});
+    println!("timers: delay={} sound={}", system.timers().0, system.timers().1);
+    println!("frame hash: {:#018x}", screen.hash());
+    println!("screen: {}x{}", screen.width, screen.height);
+    print!("{}", screen);
+    println!("seed: {}", seed);
+    drop(chip_8_system); //finishes the recordings, if there are any, which exiting would skip.
+    if let Some(error) = failure {
+	eprintln!("the chip 8 stopped: {}", error);
+	process::exit(1);
+    }
+}
+
+///This is the main function for the emulator
+///the chip8's internal workings are all abstracted away into other modules, so this main function can be self expalnatory.
+fn main() {
+    let args = env::args().collect::<Vec<String>>();
+    let options = match parse_arguments(&args) {
+	Ok(options) => options,
+	Err(message) => {
+	    eprintln!("{}", message);
+	    eprintln!("{}", USAGE);
+	    process::exit(2);
+	}
+    };
+    let program_file = match File::open(&options.program_path) {
+	Ok(program_file) => program_file,
+	Err(error) => {
+	    eprintln!("unable to open the program file {}: {}", options.program_path, error);
+	    process::exit(1);
+	}
+    };
+    if options.headless {
+	run_headless(options, program_file); //nothing but the results are printed, so scripts can read them.
+	return;
+    }
+
+    println!("Starting emulator");
+    println!("taking input program location from provided argument");
+    if options.debug {
+	run_in_debugger(&options, program_file);
+    } else {
+	run_in_terminal(&options, program_file);
     }
 }